@@ -0,0 +1,125 @@
+//! Compares the similarity search strategies on a synthetic dictionary.
+//!
+//! Run with `cargo bench --bench similarity_search`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use img_hash_linker::algorithm::bk_tree::BkTree;
+use img_hash_linker::algorithm::distance_metric::{Hamming, LegacyByteDifference};
+use img_hash_linker::algorithm::hash_dictionary::HashDictionary;
+use img_hash_linker::algorithm::hash_index::HashIndex;
+use img_hash_linker::algorithm::hash_proximity::{
+    find_top_k_similar_hashes, try_finding_similar_hash,
+};
+use img_hash_linker::algorithm::multi_index::MultiIndex;
+
+const ENTRY_COUNT: usize = 200_000;
+const QUERY_COUNT: usize = 200;
+const RADIUS: u32 = 3;
+
+/// Small deterministic xorshift generator, so runs are comparable
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn time<T>(label: &str, queries: &[String], mut search: impl FnMut(&str) -> T) {
+    let start: Instant = Instant::now();
+    for query in queries {
+        black_box(search(query));
+    }
+    let elapsed: Duration = start.elapsed();
+
+    println!(
+        "{:<12} {:>10.3} ms total, {:>10.1} us/query",
+        label,
+        elapsed.as_secs_f64() * 1000.0,
+        elapsed.as_secs_f64() * 1_000_000.0 / queries.len() as f64
+    );
+}
+
+fn main() {
+    let mut rng: Rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    let links: Vec<(String, String)> = (0..ENTRY_COUNT)
+        .map(|i| {
+            (
+                format!("{:016x}", rng.next()),
+                format!("https://example.com/{}", i),
+            )
+        })
+        .collect();
+
+    // Queries are dictionary hashes with a few flipped bits
+    let queries: Vec<String> = (0..QUERY_COUNT)
+        .map(|_| {
+            let (hash, _) = &links[rng.next() as usize % ENTRY_COUNT];
+            let mut value: u64 = u64::from_str_radix(hash, 16).unwrap();
+            for _ in 0..rng.next() % (RADIUS as u64 + 1) {
+                value ^= 1 << (rng.next() % 64);
+            }
+            format!("{:016x}", value)
+        })
+        .collect();
+
+    println!(
+        "{} entries, {} queries, radius {} bits",
+        ENTRY_COUNT, QUERY_COUNT, RADIUS
+    );
+
+    let start: Instant = Instant::now();
+    let dictionary: HashDictionary = HashDictionary::from_entries(&links).unwrap();
+    println!(
+        "Packed dictionary built in {:.3} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+
+    let start: Instant = Instant::now();
+    let bk_tree: BkTree = BkTree::from_entries(&links).unwrap();
+    println!(
+        "BK-tree built in {:.3} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+
+    let start: Instant = Instant::now();
+    let multi_index: MultiIndex = MultiIndex::from_entries(&links, 4).unwrap();
+    println!(
+        "MIH built in {:.3} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+
+    // Proximity threshold matching the radius on 64-bit hashes
+    let threshold: f64 = 1.0 - RADIUS as f64 / 64.0;
+
+    // Linear scans over the hex strings, parsing each hash once per search
+    time("linear", &queries, |query| {
+        try_finding_similar_hash(query.to_string(), &links, threshold)
+    });
+    time("top-k-scan", &queries, |query| {
+        find_top_k_similar_hashes(query, &links, usize::MAX, threshold, &Hamming)
+    });
+    // The legacy metric still compares the hex strings at each comparison
+    time("legacy-scan", &queries, |query| {
+        find_top_k_similar_hashes(query, &links, usize::MAX, threshold, &LegacyByteDifference)
+    });
+
+    // Linear scan over hashes parsed once into packed words
+    time("packed-scan", &queries, |query| {
+        dictionary.find_top_k_similar_hashes(query, usize::MAX, threshold)
+    });
+
+    // Indexes
+    time("bk-tree", &queries, |query| {
+        bk_tree.find_within(query, RADIUS)
+    });
+    time("mih", &queries, |query| {
+        multi_index.find_within(query, RADIUS)
+    });
+}
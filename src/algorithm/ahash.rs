@@ -1,6 +1,8 @@
 use fast_image_resize::{self as fir, Resizer};
 use image::DynamicImage;
 
+use crate::algorithm::hash_information::HashInformation;
+
 /// Computes the average hash (aHash) of an image.
 ///
 /// The algorithm:
@@ -10,6 +12,17 @@ use image::DynamicImage;
 /// 4. Compare each pixel to the average and set bits accordingly
 /// 5. Return a hash as a hexadecimal string
 pub fn compute_image_hash(img: &DynamicImage, hash_size: impl Into<Option<u32>>) -> String {
+    compute_image_hash_with_information(img, hash_size).0
+}
+
+/// Computes the average hash (aHash) of an image, along with how much information it carries.
+///
+/// See [`compute_image_hash`] for the algorithm, and [`HashInformation::from_grid`] for the
+/// information score.
+pub fn compute_image_hash_with_information(
+    img: &DynamicImage,
+    hash_size: impl Into<Option<u32>>,
+) -> (String, HashInformation) {
     // Get hash_size with default value of 8
     let hash_size = hash_size.into().unwrap_or(8);
 
@@ -35,18 +48,120 @@ pub fn compute_image_hash(img: &DynamicImage, hash_size: impl Into<Option<u32>>)
     for &p in pixels {
         sum += p as u32;
     }
-    let total_pixels = (hash_size * hash_size) as u32;
+    let total_pixels: u32 = hash_size * hash_size;
     let avg: u32 = sum / total_pixels;
 
-    // Create hash (size depends on number of pixels)
-    let mut hash: u64 = 0;
-    for (i, &pixel) in pixels.iter().enumerate() {
-        if pixel as u32 >= avg {
-            hash |= 1 << i;
+    // Create hash (size depends on number of pixels), bit i being set if pixel i is bright
+    let bits: Vec<bool> = pixels.iter().map(|&pixel| pixel as u32 >= avg).collect();
+
+    // Convert to hex string (width determined by number of bits needed),
+    // the last hex digit holding bits 0 to 3
+    let hex_width = hex_len_for_hash_size(hash_size); // Round up to nearest hex digit
+    let hash: String = (0..hex_width)
+        .rev()
+        .map(|nibble| {
+            let value: u32 = (0..4)
+                .filter(|bit| bits.get(nibble * 4 + bit).copied().unwrap_or(false))
+                .map(|bit| 1 << bit)
+                .sum();
+            char::from_digit(value, 16).unwrap()
+        })
+        .collect();
+
+    // Safe to unwrap since the hash was just built from hex digits
+    let information: HashInformation = HashInformation::from_grid(&hash, pixels).unwrap();
+    (hash, information)
+}
+
+/// Number of hex characters of a hash computed with the given hash size
+pub fn hex_len_for_hash_size(hash_size: u32) -> usize {
+    (hash_size * hash_size).div_ceil(4) as usize
+}
+
+/// The hash size giving hashes of `hex_len` hex characters, if any
+///
+/// Hash sizes from 2 upwards all give hashes of different lengths, so the size of a hash
+/// can be told from its length (a size of 1 gives the same length as a size of 2).
+pub fn hash_size_for_hex_len(hex_len: usize) -> Option<u32> {
+    (2u32..)
+        .map(|hash_size| (hash_size, hex_len_for_hash_size(hash_size)))
+        .take_while(|&(_, len)| len <= hex_len)
+        .find(|&(_, len)| len == hex_len)
+        .map(|(hash_size, _)| hash_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    /// A gray image of `size`×`size` pixels, hashed at the same size so that its
+    /// pixels are the grid of the hash
+    fn grid(size: u32, pixel: impl Fn(u32, u32) -> u8) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(size, size, |x, y| Luma([pixel(x, y)])))
+    }
+
+    /// The encoding used before hashes larger than 8×8 were supported, through a `u64`
+    fn legacy_hash(image: &GrayImage) -> String {
+        let pixels: &[u8] = image.as_raw();
+        let avg: u32 = pixels.iter().map(|&p| p as u32).sum::<u32>() / pixels.len() as u32;
+
+        let mut hash: u64 = 0;
+        for (i, &pixel) in pixels.iter().enumerate() {
+            if pixel as u32 >= avg {
+                hash |= 1 << i;
+            }
         }
+        let width: usize = pixels.len().div_ceil(4);
+        format!("{:0width$x}", hash, width = width)
+    }
+
+    #[test]
+    fn sizes_up_to_8_encode_as_before() {
+        for size in 2..=8 {
+            let image: DynamicImage = grid(size, |x, y| ((x * 37 + y * 91) % 256) as u8);
+            assert_eq!(
+                compute_image_hash(&image, size),
+                legacy_hash(&image.to_luma8()),
+                "hash size {}",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn hash_size_16_does_not_overflow() {
+        // Only the last row is bright: bits 240 to 255, the first 4 hex characters
+        let image: DynamicImage = grid(16, |_, y| if y == 15 { 255 } else { 0 });
+        let hash: String = compute_image_hash(&image, 16);
+
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, format!("ffff{}", "0".repeat(60)));
+
+        // Only the first pixel is dark: every bit but bit 0
+        let image: DynamicImage = grid(16, |x, y| if x == 0 && y == 0 { 0 } else { 255 });
+        assert_eq!(
+            compute_image_hash(&image, 16),
+            format!("{}e", "f".repeat(63))
+        );
     }
 
-    // Convert to hex string (width determined by number of bits needed)
-    let hex_width = ((hash_size * hash_size + 3) / 4) as usize; // Round up to nearest hex digit
-    format!("{:0width$x}", hash, width = hex_width)
+    #[test]
+    fn odd_sizes_have_padding_digits() {
+        // 9 bits, all set: the padding bits of the first hex character stay unset
+        let image: DynamicImage = grid(3, |_, _| 128);
+        assert_eq!(compute_image_hash(&image, 3), "1ff");
+    }
+
+    #[test]
+    fn hash_sizes_are_told_from_lengths() {
+        for size in 2..=32 {
+            assert_eq!(
+                hash_size_for_hex_len(hex_len_for_hash_size(size)),
+                Some(size)
+            );
+        }
+        assert_eq!(hash_size_for_hex_len(0), None);
+        assert_eq!(hash_size_for_hex_len(8), None);
+    }
 }
@@ -0,0 +1,265 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use crate::algorithm::hash_dictionary::HashDictionary;
+use crate::algorithm::hash_information::DegenerateHashCheck;
+use crate::algorithm::hash_proximity::MatchOutcome;
+use crate::{Preprocessing, compute_hashes_with_information};
+
+/// Options for the batch lookups.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BatchOptions {
+    /// The processing applied to the images before hashing (default: white border removal)
+    pub preprocessing: Preprocessing,
+    /// The hash size (default: every hash size present in the dictionary, or 8 if it is empty)
+    pub hash_size: Option<u32>,
+    /// The minimum proximity to consider a hash similar (default: 0.95)
+    pub proximity_threshold: Option<f64>,
+    /// The smallest proximity gap between the best match and the runner-up (default: 0.01)
+    pub ambiguity_margin: Option<f64>,
+    /// What to do with images giving degenerate hashes (default: warn)
+    pub degenerate: DegenerateHashCheck,
+}
+
+/// The lookup result of one query of a batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchResult {
+    /// The image the query was made for
+    pub path: PathBuf,
+    /// The hashes of the image, one per hash size searched
+    pub hashes: Vec<String>,
+    /// The outcome of the lookup
+    pub outcome: MatchOutcome,
+    /// Warnings about degenerate hashes
+    pub warnings: Vec<String>,
+}
+
+/// Called with the number of completed queries and the total number of queries.
+///
+/// Queries complete in any order, so calls may come from any thread.
+pub type ProgressCallback<'a> = &'a (dyn Fn(usize, usize) + Sync);
+
+/// Hashes many images and looks them up in a dictionary, in parallel.
+///
+/// The dictionary is parsed once and shared by all the queries. Each image is hashed at
+/// every hash size present in the dictionary, unless `options.hash_size` is set. Results are returned
+/// in the same order as `paths`, whatever the order queries complete in; a query that
+/// fails (e.g. an unreadable image, or a degenerate hash with the `Reject` policy) gives
+/// an error without stopping the others.
+///
+/// # Examples
+///
+/// ```no_run
+/// use img_hash_linker::algorithm::batch::{BatchOptions, batch_find_matches};
+/// use img_hash_linker::algorithm::hash_dictionary::HashDictionary;
+/// use img_hash_linker::data_handle::load_csv::load_entries_from_csv;
+///
+/// let dictionary = HashDictionary::from_entries(&load_entries_from_csv("links.csv".to_string())?)?;
+/// let paths = vec!["image1.jpg", "image2.jpg"];
+///
+/// let progress = |done: usize, total: usize| eprintln!("{}/{}", done, total);
+/// for result in batch_find_matches(&paths, &dictionary, &BatchOptions::default(), Some(&progress)) {
+///     println!("{:?}", result);
+/// }
+/// # Ok::<(), String>(())
+/// ```
+pub fn batch_find_matches<P: AsRef<Path> + Sync>(
+    paths: &[P],
+    dictionary: &HashDictionary,
+    options: &BatchOptions,
+    progress: Option<ProgressCallback>,
+) -> Vec<Result<BatchResult, String>> {
+    let hash_sizes: Vec<u32> = match options.hash_size {
+        Some(hash_size) => vec![hash_size],
+        None if dictionary.is_empty() => vec![8],
+        None => dictionary.hash_sizes(),
+    };
+
+    run_batch(paths, progress, |path| {
+        let path: &Path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|e| format!("Failed to open image {}: {}", path.display(), e))?;
+        let mut hashes: Vec<String> = Vec::new();
+        let mut warnings: Vec<String> = Vec::new();
+        for (hash, information) in
+            compute_hashes_with_information(image, &options.preprocessing, &hash_sizes)?
+        {
+            if let Some(warning) = options.degenerate.check(&hash, &information)? {
+                warnings.push(warning);
+            }
+            hashes.push(hash);
+        }
+
+        let outcome: MatchOutcome = dictionary.find_match_for_hashes(
+            &hashes,
+            options.proximity_threshold,
+            options.ambiguity_margin,
+        )?;
+
+        Ok(BatchResult {
+            path: path.to_path_buf(),
+            hashes,
+            outcome,
+            warnings,
+        })
+    })
+}
+
+/// Looks many already computed hashes up in a dictionary, in parallel.
+///
+/// Results are returned in the same order as `hashes`.
+pub fn batch_find_hash_matches(
+    hashes: &[String],
+    dictionary: &HashDictionary,
+    options: &BatchOptions,
+    progress: Option<ProgressCallback>,
+) -> Vec<Result<MatchOutcome, String>> {
+    run_batch(hashes, progress, |hash| {
+        dictionary.find_match(hash, options.proximity_threshold, options.ambiguity_margin)
+    })
+}
+
+/// Runs `query` on every item in parallel, keeping the order of the items
+fn run_batch<T: Sync, R: Send>(
+    items: &[T],
+    progress: Option<ProgressCallback>,
+    query: impl Fn(&T) -> Result<R, String> + Sync,
+) -> Vec<Result<R, String>> {
+    let completed: AtomicUsize = AtomicUsize::new(0);
+
+    items
+        .par_iter()
+        .map(|item| {
+            let result: Result<R, String> = query(item);
+            if let Some(progress) = progress {
+                progress(completed.fetch_add(1, Ordering::Relaxed) + 1, items.len());
+            }
+            result
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_hash_with_preprocessing;
+    use crate::test_support::temp_path;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    fn dictionary() -> HashDictionary {
+        HashDictionary::from_entries(&[
+            (
+                "ffff0000ffff0000".to_string(),
+                "https://example.com".to_string(),
+            ),
+            (
+                "0000ffff0000ffff".to_string(),
+                "https://another.com".to_string(),
+            ),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn results_keep_the_order_of_the_queries() {
+        let hashes: Vec<String> = (0..64)
+            .map(|i| {
+                if i % 2 == 0 {
+                    "ffff0000ffff0000".to_string()
+                } else {
+                    "0000ffff0000ffff".to_string()
+                }
+            })
+            .collect();
+
+        let results: Vec<Result<MatchOutcome, String>> =
+            batch_find_hash_matches(&hashes, &dictionary(), &BatchOptions::default(), None);
+
+        assert_eq!(results.len(), hashes.len());
+        for (hash, result) in hashes.iter().zip(results) {
+            assert!(matches!(result, Ok(MatchOutcome::Exact(found)) if found.hash == *hash));
+        }
+    }
+
+    #[test]
+    fn failed_queries_do_not_stop_the_others() {
+        let hashes: Vec<String> = vec![
+            "ffff0000ffff0000".to_string(),
+            "not a hash".to_string(),
+            "0000ffff0000ffff".to_string(),
+        ];
+        let completed: AtomicUsize = AtomicUsize::new(0);
+        let progress = |_: usize, total: usize| {
+            assert_eq!(total, 3);
+            completed.fetch_add(1, Ordering::Relaxed);
+        };
+
+        let results: Vec<Result<MatchOutcome, String>> = batch_find_hash_matches(
+            &hashes,
+            &dictionary(),
+            &BatchOptions::default(),
+            Some(&progress),
+        );
+
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+        assert_eq!(completed.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn empty_batches_report_no_progress() {
+        let progress = |_: usize, _: usize| panic!("No query should complete");
+        let paths: Vec<PathBuf> = Vec::new();
+
+        assert!(
+            batch_find_hash_matches(
+                &[],
+                &dictionary(),
+                &BatchOptions::default(),
+                Some(&progress)
+            )
+            .is_empty()
+        );
+        assert!(
+            batch_find_matches(
+                &paths,
+                &dictionary(),
+                &BatchOptions::default(),
+                Some(&progress)
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn images_are_hashed_at_the_sizes_of_the_dictionary() {
+        let image: DynamicImage = DynamicImage::ImageRgb8(RgbImage::from_fn(33, 17, |x, y| {
+            Rgb([(x * 7) as u8, (y * 15) as u8, ((x + y) * 5) as u8])
+        }));
+        let path: PathBuf = temp_path("batch.png");
+        image.save(&path).unwrap();
+
+        let options: BatchOptions = BatchOptions::default();
+        let hash: String =
+            compute_hash_with_preprocessing(image, &options.preprocessing, Some(4)).unwrap();
+        let dictionary: HashDictionary =
+            HashDictionary::from_entries(&[(hash.clone(), "https://example.com".to_string())])
+                .unwrap();
+
+        let paths: Vec<PathBuf> = vec![path.clone(), path.with_extension("missing.png")];
+        let results: Vec<Result<BatchResult, String>> =
+            batch_find_matches(&paths, &dictionary, &options, None);
+        std::fs::remove_file(&path).unwrap();
+
+        let found: &BatchResult = results[0].as_ref().unwrap();
+        assert_eq!(found.path, path);
+        assert_eq!(found.hashes, vec![hash]);
+        assert!(
+            matches!(&found.outcome, MatchOutcome::Exact(m) if m.link == "https://example.com")
+        );
+        assert!(results[1].is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use crate::algorithm::distance_metric::WeightedBits;
+use crate::algorithm::packed_hash::PackedHash;
+use crate::data_handle::dictionary_entry::HashEntry;
+
+/// The smallest weight given to a bit, so that bits constant across the collection
+/// still count a little, and a collection of identical hashes still tells hashes apart.
+pub const MIN_BIT_WEIGHT: f64 = 0.05;
+
+/// Computes the weight of each bit position from the hashes of a collection.
+///
+/// A bit that is set in about half of the hashes tells them apart well, while a bit
+/// that is almost always set (or almost never set), such as the bright center of most
+/// pictures, carries little information. Each bit is weighted by its entropy across the
+/// collection, from [`MIN_BIT_WEIGHT`] to 1.0, for use with the [`WeightedBits`] metric.
+///
+/// Only the hashes of `hash_len` hex characters are used, since each hash size has
+/// its own bit positions.
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::bit_weights::compute_bit_weights;
+/// use img_hash_linker::algorithm::distance_metric::DistanceMetric;
+///
+/// let links = vec![
+///     ("f0".to_string(), "https://example.com".to_string()),
+///     ("f3".to_string(), "https://another.com".to_string()),
+///     ("fc".to_string(), "https://third.com".to_string()),
+/// ];
+///
+/// // The first hex character is the same in every hash, so its bits weigh little
+/// let weights = compute_bit_weights(&links, 2)?;
+/// assert!(weights.weights[7] < weights.weights[0]);
+///
+/// // Differing on the constant bits matters less than differing on the others
+/// assert!(weights.distance("70", "f0")? < weights.distance("f1", "f0")?);
+/// # Ok::<(), String>(())
+/// ```
+pub fn compute_bit_weights<E: HashEntry>(
+    links: &[E],
+    hash_len: usize,
+) -> Result<WeightedBits, String> {
+    let bit_count: usize = hash_len * 4;
+    let mut set_counts: Vec<usize> = vec![0; bit_count];
+    let mut hash_count: usize = 0;
+
+    for entry in links.iter().filter(|entry| entry.hash().len() == hash_len) {
+        let hash: PackedHash = PackedHash::parse(entry.hash())?;
+        for (bit, count) in set_counts.iter_mut().enumerate() {
+            if hash.bit(bit) {
+                *count += 1;
+            }
+        }
+        hash_count += 1;
+    }
+
+    if hash_count == 0 {
+        return Err(format!(
+            "No hash of {} hex characters to compute bit weights from",
+            hash_len
+        ));
+    }
+
+    let weights: Vec<f64> = set_counts
+        .iter()
+        .map(|&count| binary_entropy(count as f64 / hash_count as f64).max(MIN_BIT_WEIGHT))
+        .collect();
+
+    Ok(WeightedBits::new(weights))
+}
+
+/// Entropy, in bits, of a bit set with probability `p`: 1.0 when `p` is 0.5, 0.0 when it is 0 or 1
+fn binary_entropy(p: f64) -> f64 {
+    [p, 1.0 - p]
+        .iter()
+        .filter(|&&p| p > 0.0)
+        .map(|&p| -p * p.log2())
+        .sum()
+}
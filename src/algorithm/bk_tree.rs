@@ -0,0 +1,250 @@
+use crate::algorithm::hash_index::HashIndex;
+use crate::algorithm::packed_hash::PackedHash;
+use crate::data_handle::dictionary_entry::HashEntry;
+
+/// A BK-tree indexing hashes by their Hamming distance.
+///
+/// Finding the hashes within a radius of a query only visits the branches that can
+/// contain them, instead of comparing the query against every entry. Hashes are
+/// parsed once into a [`PackedHash`] when inserted, and all of them must have the same length.
+///
+/// The tree maps hashes to links by default, as a [`HashIndex`], but may hold any value,
+/// e.g. the positions of the entries of a dictionary, with
+/// [`insert_value`](BkTree::insert_value) and [`find_values_within`](BkTree::find_values_within).
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::bk_tree::BkTree;
+/// use img_hash_linker::algorithm::hash_index::HashIndex;
+///
+/// let links = vec![
+///     ("ffff0000ffff0000".to_string(), "https://example.com".to_string()),
+///     ("0000ffff0000ffff".to_string(), "https://another.com".to_string()),
+/// ];
+///
+/// let mut index = BkTree::from_entries(&links)?;
+/// index.insert("ffff0000ffff0001", "https://third.com")?;
+///
+/// let matches = index.find_within("ffff0000ffff0000", 2)?;
+/// assert_eq!(matches.len(), 2);
+/// assert_eq!(matches[0].1, "https://example.com");
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct BkTree<V = String> {
+    nodes: Vec<BkNode<V>>,
+    hash_len: Option<usize>,
+    entry_count: usize,
+}
+
+#[derive(Debug, Clone)]
+struct BkNode<V> {
+    hash: String,
+    packed: PackedHash,
+    /// All the values sharing this exact hash
+    values: Vec<V>,
+    /// Child nodes, along with their distance to this node
+    children: Vec<(u32, usize)>,
+}
+
+impl<V> Default for BkTree<V> {
+    fn default() -> Self {
+        BkTree {
+            nodes: Vec::new(),
+            hash_len: None,
+            entry_count: 0,
+        }
+    }
+}
+
+impl BkTree {
+    /// Builds a tree from a list of links, `(hash, link)` pairs or any [`HashEntry`]
+    pub fn from_entries<E: HashEntry>(links: &[E]) -> Result<Self, String> {
+        let mut tree: BkTree = BkTree::new();
+        for entry in links {
+            tree.insert(entry.hash(), entry.link())?;
+        }
+        Ok(tree)
+    }
+}
+
+impl<V> BkTree<V> {
+    /// Creates an empty tree
+    pub fn new() -> Self {
+        BkTree::default()
+    }
+
+    /// Number of `(hash, value)` entries in the tree
+    pub fn len(&self) -> usize {
+        self.entry_count
+    }
+
+    /// Whether the tree has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entry_count == 0
+    }
+
+    /// Length, in hex characters, of the hashes in the tree (`None` while it is empty)
+    pub fn hash_len(&self) -> Option<usize> {
+        self.hash_len
+    }
+
+    /// Checks that a hash can be inserted in or searched for in this tree, and parses it
+    pub fn parse_hash(&self, hash: &str) -> Result<PackedHash, String> {
+        if let Some(hash_len) = self.hash_len
+            && hash.len() != hash_len
+        {
+            return Err(format!(
+                "Hash length mismatch: expected {} hex characters, got {} ({})",
+                hash_len,
+                hash.len(),
+                hash
+            ));
+        }
+
+        PackedHash::parse(hash)
+    }
+}
+
+impl<V> BkTree<V> {
+    /// Inserts a hash along with its value
+    pub fn insert_value(&mut self, hash: &str, value: V) -> Result<(), String> {
+        let packed: PackedHash = self.parse_hash(hash)?;
+        self.hash_len = Some(hash.len());
+        self.entry_count += 1;
+
+        if self.nodes.is_empty() {
+            self.nodes.push(BkNode::new(hash, packed, value));
+            return Ok(());
+        }
+
+        // Walk down from the root until no child lies at the same distance
+        let mut current: usize = 0;
+        loop {
+            let distance: u32 = packed.hamming_distance(&self.nodes[current].packed);
+            if distance == 0 {
+                self.nodes[current].values.push(value);
+                return Ok(());
+            }
+
+            match self.nodes[current]
+                .children
+                .iter()
+                .find(|(child_distance, _)| *child_distance == distance)
+            {
+                Some(&(_, child)) => current = child,
+                None => {
+                    let new_node: usize = self.nodes.len();
+                    self.nodes.push(BkNode::new(hash, packed, value));
+                    self.nodes[current].children.push((distance, new_node));
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Finds the values of the hashes within `radius` bits of the given hash
+    ///
+    /// Returns `(hash, value, distance)` tuples, sorted from the closest.
+    pub fn find_values_within(
+        &self,
+        hash: &str,
+        radius: u32,
+    ) -> Result<Vec<(&str, &V, u32)>, String> {
+        let packed: PackedHash = self.parse_hash(hash)?;
+        let mut matches: Vec<(&str, &V, u32)> = Vec::new();
+
+        if self.nodes.is_empty() {
+            return Ok(matches);
+        }
+
+        let mut pending: Vec<usize> = vec![0];
+        while let Some(current) = pending.pop() {
+            let node: &BkNode<V> = &self.nodes[current];
+            let distance: u32 = packed.hamming_distance(&node.packed);
+
+            if distance <= radius {
+                for value in &node.values {
+                    matches.push((&node.hash, value, distance));
+                }
+            }
+
+            // By the triangle inequality, only children in [distance - radius, distance + radius] can match
+            let lowest: u32 = distance.saturating_sub(radius);
+            let highest: u32 = distance.saturating_add(radius);
+            pending.extend(
+                node.children
+                    .iter()
+                    .filter(|(child_distance, _)| (lowest..=highest).contains(child_distance))
+                    .map(|&(_, child)| child),
+            );
+        }
+
+        matches.sort_by_key(|(_, _, distance)| *distance);
+        Ok(matches)
+    }
+}
+
+impl HashIndex for BkTree {
+    fn insert(&mut self, hash: &str, link: &str) -> Result<(), String> {
+        self.insert_value(hash, link.to_string())
+    }
+
+    fn find_within(&self, hash: &str, radius: u32) -> Result<Vec<(String, String, u32)>, String> {
+        Ok(self
+            .find_values_within(hash, radius)?
+            .into_iter()
+            .map(|(hash, link, distance)| (hash.to_string(), link.clone(), distance))
+            .collect())
+    }
+}
+
+impl<V> BkNode<V> {
+    fn new(hash: &str, packed: PackedHash, value: V) -> Self {
+        BkNode {
+            hash: hash.to_string(),
+            packed,
+            values: vec![value],
+            children: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_kept_per_hash() {
+        let mut tree: BkTree<usize> = BkTree::new();
+        tree.insert_value("ffff0000ffff0000", 0).unwrap();
+        tree.insert_value("ffff0000ffff0000", 1).unwrap();
+        tree.insert_value("ffff0000ffff0001", 2).unwrap();
+        tree.insert_value("0000ffff0000ffff", 3).unwrap();
+
+        assert_eq!(tree.len(), 4);
+        let values: Vec<(usize, u32)> = tree
+            .find_values_within("ffff0000ffff0000", 1)
+            .unwrap()
+            .into_iter()
+            .map(|(_, &value, distance)| (value, distance))
+            .collect();
+        assert_eq!(values, vec![(0, 0), (1, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn empty_tree_finds_nothing() {
+        let tree: BkTree = BkTree::new();
+        assert!(tree.is_empty());
+        assert!(tree.find_within("ffff", 16).unwrap().is_empty());
+    }
+
+    #[test]
+    fn lengths_must_match() {
+        let mut tree: BkTree = BkTree::new();
+        tree.insert("f0f", "https://a.com").unwrap();
+        assert!(tree.insert("ffff", "https://b.com").is_err());
+        assert!(tree.find_within("ffff", 1).is_err());
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::DynamicImage;
+use serde_json::{Value, json};
+
+use crate::algorithm::distance_metric::{DistanceMetric, Hamming};
+use crate::data_handle::labeled_pairs::LabeledPair;
+use crate::{Preprocessing, compute_hashes_with_preprocessing};
+
+/// Options for [`calibrate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalibrationOptions {
+    /// The hash sizes to calibrate (default: 8)
+    pub hash_sizes: Vec<u32>,
+    /// The processing applied to the images before hashing (default: white border removal)
+    pub preprocessing: Preprocessing,
+}
+
+impl Default for CalibrationOptions {
+    fn default() -> Self {
+        CalibrationOptions {
+            hash_sizes: vec![8],
+            preprocessing: Preprocessing::default(),
+        }
+    }
+}
+
+/// One point of a ROC curve: the rates obtained when accepting proximities at or above `threshold`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RocPoint {
+    pub threshold: f64,
+    /// Fraction of same-picture pairs accepted
+    pub true_positive_rate: f64,
+    /// Fraction of different-picture pairs accepted
+    pub false_positive_rate: f64,
+}
+
+/// Calibration results for one hashing algorithm and hash size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalibrationReport {
+    pub algorithm: String,
+    pub hash_size: u32,
+    /// Number of same-picture pairs
+    pub genuine_count: usize,
+    /// Number of different-picture pairs
+    pub impostor_count: usize,
+    /// ROC curve, from the strictest to the loosest threshold
+    pub roc: Vec<RocPoint>,
+    /// Equal error rate: the error rate where false accepts and false rejects balance out
+    pub equal_error_rate: f64,
+    /// The proximity threshold reaching the equal error rate
+    pub recommended_threshold: f64,
+}
+
+/// Computes the proximity distributions of labeled image pairs and calibrates
+/// the proximity threshold for each hash size.
+///
+/// Each image is loaded and preprocessed once, hashed at every hash size, and dropped
+/// before the next one is loaded, so only the hashes are kept in memory. Proximities
+/// are computed using the Hamming distance, like `try_finding_similar_hash`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use img_hash_linker::algorithm::calibration::{CalibrationOptions, calibrate};
+/// use img_hash_linker::data_handle::labeled_pairs::labeled_pairs_from_directory;
+///
+/// let pairs = labeled_pairs_from_directory("path/to/pairs".to_string())?;
+/// let options = CalibrationOptions {
+///     hash_sizes: vec![4, 8, 16],
+///     ..Default::default()
+/// };
+///
+/// for report in calibrate(&pairs, &options)? {
+///     println!("{}: {}", report.hash_size, report.recommended_threshold);
+/// }
+/// # Ok::<(), String>(())
+/// ```
+pub fn calibrate(
+    pairs: &[LabeledPair],
+    options: &CalibrationOptions,
+) -> Result<Vec<CalibrationReport>, String> {
+    // Hash every distinct image once, at all the hash sizes
+    let mut hashes: HashMap<&PathBuf, Vec<String>> = HashMap::new();
+    for pair in pairs {
+        for path in [&pair.image_a, &pair.image_b] {
+            if !hashes.contains_key(path) {
+                let image: DynamicImage = image::open(path)
+                    .map_err(|e| format!("Failed to open image {}: {}", path.display(), e))?;
+                let image_hashes: Vec<String> = compute_hashes_with_preprocessing(
+                    image,
+                    &options.preprocessing,
+                    &options.hash_sizes,
+                )?;
+                hashes.insert(path, image_hashes);
+            }
+        }
+    }
+
+    let mut reports: Vec<CalibrationReport> = Vec::new();
+    for (size_idx, &hash_size) in options.hash_sizes.iter().enumerate() {
+        let mut genuine: Vec<f64> = Vec::new();
+        let mut impostor: Vec<f64> = Vec::new();
+        for pair in pairs {
+            let proximity: f64 = Hamming.proximity(
+                &hashes[&pair.image_a][size_idx],
+                &hashes[&pair.image_b][size_idx],
+            )?;
+            if pair.same {
+                genuine.push(proximity);
+            } else {
+                impostor.push(proximity);
+            }
+        }
+
+        reports.push(calibrate_from_proximities(
+            "ahash", hash_size, &genuine, &impostor,
+        )?);
+    }
+
+    Ok(reports)
+}
+
+/// Builds a calibration report from the proximities of same-picture (`genuine`)
+/// and different-picture (`impostor`) pairs.
+pub fn calibrate_from_proximities(
+    algorithm: &str,
+    hash_size: u32,
+    genuine: &[f64],
+    impostor: &[f64],
+) -> Result<CalibrationReport, String> {
+    if genuine.is_empty() || impostor.is_empty() {
+        return Err("Calibration needs both same-picture and different-picture pairs".to_string());
+    }
+
+    // Every distinct proximity is a candidate threshold, from the strictest to the loosest
+    let mut thresholds: Vec<f64> = genuine.iter().chain(impostor).copied().collect();
+    thresholds.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    thresholds.dedup();
+
+    let rate = |proximities: &[f64], threshold: f64| {
+        proximities.iter().filter(|&&p| p >= threshold).count() as f64 / proximities.len() as f64
+    };
+
+    let roc: Vec<RocPoint> = thresholds
+        .iter()
+        .map(|&threshold| RocPoint {
+            threshold,
+            true_positive_rate: rate(genuine, threshold),
+            false_positive_rate: rate(impostor, threshold),
+        })
+        .collect();
+
+    // The equal error rate is where the false accept and false reject rates are closest
+    let eer_point: &RocPoint = roc
+        .iter()
+        .min_by(|a, b| {
+            let gap = |point: &RocPoint| {
+                (point.false_positive_rate - (1.0 - point.true_positive_rate)).abs()
+            };
+            gap(a)
+                .partial_cmp(&gap(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap(); // Safe to unwrap since there is at least one threshold
+
+    Ok(CalibrationReport {
+        algorithm: algorithm.to_string(),
+        hash_size,
+        genuine_count: genuine.len(),
+        impostor_count: impostor.len(),
+        equal_error_rate: (eer_point.false_positive_rate + 1.0 - eer_point.true_positive_rate)
+            / 2.0,
+        recommended_threshold: eer_point.threshold,
+        roc,
+    })
+}
+
+/// Writes calibration reports to a file, as JSON or CSV depending on its extension.
+///
+/// The CSV export has one row per ROC point, along with the summary of its report.
+pub fn write_calibration_reports(
+    reports: &[CalibrationReport],
+    path: String,
+) -> Result<(), String> {
+    let path_ref: &Path = path.as_ref();
+    let content: String = match path_ref.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => calibration_reports_to_json(reports),
+        Some("csv") => calibration_reports_to_csv(reports)?,
+        _ => {
+            return Err(format!(
+                "Unsupported report format (use .json or .csv): {}",
+                path
+            ));
+        }
+    };
+
+    fs::write(path_ref, content).map_err(|e| format!("Failed to write report: {}", e))
+}
+
+/// Serializes calibration reports as a JSON array.
+pub fn calibration_reports_to_json(reports: &[CalibrationReport]) -> String {
+    let reports: Vec<Value> = reports
+        .iter()
+        .map(|report| {
+            json!({
+                "algorithm": report.algorithm,
+                "hash_size": report.hash_size,
+                "genuine_count": report.genuine_count,
+                "impostor_count": report.impostor_count,
+                "equal_error_rate": report.equal_error_rate,
+                "recommended_threshold": report.recommended_threshold,
+                "roc": report.roc.iter().map(|point| json!({
+                    "threshold": point.threshold,
+                    "true_positive_rate": point.true_positive_rate,
+                    "false_positive_rate": point.false_positive_rate,
+                })).collect::<Vec<Value>>(),
+            })
+        })
+        .collect();
+
+    // Safe to unwrap since the values are built from plain numbers and strings
+    serde_json::to_string_pretty(&reports).unwrap()
+}
+
+/// Serializes calibration reports as CSV, with one row per ROC point.
+pub fn calibration_reports_to_csv(reports: &[CalibrationReport]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    writer
+        .write_record([
+            "algorithm",
+            "hash_size",
+            "threshold",
+            "true_positive_rate",
+            "false_positive_rate",
+            "equal_error_rate",
+            "recommended_threshold",
+        ])
+        .map_err(|e| format!("Failed to write headers: {}", e))?;
+
+    for report in reports {
+        for point in &report.roc {
+            writer
+                .write_record([
+                    report.algorithm.clone(),
+                    report.hash_size.to_string(),
+                    point.threshold.to_string(),
+                    point.true_positive_rate.to_string(),
+                    point.false_positive_rate.to_string(),
+                    report.equal_error_rate.to_string(),
+                    report.recommended_threshold.to_string(),
+                ])
+                .map_err(|e| format!("Failed to write record: {}", e))?;
+        }
+    }
+
+    let bytes: Vec<u8> = writer
+        .into_inner()
+        .map_err(|e| format!("Failed to flush CSV writer: {}", e))?;
+    String::from_utf8(bytes).map_err(|e| format!("Invalid UTF-8 in report: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_path;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn both_kinds_of_pairs_are_needed() {
+        assert!(calibrate_from_proximities("ahash", 8, &[], &[0.5]).is_err());
+        assert!(calibrate_from_proximities("ahash", 8, &[0.9], &[]).is_err());
+        assert!(calibrate_from_proximities("ahash", 8, &[], &[]).is_err());
+    }
+
+    #[test]
+    fn separated_proximities_have_no_error() {
+        let report: CalibrationReport =
+            calibrate_from_proximities("ahash", 8, &[1.0, 0.95], &[0.5, 0.6]).unwrap();
+
+        assert_eq!(report.equal_error_rate, 0.0);
+        assert_eq!(report.recommended_threshold, 0.95);
+        assert_eq!((report.genuine_count, report.impostor_count), (2, 2));
+    }
+
+    #[test]
+    fn uniform_proximities_cannot_be_told_apart() {
+        let report: CalibrationReport =
+            calibrate_from_proximities("ahash", 8, &[0.9, 0.9, 0.9], &[0.9]).unwrap();
+
+        // A single threshold accepts every pair
+        assert_eq!(
+            report.roc,
+            vec![RocPoint {
+                threshold: 0.9,
+                true_positive_rate: 1.0,
+                false_positive_rate: 1.0
+            }]
+        );
+        assert_eq!(report.equal_error_rate, 0.5);
+    }
+
+    #[test]
+    fn equal_error_rate_of_overlapping_proximities() {
+        let report: CalibrationReport =
+            calibrate_from_proximities("ahash", 8, &[0.9, 0.8, 0.7], &[0.75, 0.6]).unwrap();
+
+        let thresholds: Vec<f64> = report.roc.iter().map(|point| point.threshold).collect();
+        assert_eq!(thresholds, vec![0.9, 0.8, 0.75, 0.7, 0.6]);
+
+        // At 0.75, a third of the genuine pairs are rejected and half of the impostors accepted
+        assert_eq!(report.recommended_threshold, 0.75);
+        assert!((report.equal_error_rate - 5.0 / 12.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn reports_are_exported_per_roc_point() {
+        let reports: Vec<CalibrationReport> = vec![
+            calibrate_from_proximities("ahash", 8, &[1.0, 0.95], &[0.5]).unwrap(),
+            calibrate_from_proximities("ahash", 16, &[0.9], &[0.9]).unwrap(),
+        ];
+
+        let csv: String = calibration_reports_to_csv(&reports).unwrap();
+        assert_eq!(csv.lines().count(), 1 + 3 + 1);
+
+        let json: Value = serde_json::from_str(&calibration_reports_to_json(&reports)).unwrap();
+        assert_eq!(json[1]["hash_size"], 16);
+        assert_eq!(json[0]["roc"].as_array().map(Vec::len), Some(3));
+
+        assert_eq!(calibration_reports_to_csv(&[]).unwrap().lines().count(), 1);
+        assert!(write_calibration_reports(&reports, "report.txt".to_string()).is_err());
+    }
+
+    #[test]
+    fn images_are_hashed_at_every_size() {
+        let gradient = |flip: bool| {
+            DynamicImage::ImageRgb8(RgbImage::from_fn(32, 24, |x, y| {
+                let value: u8 = ((x * 8 + y * 3) % 256) as u8;
+                if flip {
+                    Rgb([255 - value, value, 0])
+                } else {
+                    Rgb([value, value, value])
+                }
+            }))
+        };
+        let paths: Vec<PathBuf> = vec![temp_path("a.png"), temp_path("b.png"), temp_path("c.png")];
+        gradient(false).save(&paths[0]).unwrap();
+        gradient(false).save(&paths[1]).unwrap();
+        gradient(true).save(&paths[2]).unwrap();
+
+        let pair = |a: usize, b: usize, same: bool| LabeledPair {
+            image_a: paths[a].clone(),
+            image_b: paths[b].clone(),
+            same,
+        };
+        let pairs: Vec<LabeledPair> = vec![pair(0, 1, true), pair(0, 2, false), pair(1, 2, false)];
+        let options: CalibrationOptions = CalibrationOptions {
+            hash_sizes: vec![4, 8],
+            ..Default::default()
+        };
+        let reports: Result<Vec<CalibrationReport>, String> = calibrate(&pairs, &options);
+        for path in &paths {
+            fs::remove_file(path).unwrap();
+        }
+
+        let reports: Vec<CalibrationReport> = reports.unwrap();
+        assert_eq!(
+            reports.iter().map(|r| r.hash_size).collect::<Vec<u32>>(),
+            vec![4, 8]
+        );
+        for report in reports {
+            assert_eq!((report.genuine_count, report.impostor_count), (1, 2));
+            assert_eq!(report.roc[0].threshold, 1.0);
+        }
+
+        // Missing images are reported
+        assert!(calibrate(&[pair(0, 1, true)], &options).is_err());
+    }
+}
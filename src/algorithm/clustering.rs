@@ -0,0 +1,246 @@
+use crate::algorithm::bk_tree::BkTree;
+use crate::data_handle::dictionary_entry::{DictionaryEntry, HashEntry};
+
+/// A group of near-duplicate dictionary entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashCluster {
+    /// The entries of the cluster, in their dictionary order
+    pub entries: Vec<DictionaryEntry>,
+    /// The distinct links of the entries, in the order they first appear
+    pub links: Vec<String>,
+}
+
+impl HashCluster {
+    /// Whether the entries of the cluster point to different links, e.g. the same artwork
+    /// enrolled twice by different people
+    pub fn has_conflicting_links(&self) -> bool {
+        self.links.len() > 1
+    }
+}
+
+/// Groups the entries of a dictionary into clusters of near-duplicate hashes.
+///
+/// Two entries are near-duplicates when their hashes are at most `max_distance` bits
+/// apart, and clusters are the connected components of near-duplicates: a cluster may
+/// chain entries further apart than `max_distance`, through the entries in between
+/// (single-linkage clustering, cut at `max_distance`). Only hashes of the same length
+/// are compared, and entries with no near-duplicate are left out.
+///
+/// # Arguments
+///
+/// * `links` - The dictionary entries, `(hash, link)` pairs or any [`HashEntry`]
+/// * `max_distance` - The largest Hamming distance, in bits, between near-duplicates
+///
+/// # Returns
+///
+/// * `Ok(Vec<HashCluster>)` - The clusters of at least two entries, ordered by their first entry
+/// * `Err(String)` - Error message if a hash is invalid
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::clustering::cluster_near_duplicates;
+///
+/// let links = vec![
+///     ("ffffffff00000000".to_string(), "https://example.com".to_string()),
+///     ("00000000ffffffff".to_string(), "https://another.com".to_string()),
+///     ("ffffffff00000001".to_string(), "https://example.com".to_string()),
+///     ("ffffffff00000003".to_string(), "https://duplicate.com".to_string()),
+/// ];
+///
+/// let clusters = cluster_near_duplicates(&links, 1)?;
+/// assert_eq!(clusters.len(), 1);
+/// assert_eq!(clusters[0].entries.len(), 3);
+/// assert!(clusters[0].has_conflicting_links());
+/// # Ok::<(), String>(())
+/// ```
+pub fn cluster_near_duplicates<E: HashEntry>(
+    links: &[E],
+    max_distance: u32,
+) -> Result<Vec<HashCluster>, String> {
+    let mut components: UnionFind = UnionFind::new(links.len());
+
+    // One tree per hash length, holding the positions of the entries
+    let mut trees: Vec<BkTree<usize>> = Vec::new();
+    for (position, entry) in links.iter().enumerate() {
+        let tree: &mut BkTree<usize> = match trees
+            .iter()
+            .position(|tree| tree.hash_len() == Some(entry.hash().len()))
+        {
+            Some(index) => &mut trees[index],
+            None => {
+                trees.push(BkTree::new());
+                trees.last_mut().unwrap()
+            }
+        };
+
+        for (_, &neighbor, _) in tree.find_values_within(entry.hash(), max_distance)? {
+            components.union(position, neighbor);
+        }
+        tree.insert_value(entry.hash(), position)?;
+    }
+
+    // Gather the entries of each component, in the order of their first entry
+    let mut clusters: Vec<HashCluster> = Vec::new();
+    let mut cluster_of_root: Vec<Option<usize>> = vec![None; links.len()];
+    for (position, entry) in links.iter().enumerate() {
+        let root: usize = components.find(position);
+        let index: usize = *cluster_of_root[root].get_or_insert_with(|| {
+            clusters.push(HashCluster {
+                entries: Vec::new(),
+                links: Vec::new(),
+            });
+            clusters.len() - 1
+        });
+
+        let cluster: &mut HashCluster = &mut clusters[index];
+        if !cluster.links.iter().any(|link| link == entry.link()) {
+            cluster.links.push(entry.link().to_string());
+        }
+        cluster.entries.push(DictionaryEntry {
+            hash: entry.hash().to_string(),
+            link: entry.link().to_string(),
+            threshold: entry.threshold(),
+            thumbnail: entry.thumbnail().map(str::to_string),
+            metadata: entry.metadata().cloned().unwrap_or_default(),
+        });
+    }
+
+    clusters.retain(|cluster| cluster.entries.len() > 1);
+    Ok(clusters)
+}
+
+/// Disjoint sets of positions, merged with path halving and union by size
+struct UnionFind {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        UnionFind {
+            parents: (0..len).collect(),
+            sizes: vec![1; len],
+        }
+    }
+
+    fn find(&mut self, mut position: usize) -> usize {
+        while self.parents[position] != position {
+            self.parents[position] = self.parents[self.parents[position]];
+            position = self.parents[position];
+        }
+        position
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.sizes[a] < self.sizes[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parents[b] = a;
+        self.sizes[a] += self.sizes[b];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(hash, link)| (hash.to_string(), link.to_string()))
+            .collect()
+    }
+
+    fn hashes(cluster: &HashCluster) -> Vec<&str> {
+        cluster
+            .entries
+            .iter()
+            .map(|entry| entry.hash.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn empty_and_single_entry_dictionaries_have_no_clusters() {
+        assert!(
+            cluster_near_duplicates::<(String, String)>(&[], 3)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            cluster_near_duplicates(&links(&[("ffff0000ffff0000", "https://a.com")]), 3)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn clusters_chain_through_intermediate_entries() {
+        // The first and last hashes are 2 bits apart, each 1 bit from the middle one
+        let clusters: Vec<HashCluster> = cluster_near_duplicates(
+            &links(&[
+                ("ffff0000ffff0000", "https://a.com"),
+                ("0000ffff0000ffff", "https://b.com"),
+                ("ffff0000ffff0001", "https://a.com"),
+                ("ffff0000ffff0003", "https://a.com"),
+            ]),
+            1,
+        )
+        .unwrap();
+
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(
+            hashes(&clusters[0]),
+            vec!["ffff0000ffff0000", "ffff0000ffff0001", "ffff0000ffff0003"]
+        );
+        assert!(!clusters[0].has_conflicting_links());
+    }
+
+    #[test]
+    fn identical_hashes_with_different_links_conflict() {
+        let clusters: Vec<HashCluster> = cluster_near_duplicates(
+            &links(&[
+                ("f0f", "https://a.com"),
+                ("f0f", "https://b.com"),
+                ("f0f", "https://a.com"),
+            ]),
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].entries.len(), 3);
+        assert_eq!(clusters[0].links, vec!["https://a.com", "https://b.com"]);
+        assert!(clusters[0].has_conflicting_links());
+    }
+
+    #[test]
+    fn hashes_of_different_lengths_are_not_clustered() {
+        let clusters: Vec<HashCluster> = cluster_near_duplicates(
+            &links(&[
+                ("000", "https://a.com"),
+                ("0000000000000000", "https://b.com"),
+                ("001", "https://c.com"),
+                ("0000000000000001", "https://d.com"),
+            ]),
+            1,
+        )
+        .unwrap();
+
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(hashes(&clusters[0]), vec!["000", "001"]);
+        assert_eq!(
+            hashes(&clusters[1]),
+            vec!["0000000000000000", "0000000000000001"]
+        );
+    }
+
+    #[test]
+    fn invalid_hashes_are_errors() {
+        assert!(cluster_near_duplicates(&links(&[("zz", "https://a.com")]), 1).is_err());
+    }
+}
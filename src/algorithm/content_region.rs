@@ -0,0 +1,425 @@
+use image::{DynamicImage, GenericImageView, GrayImage, imageops};
+
+use crate::algorithm::remove_borders::CropRect;
+
+/// Options controlling how the dominant picture of an image is detected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContentRegionOptions {
+    /// Longest side, in pixels, of the downscaled copy the analysis runs on (default: 512)
+    pub working_size: u32,
+    /// Side, in pixels of the working copy, of the square blocks being classified (default: 8)
+    pub block_size: u32,
+    /// Minimum grayscale standard deviation of a photographic block (default: 4.0)
+    pub min_block_std_dev: f64,
+    /// Maximum fraction of a photographic block's pixels sharing the same tone (out of 16 levels).
+    /// Flat UI surfaces and text are dominated by their background tone (default: 0.5)
+    pub max_dominant_tone: f64,
+    /// Maximum number of non-photographic blocks, in a row or a column, enclosed by
+    /// photographic blocks that are still considered part of the picture, so flat
+    /// areas of a picture (like a clear sky) do not split it (default: 3)
+    pub hole_reach: usize,
+    /// Minimum fraction of the image the detected region must cover (default: 0.05)
+    pub min_area_fraction: f64,
+}
+
+impl Default for ContentRegionOptions {
+    fn default() -> Self {
+        ContentRegionOptions {
+            working_size: 512,
+            block_size: 8,
+            min_block_std_dev: 4.0,
+            max_dominant_tone: 0.5,
+            hole_reach: 3,
+            min_area_fraction: 0.05,
+        }
+    }
+}
+
+/// Extracts the dominant picture from an image, such as an artwork in a screenshot.
+///
+/// If no photographic region is found, a copy of the original image is returned.
+pub fn extract_content_region(img: &DynamicImage, options: &ContentRegionOptions) -> DynamicImage {
+    match detect_content_region(img, options) {
+        Some(crop) => crop.apply(img),
+        None => img.clone(),
+    }
+}
+
+/// Detects the largest rectangular photographic region of an image.
+///
+/// The algorithm:
+/// 1. Downscale a grayscale copy of the image to `working_size`
+/// 2. Split it into blocks, and mark the blocks with enough variance and no dominant tone as photographic
+/// 3. Find the largest rectangle made only of photographic blocks
+/// 4. Snap each side of the rectangle to the strongest nearby edge
+/// 5. Map the rectangle back onto the original image
+///
+/// Returns `None` if no region covering at least `min_area_fraction` of the image is found.
+pub fn detect_content_region(
+    img: &DynamicImage,
+    options: &ContentRegionOptions,
+) -> Option<CropRect> {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 || options.block_size == 0 {
+        return None;
+    }
+
+    // Work on a downscaled grayscale copy
+    let scale: f64 = (options.working_size as f64 / width.max(height) as f64).min(1.0);
+    let work_width: u32 = ((width as f64 * scale).round() as u32).max(1);
+    let work_height: u32 = ((height as f64 * scale).round() as u32).max(1);
+    let gray: GrayImage = imageops::thumbnail(&img.to_luma8(), work_width, work_height);
+
+    // Classify blocks
+    let block: u32 = options.block_size;
+    let columns: usize = (work_width / block) as usize;
+    let rows: usize = (work_height / block) as usize;
+    if columns == 0 || rows == 0 {
+        return None;
+    }
+
+    let mut grid: Vec<bool> = vec![false; rows * columns];
+    for row in 0..rows {
+        for column in 0..columns {
+            grid[row * columns + column] =
+                is_photographic_block(&gray, column as u32 * block, row as u32 * block, options);
+        }
+    }
+    fill_holes(&mut grid, rows, columns, options.hole_reach);
+
+    // Find the largest rectangle of photographic blocks
+    let (left, top, right, bottom) = largest_rectangle(&grid, rows, columns)?;
+    let area: f64 = ((right - left + 1) * (bottom - top + 1)) as f64;
+    if area / ((rows * columns) as f64) < options.min_area_fraction {
+        return None;
+    }
+
+    // Refine the block boundaries using the edges of the working copy
+    let mut min_x: u32 = left as u32 * block;
+    let mut min_y: u32 = top as u32 * block;
+    let mut max_x: u32 = (right as u32 + 1) * block - 1;
+    let mut max_y: u32 = (bottom as u32 + 1) * block - 1;
+
+    min_y = snap_to_edge(&gray, Side::Top, min_y, (min_x, max_x), block);
+    max_y = snap_to_edge(&gray, Side::Bottom, max_y, (min_x, max_x), block);
+    min_x = snap_to_edge(&gray, Side::Left, min_x, (min_y, max_y), block);
+    max_x = snap_to_edge(&gray, Side::Right, max_x, (min_y, max_y), block);
+
+    if min_x >= max_x || min_y >= max_y {
+        return None;
+    }
+
+    let region: CropRect = CropRect {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x + 1,
+        height: max_y - min_y + 1,
+    };
+
+    Some(region.scaled((work_width, work_height), (width, height)))
+}
+
+/// Checks whether the block at `(x, y)` looks like part of a photograph
+fn is_photographic_block(gray: &GrayImage, x: u32, y: u32, options: &ContentRegionOptions) -> bool {
+    let mut sum: f64 = 0.0;
+    let mut sum_of_squares: f64 = 0.0;
+    let mut tones: [u32; 16] = [0; 16];
+
+    for block_y in y..y + options.block_size {
+        for block_x in x..x + options.block_size {
+            let value: u8 = gray.get_pixel(block_x, block_y)[0];
+            sum += value as f64;
+            sum_of_squares += (value as f64) * (value as f64);
+            tones[(value >> 4) as usize] += 1;
+        }
+    }
+
+    let count: f64 = (options.block_size * options.block_size) as f64;
+    let mean: f64 = sum / count;
+    let std_dev: f64 = (sum_of_squares / count - mean * mean).max(0.0).sqrt();
+    let dominant_tone: f64 = *tones.iter().max().unwrap_or(&0) as f64 / count;
+
+    std_dev >= options.min_block_std_dev && dominant_tone <= options.max_dominant_tone
+}
+
+/// Marks as photographic the runs of at most `reach` blocks enclosed by photographic
+/// blocks on both sides, horizontally or vertically
+fn fill_holes(grid: &mut [bool], rows: usize, columns: usize, reach: usize) {
+    let original: Vec<bool> = grid.to_vec();
+
+    // Fills the short gaps of one line of blocks, given the grid index of each of its cells
+    let mut fill_line = |cells: &mut dyn Iterator<Item = usize>| {
+        let cells: Vec<usize> = cells.collect();
+        let mut last_photographic: Option<usize> = None;
+
+        for (position, &cell) in cells.iter().enumerate() {
+            if !original[cell] {
+                continue;
+            }
+            if let Some(previous) = last_photographic {
+                let gap: usize = position - previous - 1;
+                if gap > 0 && gap <= reach {
+                    for &enclosed in &cells[previous + 1..position] {
+                        grid[enclosed] = true;
+                    }
+                }
+            }
+            last_photographic = Some(position);
+        }
+    };
+
+    for row in 0..rows {
+        fill_line(&mut (0..columns).map(|column| row * columns + column));
+    }
+    for column in 0..columns {
+        fill_line(&mut (0..rows).map(|row| row * columns + column));
+    }
+}
+
+/// Finds the largest rectangle of `true` cells in the grid, using the histogram method.
+/// Returns `(left, top, right, bottom)` as inclusive cell coordinates.
+fn largest_rectangle(
+    grid: &[bool],
+    rows: usize,
+    columns: usize,
+) -> Option<(usize, usize, usize, usize)> {
+    let mut heights: Vec<usize> = vec![0; columns];
+    let mut best: Option<(usize, (usize, usize, usize, usize))> = None;
+
+    for row in 0..rows {
+        for column in 0..columns {
+            heights[column] = if grid[row * columns + column] {
+                heights[column] + 1
+            } else {
+                0
+            };
+        }
+
+        // Largest rectangle in the histogram ending at this row
+        let mut stack: Vec<usize> = Vec::new();
+        for column in 0..=columns {
+            let height: usize = if column < columns { heights[column] } else { 0 };
+
+            while let Some(&top) = stack.last() {
+                if heights[top] < height {
+                    break;
+                }
+                stack.pop();
+
+                let left: usize = stack.last().map_or(0, |&previous| previous + 1);
+                let area: usize = heights[top] * (column - left);
+                if area > 0 && best.is_none_or(|(best_area, _)| area > best_area) {
+                    best = Some((area, (left, row + 1 - heights[top], column - 1, row)));
+                }
+            }
+            stack.push(column);
+        }
+    }
+
+    best.map(|(_, rectangle)| rectangle)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Side {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// Moves one side of the region, by at most `reach` pixels, to the strongest edge
+/// running along it. `span` is the range of pixels covered by the side.
+fn snap_to_edge(gray: &GrayImage, side: Side, position: u32, span: (u32, u32), reach: u32) -> u32 {
+    let (width, height) = gray.dimensions();
+    let limit: u32 = match side {
+        Side::Top | Side::Bottom => height,
+        Side::Left | Side::Right => width,
+    };
+
+    // Mean absolute difference between the inside line at `candidate` and the outside line next to it
+    let strength = |candidate: u32| -> f64 {
+        let outside: i64 = match side {
+            Side::Top | Side::Left => candidate as i64 - 1,
+            Side::Bottom | Side::Right => candidate as i64 + 1,
+        };
+        if outside < 0 || outside >= limit as i64 {
+            return 0.0;
+        }
+
+        let total: u64 = (span.0..=span.1)
+            .map(|along| {
+                let (inside_pixel, outside_pixel) = match side {
+                    Side::Top | Side::Bottom => (
+                        gray.get_pixel(along, candidate)[0],
+                        gray.get_pixel(along, outside as u32)[0],
+                    ),
+                    Side::Left | Side::Right => (
+                        gray.get_pixel(candidate, along)[0],
+                        gray.get_pixel(outside as u32, along)[0],
+                    ),
+                };
+                inside_pixel.abs_diff(outside_pixel) as u64
+            })
+            .sum();
+
+        total as f64 / (span.1 - span.0 + 1) as f64
+    };
+
+    let first: u32 = position.saturating_sub(reach);
+    let last: u32 = (position + reach).min(limit - 1);
+
+    // Keep the current position unless a clearly stronger edge is found nearby
+    let mut best: (u32, f64) = (position, strength(position) + 1.0);
+    for candidate in first..=last {
+        let candidate_strength: f64 = strength(candidate);
+        if candidate_strength > best.1 {
+            best = (candidate, candidate_strength);
+        }
+    }
+
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb, RgbImage};
+
+    /// Pseudo-random gray levels, spread over the whole range
+    fn noise(x: u32, y: u32) -> u8 {
+        // splitmix64 of the pixel position
+        let mut z: u64 = ((x as u64) << 32 | y as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ((z ^ (z >> 31)) >> 56) as u8
+    }
+
+    /// A white 64x64 screenshot with a dark 32x32 noisy picture at (16, 16)
+    fn screenshot() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(64, 64, |x, y| {
+            if (16..48).contains(&x) && (16..48).contains(&y) {
+                let value: u8 = noise(x, y) / 2;
+                Rgb([value, value, value])
+            } else {
+                Rgb([255, 255, 255])
+            }
+        }))
+    }
+
+    fn grid(rows: &[&str]) -> (Vec<bool>, usize, usize) {
+        let cells: Vec<bool> = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| c == '#'))
+            .collect();
+        let columns: usize = rows.first().map_or(0, |row| row.len());
+        (cells, rows.len(), columns)
+    }
+
+    #[test]
+    fn uniform_blocks_are_not_photographic() {
+        let options: ContentRegionOptions = ContentRegionOptions::default();
+
+        let uniform: GrayImage = GrayImage::from_pixel(8, 8, Luma([128]));
+        assert!(!is_photographic_block(&uniform, 0, 0, &options));
+
+        // Text on a flat background varies, but is dominated by the background tone
+        let text: GrayImage = GrayImage::from_fn(8, 8, |x, _| Luma([if x == 3 { 0 } else { 255 }]));
+        assert!(!is_photographic_block(&text, 0, 0, &options));
+
+        let picture: GrayImage = GrayImage::from_fn(8, 8, |x, y| Luma([noise(x, y)]));
+        assert!(is_photographic_block(&picture, 0, 0, &options));
+    }
+
+    #[test]
+    fn holes_are_filled_within_reach() {
+        let (mut cells, rows, columns) = grid(&["#..#", "#...", "#..#"]);
+        fill_holes(&mut cells, rows, columns, 2);
+
+        // The gap of the first and last rows is enclosed, the middle row and the
+        // first column are open on one side or have no gap
+        assert_eq!((cells, rows, columns), grid(&["####", "#..#", "####"]));
+
+        let (mut cells, rows, columns) = grid(&["#..#"]);
+        fill_holes(&mut cells, rows, columns, 1);
+        assert_eq!(cells, grid(&["#..#"]).0);
+    }
+
+    #[test]
+    fn filling_an_empty_grid_does_nothing() {
+        let mut cells: Vec<bool> = Vec::new();
+        fill_holes(&mut cells, 0, 0, 3);
+        assert!(cells.is_empty());
+
+        let (mut cells, rows, columns) = grid(&["....", "...."]);
+        fill_holes(&mut cells, rows, columns, 3);
+        assert_eq!(cells, vec![false; 8]);
+    }
+
+    #[test]
+    fn largest_rectangle_of_odd_sized_grids() {
+        assert_eq!(largest_rectangle(&[], 0, 0), None);
+
+        let (cells, rows, columns) = grid(&[".....", ".....", "....."]);
+        assert_eq!(largest_rectangle(&cells, rows, columns), None);
+
+        let (cells, rows, columns) = grid(&["#####", "#####", "#####"]);
+        assert_eq!(largest_rectangle(&cells, rows, columns), Some((0, 0, 4, 2)));
+
+        // A 3x2 rectangle beats the longer but thinner first row
+        let (cells, rows, columns) = grid(&["#####", ".###.", ".###.", "#...#"]);
+        assert_eq!(largest_rectangle(&cells, rows, columns), Some((1, 0, 3, 2)));
+
+        let (cells, rows, columns) = grid(&["#"]);
+        assert_eq!(largest_rectangle(&cells, rows, columns), Some((0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn picture_is_found_in_a_screenshot() {
+        let region: Option<CropRect> =
+            detect_content_region(&screenshot(), &ContentRegionOptions::default());
+        assert_eq!(
+            region,
+            Some(CropRect {
+                x: 16,
+                y: 16,
+                width: 32,
+                height: 32
+            })
+        );
+    }
+
+    #[test]
+    fn images_without_a_picture_are_kept() {
+        let options: ContentRegionOptions = ContentRegionOptions::default();
+
+        let empty: DynamicImage = DynamicImage::new_rgb8(0, 0);
+        assert_eq!(detect_content_region(&empty, &options), None);
+
+        let uniform: DynamicImage =
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(33, 17, Rgb([200, 10, 10])));
+        assert_eq!(detect_content_region(&uniform, &options), None);
+        assert_eq!(extract_content_region(&uniform, &options), uniform);
+
+        // Smaller than a single block
+        let tiny: DynamicImage = DynamicImage::ImageRgb8(RgbImage::from_fn(7, 5, |x, y| {
+            let value: u8 = noise(x, y);
+            Rgb([value, value, value])
+        }));
+        assert_eq!(detect_content_region(&tiny, &options), None);
+
+        let no_blocks: ContentRegionOptions = ContentRegionOptions {
+            block_size: 0,
+            ..options
+        };
+        assert_eq!(detect_content_region(&screenshot(), &no_blocks), None);
+    }
+
+    #[test]
+    fn small_pictures_are_ignored() {
+        let options: ContentRegionOptions = ContentRegionOptions {
+            min_area_fraction: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(detect_content_region(&screenshot(), &options), None);
+    }
+}
@@ -0,0 +1,402 @@
+use crate::algorithm::hash_proximity::{
+    ProximityMetric, byte_difference, check_hash_lengths, hamming_distance, hamming_distance_words,
+    max_byte_difference,
+};
+
+/// A way of measuring how far apart two hex hash strings are.
+///
+/// Implement this trait to plug a custom metric into the search functions, such as
+/// [`try_finding_similar_hash_with_metric`](crate::algorithm::hash_proximity::try_finding_similar_hash_with_metric).
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::distance_metric::DistanceMetric;
+/// use img_hash_linker::algorithm::hash_proximity::try_finding_similar_hash_with_metric;
+///
+/// /// Only compares the first hex character of each hash
+/// struct FirstNibble;
+///
+/// impl DistanceMetric for FirstNibble {
+///     fn distance(&self, hash1: &str, hash2: &str) -> Result<f64, String> {
+///         Ok(if hash1.get(..1) == hash2.get(..1) { 0.0 } else { 1.0 })
+///     }
+///
+///     fn max_distance(&self, _hash_len: usize) -> f64 {
+///         1.0
+///     }
+/// }
+///
+/// let links = vec![("f0".to_string(), "https://example.com".to_string())];
+/// let (_, link, _) = try_finding_similar_hash_with_metric("ff".to_string(), links, None, &FirstNibble)?;
+/// assert_eq!(link, "https://example.com");
+/// # Ok::<(), String>(())
+/// ```
+pub trait DistanceMetric {
+    /// Calculate the distance between two hex hash strings, 0.0 meaning identical hashes
+    ///
+    /// The built-in metrics return an error if the hashes have different lengths.
+    fn distance(&self, hash1: &str, hash2: &str) -> Result<f64, String>;
+
+    /// The largest distance possible between two hashes of `hash_len` hex characters
+    fn max_distance(&self, hash_len: usize) -> f64;
+
+    /// Calculate the distance between two hashes of `hash_len` hex characters, parsed into
+    /// the words of a [`PackedHash`](crate::algorithm::packed_hash::PackedHash)
+    ///
+    /// Returns `None` if the metric only compares hex strings (the default). Metrics
+    /// comparing the bits of the hashes implement it, so that the search functions parse
+    /// each hash once instead of on every comparison.
+    fn distance_words(&self, _words1: &[u64], _words2: &[u64], _hash_len: usize) -> Option<f64> {
+        None
+    }
+
+    /// Calculate the proximity between two hex hash strings
+    /// Returns a value between 0.0 and 1.0, where 1.0 means identical hashes
+    /// and 0.0 means maximum difference
+    fn proximity(&self, hash1: &str, hash2: &str) -> Result<f64, String> {
+        let distance: f64 = self.distance(hash1, hash2)?;
+        Ok(self.distance_to_proximity(distance, hash1.len().min(hash2.len())))
+    }
+
+    /// Convert a distance between two hashes of `hash_len` hex characters into a proximity
+    fn distance_to_proximity(&self, distance: f64, hash_len: usize) -> f64 {
+        let max_distance: f64 = self.max_distance(hash_len);
+
+        // Empty hashes are considered identical
+        if max_distance <= 0.0 {
+            return 1.0;
+        }
+
+        (1.0 - distance / max_distance).clamp(0.0, 1.0)
+    }
+}
+
+/// Number of bits that differ between the two hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Hamming;
+
+impl DistanceMetric for Hamming {
+    fn distance(&self, hash1: &str, hash2: &str) -> Result<f64, String> {
+        Ok(hamming_distance(hash1, hash2)? as f64)
+    }
+
+    fn max_distance(&self, hash_len: usize) -> f64 {
+        (hash_len * 4) as f64
+    }
+
+    fn distance_words(&self, words1: &[u64], words2: &[u64], _hash_len: usize) -> Option<f64> {
+        Some(hamming_distance_words(words1, words2) as f64)
+    }
+}
+
+/// Fraction of bits that differ between the two hashes, from 0.0 to 1.0,
+/// so that distances are comparable across hash sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NormalizedHamming;
+
+impl DistanceMetric for NormalizedHamming {
+    fn distance(&self, hash1: &str, hash2: &str) -> Result<f64, String> {
+        let distance: u32 = hamming_distance(hash1, hash2)?;
+        let bit_count: usize = hash1.len() * 4;
+        if bit_count == 0 {
+            return Ok(0.0);
+        }
+
+        Ok(distance as f64 / bit_count as f64)
+    }
+
+    fn max_distance(&self, _hash_len: usize) -> f64 {
+        1.0
+    }
+
+    fn distance_words(&self, words1: &[u64], words2: &[u64], hash_len: usize) -> Option<f64> {
+        if hash_len == 0 {
+            return Some(0.0);
+        }
+
+        Some(hamming_distance_words(words1, words2) as f64 / (hash_len * 4) as f64)
+    }
+}
+
+/// Sum of the weights of the bits that differ between the two hashes.
+///
+/// `weights[i]` is the weight of bit `i` of the hash, bit 0 being the least
+/// significant bit of the last hex character (the first pixel for aHash).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WeightedBits {
+    pub weights: Vec<f64>,
+}
+
+impl WeightedBits {
+    pub fn new(weights: Vec<f64>) -> Self {
+        WeightedBits { weights }
+    }
+
+    /// Sum of the weights of the bits that differ between two hashes of the same length,
+    /// given as the words of a [`PackedHash`](crate::algorithm::packed_hash::PackedHash)
+    ///
+    /// Bits without a weight count for nothing.
+    pub fn packed_distance(&self, words1: &[u64], words2: &[u64]) -> f64 {
+        let mut distance: f64 = 0.0;
+        for (index, (word1, word2)) in words1.iter().zip(words2).enumerate() {
+            let mut difference: u64 = word1 ^ word2;
+            while difference != 0 {
+                let bit: usize = index * 64 + difference.trailing_zeros() as usize;
+                distance += self.weights.get(bit).copied().unwrap_or(0.0);
+                difference &= difference - 1;
+            }
+        }
+
+        distance
+    }
+
+    fn weights_for(&self, hash_len: usize) -> Result<&[f64], String> {
+        self.weights.get(..hash_len * 4).ok_or(format!(
+            "Bit weights cover {} bits, but hashes have {} bits",
+            self.weights.len(),
+            hash_len * 4
+        ))
+    }
+}
+
+impl DistanceMetric for WeightedBits {
+    fn distance(&self, hash1: &str, hash2: &str) -> Result<f64, String> {
+        check_hash_lengths(hash1, hash2)?;
+        let hash_len: usize = hash1.len();
+        let weights: &[f64] = self.weights_for(hash_len)?;
+
+        // Sum from bit 0, in the same order as `packed_distance`, so that both give the same result
+        let mut distance: f64 = 0.0;
+        for (position, (c1, c2)) in hash1.chars().rev().zip(hash2.chars().rev()).enumerate() {
+            let nibble1 = c1
+                .to_digit(16)
+                .ok_or("Hash1 contains invalid hex characters")?;
+            let nibble2 = c2
+                .to_digit(16)
+                .ok_or("Hash2 contains invalid hex characters")?;
+
+            // The last hex character holds bits 0 to 3
+            let first_bit: usize = position * 4;
+            let difference: u32 = nibble1 ^ nibble2;
+            for bit in 0..4 {
+                if difference & (1 << bit) != 0 {
+                    distance += weights[first_bit + bit];
+                }
+            }
+        }
+
+        Ok(distance)
+    }
+
+    fn max_distance(&self, hash_len: usize) -> f64 {
+        self.weights.iter().take(hash_len * 4).sum()
+    }
+
+    fn distance_words(&self, words1: &[u64], words2: &[u64], hash_len: usize) -> Option<f64> {
+        // Without enough weights, the hex string comparison gives the error
+        self.weights_for(hash_len).ok()?;
+        Some(self.packed_distance(words1, words2))
+    }
+}
+
+/// Absolute difference between each pair of bytes of the two hashes.
+///
+/// This is the metric used by earlier versions, kept for thresholds tuned with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LegacyByteDifference;
+
+impl DistanceMetric for LegacyByteDifference {
+    fn distance(&self, hash1: &str, hash2: &str) -> Result<f64, String> {
+        Ok(byte_difference(hash1, hash2)? as f64)
+    }
+
+    fn max_distance(&self, hash_len: usize) -> f64 {
+        max_byte_difference(hash_len) as f64
+    }
+}
+
+impl DistanceMetric for ProximityMetric {
+    fn distance(&self, hash1: &str, hash2: &str) -> Result<f64, String> {
+        match self {
+            ProximityMetric::Hamming => Hamming.distance(hash1, hash2),
+            ProximityMetric::LegacyByteDifference => LegacyByteDifference.distance(hash1, hash2),
+        }
+    }
+
+    fn max_distance(&self, hash_len: usize) -> f64 {
+        match self {
+            ProximityMetric::Hamming => Hamming.max_distance(hash_len),
+            ProximityMetric::LegacyByteDifference => LegacyByteDifference.max_distance(hash_len),
+        }
+    }
+
+    fn distance_words(&self, words1: &[u64], words2: &[u64], hash_len: usize) -> Option<f64> {
+        match self {
+            ProximityMetric::Hamming => Hamming.distance_words(words1, words2, hash_len),
+            ProximityMetric::LegacyByteDifference => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::hash_proximity::{calculate_hex_hash_proximity, parse_hex_hash};
+
+    /// Pairs of random hashes of several sizes, along with identical and complementary pairs
+    fn hash_pairs() -> Vec<(String, String)> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut hex = |len: usize| -> String {
+            (0..len)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    char::from_digit((state % 16) as u32, 16).unwrap()
+                })
+                .collect()
+        };
+
+        let mut pairs: Vec<(String, String)> = Vec::new();
+        for len in [1, 2, 3, 16, 17, 32, 64] {
+            for _ in 0..20 {
+                pairs.push((hex(len), hex(len)));
+            }
+            let hash: String = hex(len);
+            pairs.push((hash.clone(), hash));
+            pairs.push(("0".repeat(len), "f".repeat(len)));
+        }
+        pairs
+    }
+
+    /// The proximity of the first version of the library, which only supported even lengths
+    fn baseline_proximity(hash1: &str, hash2: &str) -> f64 {
+        let mut total_difference: u32 = 0;
+        for i in (0..hash1.len()).step_by(2) {
+            let byte1: u8 = u8::from_str_radix(&hash1[i..i + 2], 16).unwrap();
+            let byte2: u8 = u8::from_str_radix(&hash2[i..i + 2], 16).unwrap();
+            total_difference += (byte1 as i16 - byte2 as i16).unsigned_abs() as u32;
+        }
+        1.0 - total_difference as f64 / (255 * hash1.len() / 2) as f64
+    }
+
+    fn assert_packed_distance_matches<M: DistanceMetric>(metric: &M, hash1: &str, hash2: &str) {
+        let words1: Vec<u64> = parse_hex_hash(hash1).unwrap();
+        let words2: Vec<u64> = parse_hex_hash(hash2).unwrap();
+        assert_eq!(
+            metric.distance_words(&words1, &words2, hash1.len()),
+            Some(metric.distance(hash1, hash2).unwrap()),
+            "{} {}",
+            hash1,
+            hash2
+        );
+    }
+
+    #[test]
+    fn normalized_hamming_stays_between_0_and_1() {
+        for (hash1, hash2) in hash_pairs() {
+            let distance: f64 = NormalizedHamming.distance(&hash1, &hash2).unwrap();
+            assert!((0.0..=1.0).contains(&distance), "{} {}", hash1, hash2);
+            assert_eq!(
+                NormalizedHamming.proximity(&hash1, &hash2).unwrap(),
+                Hamming.proximity(&hash1, &hash2).unwrap()
+            );
+            assert_packed_distance_matches(&NormalizedHamming, &hash1, &hash2);
+        }
+
+        assert_eq!(NormalizedHamming.distance("0f", "0f"), Ok(0.0));
+        assert_eq!(NormalizedHamming.distance("00", "ff"), Ok(1.0));
+        assert_eq!(NormalizedHamming.distance("", ""), Ok(0.0));
+        assert_eq!(NormalizedHamming.distance("00", "f0"), Ok(0.5));
+    }
+
+    #[test]
+    fn weighted_bits_stay_within_the_sum_of_the_weights() {
+        let weights: Vec<f64> = (0..256).map(|bit| (bit % 7) as f64 / 3.0).collect();
+        let metric: WeightedBits = WeightedBits::new(weights.clone());
+
+        for (hash1, hash2) in hash_pairs() {
+            let distance: f64 = metric.distance(&hash1, &hash2).unwrap();
+            let max_distance: f64 = metric.max_distance(hash1.len());
+            assert!(
+                (0.0..=max_distance + 1e-9).contains(&distance),
+                "{} {}",
+                hash1,
+                hash2
+            );
+            let proximity: f64 = metric.proximity(&hash1, &hash2).unwrap();
+            assert!((0.0..=1.0).contains(&proximity), "{} {}", hash1, hash2);
+            assert_packed_distance_matches(&metric, &hash1, &hash2);
+        }
+
+        // Every bit differs, so every weight counts
+        let max_distance: f64 = weights[..64].iter().sum();
+        assert_eq!(metric.max_distance(16), max_distance);
+        let distance: f64 = metric.distance(&"0".repeat(16), &"f".repeat(16)).unwrap();
+        assert!((distance - max_distance).abs() < 1e-9);
+        assert_eq!(metric.distance_to_proximity(distance, 16), 0.0);
+
+        // Bit 0 is the least significant bit of the last hex character
+        let metric: WeightedBits = WeightedBits::new(vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 10.0]);
+        assert_eq!(metric.distance("00", "01"), Ok(1.0));
+        assert_eq!(metric.distance("00", "80"), Ok(10.0));
+    }
+
+    #[test]
+    fn weighted_bits_need_a_weight_for_each_bit() {
+        let metric: WeightedBits = WeightedBits::new(vec![1.0; 8]);
+        assert!(metric.distance("000", "fff").is_err());
+        assert_eq!(metric.distance_words(&[0], &[0xfff], 3), None);
+
+        // Zero weights make every hash identical
+        let metric: WeightedBits = WeightedBits::new(vec![0.0; 8]);
+        assert_eq!(metric.proximity("00", "ff"), Ok(1.0));
+    }
+
+    #[test]
+    fn legacy_byte_difference_matches_the_baseline() {
+        for (hash1, hash2) in hash_pairs() {
+            let proximity: f64 = LegacyByteDifference.proximity(&hash1, &hash2).unwrap();
+            assert_eq!(
+                proximity,
+                calculate_hex_hash_proximity(&hash1, &hash2).unwrap()
+            );
+            assert_eq!(
+                ProximityMetric::LegacyByteDifference.proximity(&hash1, &hash2),
+                Ok(proximity)
+            );
+            if hash1.len() % 2 == 0 {
+                assert_eq!(
+                    proximity,
+                    baseline_proximity(&hash1, &hash2),
+                    "{} {}",
+                    hash1,
+                    hash2
+                );
+            }
+        }
+
+        // Bytes are compared by value, not by bits
+        assert_eq!(LegacyByteDifference.distance("80", "7f"), Ok(1.0));
+        assert_eq!(Hamming.distance("80", "7f"), Ok(8.0));
+        // A leftover hex character is a byte of its own
+        assert_eq!(LegacyByteDifference.distance("f00", "000"), Ok(15.0));
+        assert_eq!(LegacyByteDifference.max_distance(3), 270.0);
+        assert_eq!(LegacyByteDifference.distance_words(&[0], &[1], 1), None);
+    }
+
+    #[test]
+    fn hashes_of_different_lengths_are_errors() {
+        let metrics: [&dyn DistanceMetric; 4] = [
+            &Hamming,
+            &NormalizedHamming,
+            &WeightedBits::new(vec![1.0; 64]),
+            &LegacyByteDifference,
+        ];
+        for metric in metrics {
+            assert!(metric.distance("ff", "fff").is_err());
+            assert!(metric.distance("fg", "ff").is_err());
+        }
+    }
+}
@@ -0,0 +1,632 @@
+use crate::algorithm::ahash::hash_size_for_hex_len;
+use crate::algorithm::distance_metric::{DistanceMetric, Hamming, WeightedBits};
+use crate::algorithm::hash_proximity::{HashMatch, MatchOutcome, classify_matches};
+use crate::algorithm::match_confidence::ImpostorDistribution;
+use crate::algorithm::packed_hash::{PackedHash, PackedHashes};
+use crate::data_handle::dictionary_entry::{DictionaryEntry, HashEntry};
+
+/// A dictionary whose hashes are parsed once, to be searched by many queries.
+///
+/// Searching it gives the same results as the Hamming-based search functions of
+/// `hash_proximity`, without re-parsing every hash for every query: hashes are
+/// stored as [`PackedHashes`] and compared with XOR and popcount. It is immutable
+/// once built, so it can be shared between threads.
+///
+/// The dictionary may hold hashes of several sizes. They are partitioned by length,
+/// and each query hash is only compared with the hashes of its own size: to look an
+/// image up, hash it at each of the [`hash_sizes`](HashDictionary::hash_sizes) and use
+/// [`find_match_for_hashes`](HashDictionary::find_match_for_hashes).
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::hash_dictionary::HashDictionary;
+///
+/// let links = vec![
+///     ("ffffffff00000000".to_string(), "https://example.com".to_string()),
+///     ("00000000ffffffff".to_string(), "https://another.com".to_string()),
+///     ("f0f0".to_string(), "https://small.com".to_string()),
+/// ];
+///
+/// let dictionary = HashDictionary::from_entries(&links)?;
+/// assert_eq!(dictionary.hash_sizes(), vec![4, 8]);
+///
+/// let matches = dictionary.find_top_k_similar_hashes("ffffffff00000001", 1, None)?;
+/// assert_eq!(matches[0].link, "https://example.com");
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HashDictionary {
+    entries: Vec<DictionaryEntry>,
+    /// One partition per hash length, sorted by length
+    partitions: Vec<Partition>,
+    impostors: Option<ImpostorDistribution>,
+}
+
+#[derive(Debug, Clone)]
+struct Partition {
+    hashes: PackedHashes,
+    /// Position in `entries` of each hash of the partition
+    positions: Vec<usize>,
+    /// Weights of the bits of the hashes of the partition, if any
+    weights: Option<WeightedBits>,
+}
+
+impl HashDictionary {
+    /// Parses the hashes of a list of links, `(hash, link)` pairs or any [`HashEntry`]
+    ///
+    /// Every hash must have the length of a hash size (e.g. 16 hex characters for 8×8):
+    /// a hash of any other length could never be searched, so it is an error.
+    pub fn from_entries<E: HashEntry>(links: &[E]) -> Result<Self, String> {
+        let mut dictionary: HashDictionary = HashDictionary::default();
+
+        for entry in links {
+            let packed: PackedHash = PackedHash::parse(entry.hash())?;
+            if hash_size_for_hex_len(packed.hex_len()).is_none() {
+                return Err(format!(
+                    "Invalid hash {} for {}: {} hex characters match no hash size",
+                    entry.hash(),
+                    entry.link(),
+                    packed.hex_len()
+                ));
+            }
+
+            let partition: &mut Partition = match dictionary
+                .partitions
+                .binary_search_by_key(&packed.hex_len(), |partition| partition.hex_len())
+            {
+                Ok(index) => &mut dictionary.partitions[index],
+                Err(index) => {
+                    dictionary.partitions.insert(
+                        index,
+                        Partition {
+                            hashes: PackedHashes::new(),
+                            positions: Vec::new(),
+                            weights: None,
+                        },
+                    );
+                    &mut dictionary.partitions[index]
+                }
+            };
+
+            partition.hashes.push(&packed)?;
+            partition.positions.push(dictionary.entries.len());
+            dictionary.entries.push(DictionaryEntry {
+                hash: entry.hash().to_string(),
+                link: entry.link().to_string(),
+                threshold: entry.threshold(),
+                thumbnail: entry.thumbnail().map(str::to_string),
+                ..Default::default()
+            });
+        }
+
+        Ok(dictionary)
+    }
+
+    /// Estimates the false-match probability of every match found in the dictionary
+    ///
+    /// The matches then carry their
+    /// [`false_match_probability`](HashMatch::false_match_probability), usually from the
+    /// impostor distribution of the dictionary itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use img_hash_linker::algorithm::hash_dictionary::HashDictionary;
+    /// use img_hash_linker::algorithm::match_confidence::ImpostorDistribution;
+    ///
+    /// let links = vec![
+    ///     ("ffffffff00000000".to_string(), "https://example.com".to_string()),
+    ///     ("00000000ffffffff".to_string(), "https://another.com".to_string()),
+    /// ];
+    ///
+    /// let dictionary = HashDictionary::from_entries(&links)?
+    ///     .with_impostor_distribution(ImpostorDistribution::from_entries(&links, None)?);
+    ///
+    /// let matches = dictionary.find_top_k_similar_hashes("ffffffff00000001", 1, None)?;
+    /// assert_eq!(matches[0].false_match_probability, Some(0.5));
+    /// # Ok::<(), String>(())
+    /// ```
+    pub fn with_impostor_distribution(mut self, impostors: ImpostorDistribution) -> Self {
+        self.impostors = Some(impostors);
+        self
+    }
+
+    /// Weighs the bits of the hashes of one size, to compare them with the [`WeightedBits`]
+    /// metric instead of the Hamming distance
+    ///
+    /// The weights apply to the hashes with one weight per bit, usually computed by
+    /// [`compute_bit_weights`](crate::algorithm::bit_weights::compute_bit_weights): the
+    /// distance of a match is then the sum of the weights of its differing bits, and its
+    /// proximity is relative to the sum of all weights. False-match probabilities are still
+    /// estimated from the number of differing bits.
+    ///
+    /// Returns an error if the dictionary has no hashes with one bit per weight.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use img_hash_linker::algorithm::distance_metric::WeightedBits;
+    /// use img_hash_linker::algorithm::hash_dictionary::HashDictionary;
+    ///
+    /// let links = vec![("0f0".to_string(), "https://example.com".to_string())];
+    ///
+    /// // The bits of the middle hex character do not count
+    /// let mut weights = WeightedBits::new(vec![1.0; 12]);
+    /// weights.weights[4..8].fill(0.0);
+    /// let dictionary = HashDictionary::from_entries(&links)?.with_bit_weights(weights)?;
+    ///
+    /// let matches = dictionary.find_top_k_similar_hashes("000", 1, None)?;
+    /// assert_eq!(matches[0].proximity, 1.0);
+    /// # Ok::<(), String>(())
+    /// ```
+    pub fn with_bit_weights(mut self, weights: WeightedBits) -> Result<Self, String> {
+        let bit_count: usize = weights.weights.len();
+        let partition: Option<&mut Partition> = self
+            .partitions
+            .iter_mut()
+            .find(|partition| partition.hex_len() * 4 == bit_count);
+
+        match partition {
+            Some(partition) => {
+                partition.weights = Some(weights);
+                Ok(self)
+            }
+            None => Err(format!(
+                "The dictionary has no hashes of {} bits to weigh",
+                bit_count
+            )),
+        }
+    }
+
+    /// The entries of the dictionary, in their original order
+    pub fn entries(&self) -> &[DictionaryEntry] {
+        &self.entries
+    }
+
+    /// Number of entries in the dictionary
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the dictionary has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Lengths, in hex characters, of the hashes in the dictionary, from the shortest
+    pub fn hash_lens(&self) -> Vec<usize> {
+        self.partitions
+            .iter()
+            .map(|partition| partition.hex_len())
+            .collect()
+    }
+
+    /// Hash sizes of the hashes in the dictionary, from the smallest
+    pub fn hash_sizes(&self) -> Vec<u32> {
+        self.hash_lens()
+            .into_iter()
+            .filter_map(hash_size_for_hex_len)
+            .collect()
+    }
+
+    /// Find the `k` hashes most similar to the given hash, by Hamming proximity
+    /// Returns the matches above the threshold, sorted from the most to the least similar
+    ///
+    /// Only the hashes of the same length are compared: searching a hash of a size missing
+    /// from a non-empty dictionary is an error.
+    /// See [`find_top_k_similar_hashes`](crate::algorithm::hash_proximity::find_top_k_similar_hashes).
+    pub fn find_top_k_similar_hashes(
+        &self,
+        hash: &str,
+        k: usize,
+        proximity_threshold: impl Into<Option<f64>>,
+    ) -> Result<Vec<HashMatch>, String> {
+        self.find_top_k_for_hashes(&[hash], k, proximity_threshold)
+    }
+
+    /// Find the `k` hashes most similar to any of the given hashes, by Hamming proximity
+    /// Returns the matches above the threshold, from the largest hash size
+    ///
+    /// `hashes` are the hashes of one query at several sizes: each is compared with the
+    /// hashes of its own size, and hashes of sizes missing from the dictionary are ignored.
+    /// It is an error if none of them has a size present in a non-empty dictionary.
+    ///
+    /// Proximities of different hash sizes are not comparable (a few bits of a small hash
+    /// weigh more than many bits of a large one), so matches are not ranked across sizes:
+    /// the matches of the largest hash size come first, which tells images apart best,
+    /// each size being sorted from the most to the least similar.
+    pub fn find_top_k_for_hashes<S: AsRef<str>>(
+        &self,
+        hashes: &[S],
+        k: usize,
+        proximity_threshold: impl Into<Option<f64>>,
+    ) -> Result<Vec<HashMatch>, String> {
+        let minimum_proximity: f64 = proximity_threshold.into().unwrap_or(0.95);
+
+        let mut similar_hashes: Vec<HashMatch> = Vec::new();
+        let mut compared: bool = false;
+
+        for hash in hashes {
+            let query: PackedHash = PackedHash::parse(hash.as_ref())?;
+            let Some(partition) = self.partition(query.hex_len()) else {
+                continue;
+            };
+            compared = true;
+
+            for (index, (&position, distance)) in partition
+                .positions
+                .iter()
+                .zip(partition.hashes.distances(&query)?)
+                .enumerate()
+            {
+                let entry: &DictionaryEntry = &self.entries[position];
+                let (metric_distance, proximity): (f64, f64) =
+                    partition.score(&query, index, distance);
+
+                if proximity >= entry.threshold.unwrap_or(minimum_proximity) {
+                    similar_hashes.push(HashMatch {
+                        hash: entry.hash.clone(),
+                        link: entry.link.clone(),
+                        distance: metric_distance,
+                        proximity,
+                        false_match_probability: self.impostors.as_ref().and_then(|impostors| {
+                            impostors.false_match_probability(query.hex_len(), distance as f64)
+                        }),
+                        thumbnail_similarity: None,
+                    });
+                }
+            }
+        }
+
+        if !compared && !self.is_empty() {
+            let lens: Vec<usize> = hashes.iter().map(|hash| hash.as_ref().len()).collect();
+            return Err(format!(
+                "Hash length mismatch: the dictionary has hashes of {:?} hex characters, searched hashes have {:?}",
+                self.hash_lens(),
+                lens
+            ));
+        }
+
+        // Sort from the largest hash size, then from the most to the least similar,
+        // keeping the original order for ties
+        similar_hashes.sort_by(|a, b| {
+            b.hash.len().cmp(&a.hash.len()).then(
+                b.proximity
+                    .partial_cmp(&a.proximity)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            )
+        });
+        similar_hashes.truncate(k);
+
+        Ok(similar_hashes)
+    }
+
+    /// Look a hash up in the dictionary, telling confident matches from ambiguous ones
+    ///
+    /// See [`find_match`](crate::algorithm::hash_proximity::find_match).
+    pub fn find_match(
+        &self,
+        hash: &str,
+        proximity_threshold: impl Into<Option<f64>>,
+        ambiguity_margin: impl Into<Option<f64>>,
+    ) -> Result<MatchOutcome, String> {
+        self.find_match_for_hashes(&[hash], proximity_threshold, ambiguity_margin)
+    }
+
+    /// Look a query up in the dictionary from its hashes at several sizes, telling
+    /// confident matches from ambiguous ones
+    ///
+    /// See [`find_top_k_for_hashes`](HashDictionary::find_top_k_for_hashes) for how the
+    /// hashes are compared, and [`find_match`](crate::algorithm::hash_proximity::find_match)
+    /// for how ambiguous matches are detected.
+    ///
+    /// Each hash size is classified on its own, as proximities of different sizes are not
+    /// comparable. The outcome of the largest size wins, unless an ambiguous size makes the
+    /// query ambiguous too, or sizes agree on no single link: the query is then ambiguous
+    /// between the matches of the two sizes. An exact match of any size pointing to the
+    /// winning link is preferred.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use img_hash_linker::algorithm::hash_dictionary::HashDictionary;
+    /// use img_hash_linker::algorithm::hash_proximity::MatchOutcome;
+    /// use img_hash_linker::data_handle::load_csv::load_entries_from_csv;
+    /// use img_hash_linker::{Preprocessing, compute_hashes_with_preprocessing};
+    ///
+    /// let dictionary = HashDictionary::from_entries(&load_entries_from_csv("links.csv".to_string())?)?;
+    ///
+    /// // Hash the image at every size present in the dictionary
+    /// let image = image::open("path/to/image.jpg").unwrap();
+    /// let hashes = compute_hashes_with_preprocessing(
+    ///     image,
+    ///     &Preprocessing::default(),
+    ///     &dictionary.hash_sizes(),
+    /// )?;
+    ///
+    /// if let MatchOutcome::Confident(found) = dictionary.find_match_for_hashes(&hashes, None, None)? {
+    ///     println!("{}", found.link);
+    /// }
+    /// # Ok::<(), String>(())
+    /// ```
+    pub fn find_match_for_hashes<S: AsRef<str>>(
+        &self,
+        hashes: &[S],
+        proximity_threshold: impl Into<Option<f64>>,
+        ambiguity_margin: impl Into<Option<f64>>,
+    ) -> Result<MatchOutcome, String> {
+        let proximity_threshold: Option<f64> = proximity_threshold.into();
+        let ambiguity_margin: f64 = ambiguity_margin.into().unwrap_or(0.01);
+
+        // Classify each size on its own, from the largest
+        let mut hashes: Vec<&str> = hashes.iter().map(|hash| hash.as_ref()).collect();
+        hashes.sort_by_key(|hash| std::cmp::Reverse(hash.len()));
+
+        let mut outcomes: Vec<MatchOutcome> = Vec::new();
+        for &hash in &hashes {
+            if self.partition(hash.len()).is_none() {
+                continue;
+            }
+            let candidates: Vec<HashMatch> =
+                self.find_top_k_for_hashes(&[hash], usize::MAX, proximity_threshold)?;
+            outcomes.push(classify_matches(hash, candidates, ambiguity_margin));
+        }
+
+        if outcomes.is_empty() && !self.is_empty() {
+            // Gives the length mismatch error
+            self.find_top_k_for_hashes(&hashes, 1, proximity_threshold)?;
+        }
+
+        Ok(combine_outcomes(outcomes))
+    }
+
+    fn partition(&self, hex_len: usize) -> Option<&Partition> {
+        self.partitions
+            .binary_search_by_key(&hex_len, |partition| partition.hex_len())
+            .ok()
+            .map(|index| &self.partitions[index])
+    }
+}
+
+impl Partition {
+    fn hex_len(&self) -> usize {
+        // Safe to unwrap since partitions are only created along with their first hash
+        self.hashes.hex_len().unwrap()
+    }
+
+    /// Distance and proximity between a query and the hash at `index`, whose Hamming
+    /// distance is `hamming`, measured with the bit weights of the partition if any
+    fn score(&self, query: &PackedHash, index: usize, hamming: u32) -> (f64, f64) {
+        match &self.weights {
+            Some(weights) => {
+                let words: &[u64] = self.hashes.get(index).unwrap_or_default();
+                let distance: f64 = weights.packed_distance(query.words(), words);
+                (
+                    distance,
+                    weights.distance_to_proximity(distance, query.hex_len()),
+                )
+            }
+            None => (
+                hamming as f64,
+                Hamming.distance_to_proximity(hamming as f64, query.hex_len()),
+            ),
+        }
+    }
+}
+
+/// Combines the outcomes of the sizes of a query, from the largest size
+fn combine_outcomes(outcomes: Vec<MatchOutcome>) -> MatchOutcome {
+    let mut found: Vec<MatchOutcome> = Vec::new();
+    for outcome in outcomes {
+        match outcome {
+            MatchOutcome::NoMatch => {}
+            MatchOutcome::Ambiguous { .. } => return outcome,
+            _ => found.push(outcome),
+        }
+    }
+
+    let matched = |outcome: &MatchOutcome| -> Option<HashMatch> {
+        match outcome {
+            MatchOutcome::Exact(found) | MatchOutcome::Confident(found) => Some(found.clone()),
+            _ => None,
+        }
+    };
+
+    let Some(best) = found.first().and_then(matched) else {
+        return MatchOutcome::NoMatch;
+    };
+    if let Some(runner_up) = found
+        .iter()
+        .filter_map(matched)
+        .find(|other| other.link != best.link)
+    {
+        return MatchOutcome::Ambiguous { best, runner_up };
+    }
+
+    match found
+        .iter()
+        .position(|outcome| matches!(outcome, MatchOutcome::Exact(_)))
+    {
+        Some(exact) => found.swap_remove(exact),
+        None => found.swap_remove(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(hash, link)| (hash.to_string(), link.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn hashes_matching_no_hash_size_are_rejected() {
+        let error: String =
+            HashDictionary::from_entries(&links(&[("ffff0000", "https://a.com")])).unwrap_err();
+        assert!(error.contains("match no hash size"));
+
+        assert!(HashDictionary::from_entries(&links(&[("zz", "https://a.com")])).is_err());
+    }
+
+    #[test]
+    fn empty_dictionary() {
+        let dictionary: HashDictionary =
+            HashDictionary::from_entries::<(String, String)>(&[]).unwrap();
+        assert!(dictionary.is_empty());
+        assert!(dictionary.hash_sizes().is_empty());
+        assert_eq!(
+            dictionary.find_match("ffff0000ffff0000", None, None),
+            Ok(MatchOutcome::NoMatch)
+        );
+    }
+
+    #[test]
+    fn entries_are_partitioned_by_length() {
+        let dictionary: HashDictionary = HashDictionary::from_entries(&links(&[
+            ("ffff0000ffff0000", "https://a.com"),
+            ("f0f", "https://b.com"),
+            ("0000ffff0000ffff", "https://c.com"),
+            ("0f0", "https://d.com"),
+        ]))
+        .unwrap();
+
+        assert_eq!(dictionary.hash_lens(), vec![3, 16]);
+        assert_eq!(dictionary.hash_sizes(), vec![3, 8]);
+
+        // Only the hashes of the same length are compared
+        let matches: Vec<HashMatch> = dictionary
+            .find_top_k_similar_hashes("f0f", usize::MAX, 0.0)
+            .unwrap();
+        let found: Vec<&str> = matches.iter().map(|m| m.link.as_str()).collect();
+        assert_eq!(found, vec!["https://b.com", "https://d.com"]);
+
+        // A size missing from the dictionary is an error
+        assert!(
+            dictionary
+                .find_top_k_similar_hashes("ffff", 1, None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn matches_are_not_ranked_across_sizes() {
+        let dictionary: HashDictionary = HashDictionary::from_entries(&links(&[
+            ("f0f", "https://small.com"),
+            ("ffff0000ffff0001", "https://large.com"),
+        ]))
+        .unwrap();
+
+        // The small hash matches exactly, but the large size comes first
+        let matches: Vec<HashMatch> = dictionary
+            .find_top_k_for_hashes(&["f0f", "ffff0000ffff0000"], usize::MAX, 0.9)
+            .unwrap();
+        assert_eq!(matches[0].link, "https://large.com");
+        assert_eq!(matches[1].link, "https://small.com");
+    }
+
+    #[test]
+    fn sizes_pointing_to_different_links_are_ambiguous() {
+        let dictionary: HashDictionary = HashDictionary::from_entries(&links(&[
+            ("f0f", "https://small.com"),
+            ("ffff0000ffff0001", "https://large.com"),
+        ]))
+        .unwrap();
+
+        let outcome: MatchOutcome = dictionary
+            .find_match_for_hashes(&["f0f", "ffff0000ffff0000"], 0.9, None)
+            .unwrap();
+        let MatchOutcome::Ambiguous { best, runner_up } = outcome else {
+            panic!("expected an ambiguous match, got {:?}", outcome);
+        };
+        assert_eq!(best.link, "https://large.com");
+        assert_eq!(runner_up.link, "https://small.com");
+    }
+
+    #[test]
+    fn sizes_agreeing_on_a_link_prefer_the_exact_match() {
+        let dictionary: HashDictionary = HashDictionary::from_entries(&links(&[
+            ("f0f", "https://a.com"),
+            ("ffff0000ffff0001", "https://a.com"),
+        ]))
+        .unwrap();
+
+        let outcome: MatchOutcome = dictionary
+            .find_match_for_hashes(&["ffff0000ffff0000", "f0f"], 0.9, None)
+            .unwrap();
+        assert!(matches!(outcome, MatchOutcome::Exact(found) if found.hash == "f0f"));
+
+        // Sizes without matches do not prevent a match of another size
+        let outcome: MatchOutcome = dictionary
+            .find_match_for_hashes(&["0000ffff0000ffff", "f0f"], 0.9, None)
+            .unwrap();
+        assert!(matches!(outcome, MatchOutcome::Exact(found) if found.hash == "f0f"));
+    }
+
+    #[test]
+    fn queries_of_missing_sizes_are_errors() {
+        let dictionary: HashDictionary =
+            HashDictionary::from_entries(&links(&[("ffff0000ffff0000", "https://a.com")])).unwrap();
+
+        let error: String = dictionary
+            .find_match_for_hashes(&["f0f"], None, None)
+            .unwrap_err();
+        assert!(error.contains("Hash length mismatch"));
+    }
+
+    #[test]
+    fn bit_weights_apply_to_their_own_size() {
+        let entries: Vec<(String, String)> = links(&[
+            ("ffff0000ffff0000", "https://a.com"),
+            ("0f0f0f0f0f0f0f0f", "https://b.com"),
+            ("f0f", "https://c.com"),
+        ]);
+        let weights: WeightedBits = WeightedBits::new((0..64).map(|bit| bit as f64).collect());
+        let dictionary: HashDictionary = HashDictionary::from_entries(&entries)
+            .unwrap()
+            .with_bit_weights(weights.clone())
+            .unwrap();
+
+        // Same results as the weighted metric on the hex strings
+        let query: &str = "ffff0000ffff0f01";
+        let matches: Vec<HashMatch> = dictionary
+            .find_top_k_similar_hashes(query, usize::MAX, 0.0)
+            .unwrap();
+        for found in &matches {
+            assert_eq!(
+                found.distance,
+                weights.distance(query, &found.hash).unwrap()
+            );
+            assert_eq!(
+                found.proximity,
+                weights.proximity(query, &found.hash).unwrap()
+            );
+        }
+
+        // The other sizes are still compared by Hamming distance
+        let matches: Vec<HashMatch> = dictionary.find_top_k_similar_hashes("f0e", 1, 0.0).unwrap();
+        assert_eq!(matches[0].distance, 1.0);
+    }
+
+    #[test]
+    fn bit_weights_of_a_missing_size_are_errors() {
+        let dictionary: HashDictionary =
+            HashDictionary::from_entries(&links(&[("ffff0000ffff0000", "https://a.com")])).unwrap();
+
+        assert!(
+            dictionary
+                .clone()
+                .with_bit_weights(WeightedBits::new(vec![1.0; 12]))
+                .is_err()
+        );
+        assert!(
+            dictionary
+                .with_bit_weights(WeightedBits::new(vec![1.0; 63]))
+                .is_err()
+        );
+    }
+}
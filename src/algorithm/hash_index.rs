@@ -0,0 +1,61 @@
+/// An index answering Hamming radius queries over `(hash, link)` entries.
+///
+/// Implemented by [`BkTree`](crate::algorithm::bk_tree::BkTree) and
+/// [`MultiIndex`](crate::algorithm::multi_index::MultiIndex), as faster alternatives to
+/// the linear scan of [`try_finding_similar_hash`](crate::algorithm::hash_proximity::try_finding_similar_hash).
+pub trait HashIndex {
+    /// Adds a `(hash, link)` entry to the index
+    fn insert(&mut self, hash: &str, link: &str) -> Result<(), String>;
+
+    /// Finds every entry whose hash is within `radius` bits of the given hash
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<(hash, link, distance)>)` - The matching entries, closest first
+    /// * `Err(String)` - Error message if the hash is invalid or has the wrong length
+    fn find_within(&self, hash: &str, radius: u32) -> Result<Vec<(String, String, u32)>, String>;
+
+    /// Try to find the most similar hash in the index, like `try_finding_similar_hash`
+    /// Returns the hash-link pair with the highest Hamming proximity above the threshold
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash to find a similar hash for
+    /// * `proximity_threshold` - The minimum proximity to consider a hash similar (default: 0.95)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((hash, link, proximity))` - The hash-link pair with the highest proximity above threshold
+    /// * `Err(String)` - Error message if no similar hash is found or the hash is invalid
+    fn find_similar(
+        &self,
+        hash: &str,
+        proximity_threshold: Option<f64>,
+    ) -> Result<(String, String, f64), String> {
+        let minimum_proximity: f64 = proximity_threshold.unwrap_or(0.95);
+        let bit_count: u32 = hash.len() as u32 * 4;
+
+        let (similar_hash, link, distance) = self
+            .find_within(hash, radius_for_proximity(minimum_proximity, bit_count))?
+            .into_iter()
+            .next()
+            .ok_or("No similar hash found")?;
+
+        let proximity: f64 = if bit_count == 0 {
+            1.0
+        } else {
+            1.0 - distance as f64 / bit_count as f64
+        };
+
+        Ok((similar_hash, link, proximity))
+    }
+}
+
+/// Converts a minimum Hamming proximity into the largest distance (in bits) satisfying it
+///
+/// Since `proximity = 1 - distance / bit_count`, a hash is similar enough when
+/// `distance <= (1 - proximity) * bit_count`.
+pub fn radius_for_proximity(minimum_proximity: f64, bit_count: u32) -> u32 {
+    // The small epsilon avoids losing a bit to floating point rounding (e.g. 0.05 * 64)
+    ((1.0 - minimum_proximity).max(0.0) * bit_count as f64 + 1e-9).floor() as u32
+}
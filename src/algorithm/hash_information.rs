@@ -0,0 +1,255 @@
+use crate::algorithm::ahash::hash_size_for_hex_len;
+use crate::algorithm::packed_hash::PackedHash;
+
+/// How much information a hash carries, to detect degenerate hashes.
+///
+/// Blank or uniform images give hashes such as `ffffffffffffffff`, which are close to
+/// the hash of almost any other flat image and cannot tell pictures apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HashInformation {
+    /// Balance between set and unset bits, from 0.0 (all bits equal) to 1.0 (half of them set)
+    pub bit_balance: f64,
+    /// Variance of the gray levels of the downscaled image the hash was computed from,
+    /// if known (0.0 for a uniform image)
+    pub grid_variance: Option<f64>,
+    /// Overall information score, from 0.0 to 1.0
+    pub score: f64,
+}
+
+/// Standard deviation of the gray levels, under which the grid lowers the information score
+const FULL_CONTRAST_STD_DEV: f64 = 16.0;
+
+impl HashInformation {
+    /// Measures the information of a hash from its bits only
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use img_hash_linker::algorithm::hash_information::HashInformation;
+    ///
+    /// assert_eq!(HashInformation::from_hash("ffffffffffffffff")?.score, 0.0);
+    /// assert_eq!(HashInformation::from_hash("ffffffff00000000")?.score, 1.0);
+    /// # Ok::<(), String>(())
+    /// ```
+    pub fn from_hash(hash: &str) -> Result<Self, String> {
+        let bit_balance: f64 = bit_balance(&PackedHash::parse(hash)?);
+
+        Ok(HashInformation {
+            bit_balance,
+            grid_variance: None,
+            score: bit_balance,
+        })
+    }
+
+    /// Measures the information of a hash along with the gray levels of the grid it was computed from
+    ///
+    /// The score is the lowest of the bit balance and of the grid contrast, the contrast
+    /// being full from a standard deviation of 16 gray levels: a nearly uniform image gives
+    /// a low score even if noise balances its bits.
+    pub fn from_grid(hash: &str, pixels: &[u8]) -> Result<Self, String> {
+        let bit_balance: f64 = bit_balance(&PackedHash::parse(hash)?);
+
+        let count: f64 = pixels.len().max(1) as f64;
+        let mean: f64 = pixels.iter().map(|&p| p as f64).sum::<f64>() / count;
+        let grid_variance: f64 = pixels
+            .iter()
+            .map(|&p| (p as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        let contrast: f64 = (grid_variance.sqrt() / FULL_CONTRAST_STD_DEV).min(1.0);
+
+        Ok(HashInformation {
+            bit_balance,
+            grid_variance: Some(grid_variance),
+            score: bit_balance.min(contrast),
+        })
+    }
+}
+
+/// What to do with degenerate hashes, whose information score is too low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DegenerateHashPolicy {
+    /// Accept them silently
+    Allow,
+    /// Accept them, with a warning
+    #[default]
+    Warn,
+    /// Refuse them
+    Reject,
+}
+
+/// Detection of degenerate hashes, for enrollment and lookup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DegenerateHashCheck {
+    pub policy: DegenerateHashPolicy,
+    /// Hashes with a lower information score are degenerate (default: 0.25)
+    pub min_score: f64,
+}
+
+impl Default for DegenerateHashCheck {
+    fn default() -> Self {
+        DegenerateHashCheck {
+            policy: DegenerateHashPolicy::default(),
+            min_score: 0.25,
+        }
+    }
+}
+
+impl DegenerateHashCheck {
+    /// Applies the policy to a hash and its information
+    ///
+    /// Returns `Ok(Some(warning))` for a degenerate hash with the `Warn` policy, and an
+    /// error for a degenerate hash with the `Reject` policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use img_hash_linker::algorithm::hash_information::{
+    ///     DegenerateHashCheck, DegenerateHashPolicy, HashInformation,
+    /// };
+    ///
+    /// let check = DegenerateHashCheck {
+    ///     policy: DegenerateHashPolicy::Reject,
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let hash = "ffffffffffffffff";
+    /// assert!(check.check(hash, &HashInformation::from_hash(hash)?).is_err());
+    /// # Ok::<(), String>(())
+    /// ```
+    pub fn check(
+        &self,
+        hash: &str,
+        information: &HashInformation,
+    ) -> Result<Option<String>, String> {
+        if information.score >= self.min_score {
+            return Ok(None);
+        }
+
+        let message: String = format!(
+            "Degenerate hash {} (information score {:.2}, below {:.2}): it may come from a blank or uniform image",
+            hash, information.score, self.min_score
+        );
+
+        match self.policy {
+            DegenerateHashPolicy::Allow => Ok(None),
+            DegenerateHashPolicy::Warn => Ok(Some(message)),
+            DegenerateHashPolicy::Reject => Err(message),
+        }
+    }
+
+    /// Applies the policy to a hash, measuring its information from its bits only
+    pub fn check_hash(&self, hash: &str) -> Result<Option<String>, String> {
+        self.check(hash, &HashInformation::from_hash(hash)?)
+    }
+}
+
+/// Balance between set and unset bits: twice the fraction of the minority value
+fn bit_balance(hash: &PackedHash) -> f64 {
+    // Only count the bits of the pixels, not the padding of the first hex character
+    let bit_count: usize = hash_size_for_hex_len(hash.hex_len())
+        .map_or(hash.bit_count(), |hash_size| {
+            (hash_size * hash_size) as usize
+        });
+    if bit_count == 0 {
+        return 0.0;
+    }
+
+    // The padding bits are the highest bits of the hash: a hash such as `fff` has 9
+    // pixel bits but 12 set bits
+    let set: usize = hash
+        .words()
+        .iter()
+        .enumerate()
+        .map(|(index, word)| {
+            let pixel_bits: usize = bit_count.saturating_sub(index * 64).min(64);
+            let mask: u64 = match pixel_bits {
+                64 => u64::MAX,
+                bits => (1u64 << bits) - 1,
+            };
+            (word & mask).count_ones() as usize
+        })
+        .sum();
+    2.0 * set.min(bit_count - set) as f64 / bit_count as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padding_bits_are_not_counted() {
+        // Hash size 3: 9 pixel bits, all set, and 3 padding bits
+        let information: HashInformation = HashInformation::from_hash("fff").unwrap();
+        assert_eq!(information.bit_balance, 0.0);
+
+        // Hash size 5: 25 pixel bits, all set, and 3 padding bits
+        let information: HashInformation = HashInformation::from_hash("fffffff").unwrap();
+        assert_eq!(information.bit_balance, 0.0);
+    }
+
+    #[test]
+    fn balance_of_odd_sized_hashes() {
+        // Hash size 3, 4 of the 9 pixel bits set
+        let information: HashInformation = HashInformation::from_hash("00f").unwrap();
+        assert!((information.bit_balance - 8.0 / 9.0).abs() < 1e-9);
+
+        // Padding bits set on top of the pixel bits are ignored
+        assert_eq!(
+            HashInformation::from_hash("e0f").unwrap().bit_balance,
+            information.bit_balance
+        );
+    }
+
+    #[test]
+    fn hash_size_16_spans_several_words() {
+        let uniform: String = "f".repeat(64);
+        assert_eq!(HashInformation::from_hash(&uniform).unwrap().score, 0.0);
+
+        let balanced: String = format!("{}{}", "f".repeat(32), "0".repeat(32));
+        assert_eq!(HashInformation::from_hash(&balanced).unwrap().score, 1.0);
+
+        let quarter: String = format!("{}{}", "f".repeat(16), "0".repeat(48));
+        assert_eq!(
+            HashInformation::from_hash(&quarter).unwrap().bit_balance,
+            0.5
+        );
+    }
+
+    #[test]
+    fn empty_and_uniform_grids() {
+        let information: HashInformation = HashInformation::from_grid("", &[]).unwrap();
+        assert_eq!(information.score, 0.0);
+
+        let information: HashInformation =
+            HashInformation::from_grid("ffff0000ffff0000", &[128; 64]).unwrap();
+        assert_eq!(information.grid_variance, Some(0.0));
+        assert_eq!(information.score, 0.0);
+    }
+
+    #[test]
+    fn check_applies_the_policy() {
+        let check = |policy: DegenerateHashPolicy| DegenerateHashCheck {
+            policy,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            check(DegenerateHashPolicy::Allow).check_hash("fff"),
+            Ok(None)
+        );
+        assert!(matches!(
+            check(DegenerateHashPolicy::Warn).check_hash("fff"),
+            Ok(Some(_))
+        ));
+        assert!(
+            check(DegenerateHashPolicy::Reject)
+                .check_hash("fff")
+                .is_err()
+        );
+        assert_eq!(
+            check(DegenerateHashPolicy::Reject).check_hash("ffffffff00000000"),
+            Ok(None)
+        );
+    }
+}
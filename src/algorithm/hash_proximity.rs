@@ -1,90 +1,259 @@
+use crate::algorithm::distance_metric::{DistanceMetric, Hamming};
+use crate::algorithm::packed_hash::{PackedHash, PackedHashes, parse_hex_word};
+use crate::data_handle::dictionary_entry::HashEntry;
+
+/// A dictionary entry found similar to a hash
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashMatch {
+    /// The hash of the dictionary entry
+    pub hash: String,
+    /// The link of the dictionary entry
+    pub link: String,
+    /// The distance between the entry's hash and the searched hash, according to the metric used
+    pub distance: f64,
+    /// The proximity between the entry's hash and the searched hash, from 0.0 to 1.0
+    pub proximity: f64,
+    /// Estimated probability that an unrelated image matches this closely
+    ///
+    /// Only filled in by a [`HashDictionary`](crate::algorithm::hash_dictionary::HashDictionary)
+    /// given an [`ImpostorDistribution`](crate::algorithm::match_confidence::ImpostorDistribution);
+    /// the searches of this module and of `streaming_search` always leave it `None`.
+    pub false_match_probability: Option<f64>,
+    /// Similarity between the searched image and the entry's reference thumbnail, once verified
+    /// (see [`verify_matches`](crate::algorithm::thumbnail_verification::verify_matches))
+    pub thumbnail_similarity: Option<f64>,
+}
+
+/// The result of looking a hash up in a dictionary
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchOutcome {
+    /// The hash itself is in the dictionary
+    Exact(HashMatch),
+    /// A similar hash was found, clearly closer than any entry pointing to another link
+    Confident(HashMatch),
+    /// The best match and the runner-up point to different links, and their
+    /// proximities are within the ambiguity margin of each other
+    Ambiguous {
+        best: HashMatch,
+        runner_up: HashMatch,
+    },
+    /// No hash is similar enough
+    NoMatch,
+}
+
+/// The similarity measures built into the crate, see [`DistanceMetric`] for custom ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProximityMetric {
+    /// Fraction of bits that are equal in both hashes (see [`calculate_hamming_proximity`])
+    #[default]
+    Hamming,
+    /// Absolute difference between each pair of bytes (see [`calculate_hex_hash_proximity`]).
+    /// Kept for thresholds tuned with earlier versions.
+    LegacyByteDifference,
+}
+
+/// Check that two hex hash strings have the same length
+///
+/// Hashes of different lengths come from different hash sizes, so comparing them
+/// would not be meaningful.
+pub fn check_hash_lengths(hash1: &str, hash2: &str) -> Result<(), String> {
+    if hash1.len() != hash2.len() {
+        return Err(format!(
+            "Hash length mismatch: {} has {} hex characters, {} has {}",
+            hash1,
+            hash1.len(),
+            hash2,
+            hash2.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Calculate the Hamming distance between two hex hash strings,
+/// that is the number of bits that differ between them
+/// Both hashes must have the same length
+pub fn hamming_distance(hash1: &str, hash2: &str) -> Result<u32, String> {
+    check_hash_lengths(hash1, hash2)?;
+
+    let mut distance = 0u32;
+
+    // Compare 16 hex characters (64 bits) at a time
+    for (chunk1, chunk2) in hash1.as_bytes().chunks(16).zip(hash2.as_bytes().chunks(16)) {
+        let word1 = parse_hex_word(chunk1).ok_or("Hash1 contains invalid hex characters")?;
+        let word2 = parse_hex_word(chunk2).ok_or("Hash2 contains invalid hex characters")?;
+
+        distance += (word1 ^ word2).count_ones();
+    }
+
+    Ok(distance)
+}
+
+/// Parse a hex hash string into 64-bit words, to compare it many times without re-parsing
+/// The last 16 hex characters go in the first word, so bit `i` of the hash is bit `i % 64` of word `i / 64`
+///
+/// See [`PackedHash`](crate::algorithm::packed_hash::PackedHash) to also keep the length of the hash.
+pub fn parse_hex_hash(hash: &str) -> Result<Vec<u64>, String> {
+    hash.as_bytes()
+        .rchunks(16)
+        .map(|chunk| {
+            parse_hex_word(chunk).ok_or(format!("Hash contains invalid hex characters: {}", hash))
+        })
+        .collect()
+}
+
+/// Calculate the Hamming distance between two hashes parsed with [`parse_hex_hash`]
+pub fn hamming_distance_words(words1: &[u64], words2: &[u64]) -> u32 {
+    words1
+        .iter()
+        .zip(words2)
+        .map(|(w1, w2)| (w1 ^ w2).count_ones())
+        .sum()
+}
+
+/// Calculate the proximity between two hex hash strings based on their Hamming distance
+/// Returns a value between 0.0 and 1.0, where 1.0 means identical hashes
+/// and 0.0 means every bit differs
+/// Both hashes must have the same length
+pub fn calculate_hamming_proximity(hash1: &str, hash2: &str) -> Result<f64, String> {
+    let distance = hamming_distance(hash1, hash2)?;
+    let bit_count = hash1.len() as u32 * 4;
+
+    // Empty hashes are considered identical
+    if bit_count == 0 {
+        return Ok(1.0);
+    }
+
+    Ok(1.0 - (distance as f64 / bit_count as f64))
+}
+
 /// Calculate the proximity between two hex hash strings
 /// Returns a value between 0.0 and 1.0, where 1.0 means identical hashes
 /// and 0.0 means maximum difference
+/// Both hashes must have the same length
+///
+/// Bytes are compared by their numeric difference, which does not reflect how many
+/// bits differ: prefer [`calculate_hamming_proximity`] for new thresholds.
 pub fn calculate_hex_hash_proximity(hash1: &str, hash2: &str) -> Result<f64, String> {
-    // Find the minimum length and truncate both hashes to that length
-    let min_len = hash1.len().min(hash2.len());
+    let total_difference = byte_difference(hash1, hash2)?;
+    let max_possible_difference = max_byte_difference(hash1.len());
 
-    // Ensure the minimum length is even (pairs of hex characters)
-    let truncated_len = if min_len % 2 == 0 {
-        min_len
+    // Calculate proximity: 1.0 - (actual_difference / max_possible_difference)
+    let proximity = if max_possible_difference == 0 {
+        1.0 // Empty hashes are considered identical
     } else {
-        min_len - 1
+        1.0 - (total_difference as f64 / max_possible_difference as f64)
     };
 
-    // Truncate both hashes to the same even length
-    let hash1_truncated = &hash1[..truncated_len];
-    let hash2_truncated = &hash2[..truncated_len];
+    Ok(proximity)
+}
+
+/// Calculate the sum of the absolute differences between each pair of bytes of two hex hash strings
+/// Both hashes must have the same length
+///
+/// Hashes with an odd number of hex characters have their first character compared
+/// as a byte of its own, as if the hash started with a `0`.
+pub fn byte_difference(hash1: &str, hash2: &str) -> Result<u32, String> {
+    check_hash_lengths(hash1, hash2)?;
 
     // Validate that all characters are valid hex
-    if !hash1_truncated.chars().all(|c| c.is_ascii_hexdigit()) {
+    if !hash1.chars().all(|c| c.is_ascii_hexdigit()) {
         return Err("Hash1 contains invalid hex characters".to_string());
     }
-    if !hash2_truncated.chars().all(|c| c.is_ascii_hexdigit()) {
+    if !hash2.chars().all(|c| c.is_ascii_hexdigit()) {
         return Err("Hash2 contains invalid hex characters".to_string());
     }
 
     let mut total_difference = 0u32;
-    let byte_count = hash1_truncated.len() / 2;
-
-    // Compare byte by byte (pairs of hex characters)
-    for i in (0..hash1_truncated.len()).step_by(2) {
-        let byte1_str = &hash1_truncated[i..i + 2];
-        let byte2_str = &hash2_truncated[i..i + 2];
 
-        // Parse hex bytes to u8 values
-        let byte1 = u8::from_str_radix(byte1_str, 16)
-            .map_err(|_| format!("Failed to parse hex byte: {}", byte1_str))?;
-        let byte2 = u8::from_str_radix(byte2_str, 16)
-            .map_err(|_| format!("Failed to parse hex byte: {}", byte2_str))?;
+    // Compare byte by byte (pairs of hex characters), starting from the end
+    // so that a leftover character is the first one
+    for (byte1_str, byte2_str) in hash1.as_bytes().rchunks(2).zip(hash2.as_bytes().rchunks(2)) {
+        // Safe to unwrap since all characters were checked to be hex digits
+        let byte1 = parse_hex_word(byte1_str).unwrap() as u8;
+        let byte2 = parse_hex_word(byte2_str).unwrap() as u8;
 
         // Calculate absolute difference between bytes
-        let difference = (byte1 as i16 - byte2 as i16).abs() as u32;
+        let difference = byte1.abs_diff(byte2) as u32;
         total_difference += difference;
     }
 
-    // Maximum possible difference: 255 per byte * number of bytes
-    let max_possible_difference = 255u32 * byte_count as u32;
-
-    // Calculate proximity: 1.0 - (actual_difference / max_possible_difference)
-    let proximity = if max_possible_difference == 0 {
-        1.0 // Empty hashes are considered identical
-    } else {
-        1.0 - (total_difference as f64 / max_possible_difference as f64)
-    };
+    Ok(total_difference)
+}
 
-    Ok(proximity)
+/// The largest value [`byte_difference`] can return for hashes of `hash_len` hex characters:
+/// 255 per byte, and 15 for a leftover hex character
+pub fn max_byte_difference(hash_len: usize) -> u32 {
+    (255 * (hash_len / 2) + 15 * (hash_len % 2)) as u32
 }
 
 /// Try to find the most similar hash in the list of links
 /// Returns the hash-link pair with the highest proximity above the threshold
 ///
+/// Hashes are compared using their Hamming distance, see [`try_finding_similar_hash_with_metric`]
+/// to use another [`DistanceMetric`].
+///
 /// # Arguments
 ///
 /// * `hash` - The hash to find a similar hash for
-/// * `links` - The list of links to search through
-/// * `proximity_threshold` - The minimum proximity to consider a hash similar (default: 0.95)
+/// * `links` - The list of links to search through, `(hash, link)` pairs or any [`HashEntry`],
+///   owned or borrowed. Only the entries whose hash has the same length as `hash` are compared
+/// * `proximity_threshold` - The minimum proximity to consider a hash similar (default: 0.95).
+///   Entries with their own threshold use it instead
 ///
 /// # Returns
 ///
 /// * `Ok((hash, link, proximity))` - The hash-link pair with the highest proximity above threshold and the rounded proximity value
 /// * `Err(String)` - Error message if no similar hash is found or calculation fails
-pub fn try_finding_similar_hash(
+pub fn try_finding_similar_hash<E: HashEntry>(
     hash: String,
-    links: Vec<(String, String)>,
+    links: impl AsRef<[E]>,
     proximity_threshold: impl Into<Option<f64>>,
+) -> Result<(String, String, f64), String> {
+    try_finding_similar_hash_with_metric(hash, links, proximity_threshold, &Hamming)
+}
+
+/// Try to find the most similar hash in the list of links, using the given metric
+/// Returns the hash-link pair with the highest proximity above the threshold
+///
+/// # Arguments
+///
+/// * `hash` - The hash to find a similar hash for
+/// * `links` - The list of links to search through, `(hash, link)` pairs or any [`HashEntry`],
+///   owned or borrowed. Only the entries whose hash has the same length as `hash` are compared
+/// * `proximity_threshold` - The minimum proximity to consider a hash similar (default: 0.95).
+///   Entries with their own threshold use it instead
+/// * `metric` - The metric used to compare hashes, any [`DistanceMetric`] implementation
+///
+/// # Returns
+///
+/// * `Ok((hash, link, proximity))` - The hash-link pair with the highest proximity above threshold and the rounded proximity value
+/// * `Err(String)` - Error message if no similar hash is found or calculation fails
+pub fn try_finding_similar_hash_with_metric<E: HashEntry, M: DistanceMetric + ?Sized>(
+    hash: String,
+    links: impl AsRef<[E]>,
+    proximity_threshold: impl Into<Option<f64>>,
+    metric: &M,
 ) -> Result<(String, String, f64), String> {
     let minimum_proximity: f64 = proximity_threshold.into().unwrap_or(0.95);
+    let links: &[E] = links.as_ref();
+
+    check_dictionary_has_hash_len(&hash, links)?;
 
     let mut similar_hashes: Vec<(String, String, f64)> = Vec::new();
 
-    // Collect all hashes that pass the proximity threshold
-    for (h, link) in links {
-        let hash_proximity: f64 = calculate_hex_hash_proximity(&hash, &h)
-            .map_err(|e| format!("Failed to calculate proximity: {}", e))?;
+    // Collect all hashes of the same size that pass the proximity threshold
+    for (entry, distance) in entry_distances(&hash, links, metric)
+        .map_err(|e| format!("Failed to calculate proximity: {}", e))?
+    {
+        let hash_proximity: f64 = metric.distance_to_proximity(distance, hash.len());
 
-        if hash_proximity >= minimum_proximity {
-            similar_hashes.push((h, link, hash_proximity));
+        if hash_proximity >= entry.threshold().unwrap_or(minimum_proximity) {
+            similar_hashes.push((
+                entry.hash().to_string(),
+                entry.link().to_string(),
+                hash_proximity,
+            ));
         }
     }
 
@@ -101,3 +270,311 @@ pub fn try_finding_similar_hash(
 
     Ok(most_similar)
 }
+
+/// Find the `k` hashes most similar to the given hash in the list of links
+/// Returns the matches above the threshold, sorted from the most to the least similar
+///
+/// Entries with the same proximity keep their order from the list of links.
+///
+/// # Arguments
+///
+/// * `hash` - The hash to find similar hashes for
+/// * `links` - The list of links to search through, `(hash, link)` pairs or any [`HashEntry`].
+///   Only the entries whose hash has the same length as `hash` are compared
+/// * `k` - The maximum number of matches to return
+/// * `proximity_threshold` - The minimum proximity to consider a hash similar (default: 0.95).
+///   Use a lower threshold to also get near-misses, e.g. for "did you mean" suggestions.
+///   Entries with their own threshold use it instead
+/// * `metric` - The metric used to compare hashes, any [`DistanceMetric`] implementation
+///
+/// # Returns
+///
+/// * `Ok(Vec<HashMatch>)` - Up to `k` matches, with their distance and proximity (empty if none is similar)
+/// * `Err(String)` - Error message if the calculation fails
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::distance_metric::Hamming;
+/// use img_hash_linker::algorithm::hash_proximity::find_top_k_similar_hashes;
+///
+/// let links = vec![
+///     ("ffffffff00000000".to_string(), "https://example.com".to_string()),
+///     ("ffffffff00000003".to_string(), "https://another.com".to_string()),
+///     ("00000000ffffffff".to_string(), "https://third.com".to_string()),
+/// ];
+///
+/// let matches = find_top_k_similar_hashes("ffffffff00000001", &links, 2, 0.9, &Hamming)?;
+/// assert_eq!(matches.len(), 2);
+/// assert_eq!(matches[0].link, "https://example.com");
+/// assert_eq!(matches[1].distance, 1.0);
+/// # Ok::<(), String>(())
+/// ```
+pub fn find_top_k_similar_hashes<E: HashEntry, M: DistanceMetric + ?Sized>(
+    hash: &str,
+    links: &[E],
+    k: usize,
+    proximity_threshold: impl Into<Option<f64>>,
+    metric: &M,
+) -> Result<Vec<HashMatch>, String> {
+    let minimum_proximity: f64 = proximity_threshold.into().unwrap_or(0.95);
+
+    check_dictionary_has_hash_len(hash, links)?;
+
+    let mut similar_hashes: Vec<HashMatch> = Vec::new();
+
+    // Collect all hashes of the same size that pass the proximity threshold
+    for (entry, distance) in entry_distances(hash, links, metric)
+        .map_err(|e| format!("Failed to calculate distance: {}", e))?
+    {
+        let hash_proximity: f64 = metric.distance_to_proximity(distance, hash.len());
+
+        if hash_proximity >= entry.threshold().unwrap_or(minimum_proximity) {
+            similar_hashes.push(HashMatch {
+                hash: entry.hash().to_string(),
+                link: entry.link().to_string(),
+                distance,
+                proximity: hash_proximity,
+                false_match_probability: None,
+                thumbnail_similarity: None,
+            });
+        }
+    }
+
+    // Sort from the most to the least similar, keeping the original order for ties
+    similar_hashes.sort_by(|a, b| {
+        b.proximity
+            .partial_cmp(&a.proximity)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    similar_hashes.truncate(k);
+
+    Ok(similar_hashes)
+}
+
+/// Look a hash up in the list of links, telling confident matches from ambiguous ones
+///
+/// The best match is compared with the runner-up, the best match pointing to a different
+/// link: if their proximities are within `ambiguity_margin` of each other, the outcome
+/// is [`MatchOutcome::Ambiguous`] rather than silently picking one of them.
+///
+/// # Arguments
+///
+/// * `hash` - The hash to look up
+/// * `links` - The list of links to search through, `(hash, link)` pairs or any [`HashEntry`].
+///   Only the entries whose hash has the same length as `hash` are compared
+/// * `proximity_threshold` - The minimum proximity to consider a hash similar (default: 0.95).
+///   Entries with their own threshold use it instead
+/// * `ambiguity_margin` - The smallest proximity gap between the best match and the
+///   runner-up for the match to be confident (default: 0.01)
+/// * `metric` - The metric used to compare hashes, any [`DistanceMetric`] implementation
+///
+/// # Returns
+///
+/// * `Ok(MatchOutcome)` - The outcome of the lookup
+/// * `Err(String)` - Error message if the calculation fails
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::distance_metric::Hamming;
+/// use img_hash_linker::algorithm::hash_proximity::{MatchOutcome, find_match};
+///
+/// let links = vec![
+///     ("ffffffff00000000".to_string(), "https://example.com".to_string()),
+///     ("ffffffff00000003".to_string(), "https://another.com".to_string()),
+/// ];
+///
+/// // One bit away from both entries
+/// let outcome = find_match("ffffffff00000001", &links, None, None, &Hamming)?;
+/// assert!(matches!(outcome, MatchOutcome::Ambiguous { .. }));
+/// # Ok::<(), String>(())
+/// ```
+pub fn find_match<E: HashEntry, M: DistanceMetric + ?Sized>(
+    hash: &str,
+    links: &[E],
+    proximity_threshold: impl Into<Option<f64>>,
+    ambiguity_margin: impl Into<Option<f64>>,
+    metric: &M,
+) -> Result<MatchOutcome, String> {
+    let candidates: Vec<HashMatch> =
+        find_top_k_similar_hashes(hash, links, usize::MAX, proximity_threshold, metric)?;
+
+    Ok(classify_matches(
+        hash,
+        candidates,
+        ambiguity_margin.into().unwrap_or(0.01),
+    ))
+}
+
+/// The entries whose hash has the same length as `hash`, in order, with their distance to it
+///
+/// Metrics comparing the bits of the hashes (see [`DistanceMetric::distance_words`]) get
+/// every hash parsed once into [`PackedHashes`], rather than on every comparison.
+fn entry_distances<'a, E: HashEntry, M: DistanceMetric + ?Sized>(
+    hash: &str,
+    links: &'a [E],
+    metric: &M,
+) -> Result<Vec<(&'a E, f64)>, String> {
+    let entries: Vec<&E> = links
+        .iter()
+        .filter(|entry| entry.hash().len() == hash.len())
+        .collect();
+
+    let query: Option<PackedHash> = PackedHash::parse(hash).ok().filter(|query| {
+        metric
+            .distance_words(query.words(), query.words(), hash.len())
+            .is_some()
+    });
+    let Some(query) = query else {
+        return entries
+            .into_iter()
+            .map(|entry| Ok((entry, metric.distance(hash, entry.hash())?)))
+            .collect();
+    };
+
+    let mut hashes: PackedHashes = PackedHashes::new();
+    for entry in &entries {
+        hashes.push(&PackedHash::parse(entry.hash())?)?;
+    }
+
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let words: &[u64] = hashes.get(index).unwrap_or_default();
+            let distance: Option<f64> = metric.distance_words(query.words(), words, hash.len());
+            (entry, distance.unwrap_or_default())
+        })
+        .collect())
+}
+
+/// Check that a dictionary holds hashes of the same length as the searched hash
+///
+/// Dictionaries may mix several hash sizes, but each hash is only compared like-for-like:
+/// searching a hash of a size missing from a non-empty dictionary is an error.
+fn check_dictionary_has_hash_len<E: HashEntry>(hash: &str, links: &[E]) -> Result<(), String> {
+    if !links.is_empty() && !links.iter().any(|entry| entry.hash().len() == hash.len()) {
+        return Err(format!(
+            "Hash length mismatch: no hash of {} hex characters in the dictionary ({})",
+            hash.len(),
+            hash
+        ));
+    }
+
+    Ok(())
+}
+
+/// Turn the matches of a hash, sorted from the most to the least similar, into a [`MatchOutcome`]
+///
+/// See [`find_match`] for how ambiguous matches are detected.
+pub fn classify_matches(
+    hash: &str,
+    candidates: Vec<HashMatch>,
+    ambiguity_margin: f64,
+) -> MatchOutcome {
+    let mut candidates = candidates.into_iter();
+
+    let Some(best) = candidates.next() else {
+        return MatchOutcome::NoMatch;
+    };
+
+    // Other entries pointing to the same link do not make the match ambiguous
+    let runner_up: Option<HashMatch> = candidates.find(|candidate| candidate.link != best.link);
+
+    match runner_up {
+        Some(runner_up) if best.proximity - runner_up.proximity <= ambiguity_margin => {
+            MatchOutcome::Ambiguous { best, runner_up }
+        }
+        _ if best.hash == hash => MatchOutcome::Exact(best),
+        _ => MatchOutcome::Confident(best),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::distance_metric::{
+        LegacyByteDifference, NormalizedHamming, WeightedBits,
+    };
+
+    fn links(hashes: &[&str]) -> Vec<(String, String)> {
+        hashes
+            .iter()
+            .enumerate()
+            .map(|(index, hash)| (hash.to_string(), format!("https://{}.com", index)))
+            .collect()
+    }
+
+    /// Checks that the search gives the distances of the metric on the hex strings
+    fn check_distances<M: DistanceMetric>(metric: &M, hash: &str, hashes: &[&str]) {
+        let matches: Vec<HashMatch> =
+            find_top_k_similar_hashes(hash, &links(hashes), usize::MAX, 0.0, metric).unwrap();
+        assert_eq!(matches.len(), hashes.len());
+        for found in &matches {
+            assert_eq!(found.distance, metric.distance(hash, &found.hash).unwrap());
+        }
+    }
+
+    #[test]
+    fn packed_distances_match_the_hex_strings() {
+        let hashes: [&str; 3] = ["ffff0000ffff0000", "0123456789abcdef", "FFFF0000FFFF0001"];
+        let query: &str = "fedcba9876543210";
+        check_distances(&Hamming, query, &hashes);
+        check_distances(&NormalizedHamming, query, &hashes);
+        check_distances(&ProximityMetric::Hamming, query, &hashes);
+        check_distances(&LegacyByteDifference, query, &hashes);
+        check_distances(
+            &WeightedBits::new((0..64).map(|bit| bit as f64 / 64.0).collect()),
+            query,
+            &hashes,
+        );
+
+        // Hashes spanning several words, and odd sizes
+        let large: String = "0f".repeat(32);
+        check_distances(&Hamming, &"f0".repeat(32), &[&large, &"ff".repeat(32)]);
+        check_distances(&Hamming, "1ff", &["000", "1fe"]);
+    }
+
+    #[test]
+    fn empty_hashes_are_identical() {
+        let matches: Vec<HashMatch> =
+            find_top_k_similar_hashes("", &links(&[""]), 1, None, &Hamming).unwrap();
+        assert_eq!(matches[0].proximity, 1.0);
+    }
+
+    #[test]
+    fn invalid_hashes_are_errors() {
+        let result: Result<Vec<HashMatch>, String> =
+            find_top_k_similar_hashes("ffff", &links(&["zzzz"]), 1, None, &Hamming);
+        assert!(result.is_err());
+
+        let result: Result<Vec<HashMatch>, String> =
+            find_top_k_similar_hashes("zzzz", &links(&["ffff"]), 1, None, &Hamming);
+        assert!(result.is_err());
+
+        // Weights not covering the hashes are an error, as with the hex strings
+        let result: Result<Vec<HashMatch>, String> = find_top_k_similar_hashes(
+            "ffff",
+            &links(&["ffff"]),
+            1,
+            None,
+            &WeightedBits::new(vec![1.0; 8]),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn ties_are_broken_as_before() {
+        // try_finding_similar_hash keeps the last of equally similar hashes,
+        // find_top_k_similar_hashes the first
+        let links: Vec<(String, String)> = links(&["ffff0000ffff0001", "ffff0000ffff0002"]);
+        let (_, link, _) =
+            try_finding_similar_hash("ffff0000ffff0000".to_string(), &links, None).unwrap();
+        assert_eq!(link, "https://1.com");
+
+        let matches: Vec<HashMatch> =
+            find_top_k_similar_hashes("ffff0000ffff0000", &links, 2, None, &Hamming).unwrap();
+        assert_eq!(matches[0].link, "https://0.com");
+    }
+}
@@ -0,0 +1,223 @@
+use crate::algorithm::packed_hash::PackedHash;
+use crate::data_handle::dictionary_entry::HashEntry;
+
+/// Default number of entry pairs measured per hash size, see [`ImpostorDistribution::from_entries`]
+pub const DEFAULT_MAX_PAIRS: usize = 100_000;
+
+/// The distribution of Hamming distances between unrelated dictionary entries, to turn
+/// a match distance into an estimated false-match probability.
+///
+/// A proximity of 90% means little on its own: in a dictionary of similar pictures,
+/// unrelated entries may often be that close to each other. Pairs of entries pointing to
+/// different links are "impostors", and the fraction of impostor pairs at most at a given
+/// distance estimates the probability that an unrelated image matches at that distance.
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::match_confidence::ImpostorDistribution;
+///
+/// let links = vec![
+///     ("ffffffff00000000".to_string(), "https://example.com".to_string()),
+///     ("00000000ffffffff".to_string(), "https://another.com".to_string()),
+///     ("ffff0000ffff0000".to_string(), "https://third.com".to_string()),
+/// ];
+///
+/// let impostors = ImpostorDistribution::from_entries(&links, None)?;
+/// assert_eq!(impostors.pair_count(16), 3);
+///
+/// // No unrelated entries are closer than 32 bits apart
+/// let close = impostors.false_match_probability(16, 2.0).unwrap();
+/// let far = impostors.false_match_probability(16, 40.0).unwrap();
+/// assert!(close < far);
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImpostorDistribution {
+    /// One distribution per hash length, sorted by length
+    distributions: Vec<Distribution>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Distribution {
+    hex_len: usize,
+    /// Number of impostor pairs at most at each distance, from 0 to the bit count
+    cumulative_counts: Vec<u64>,
+    pair_count: u64,
+}
+
+impl ImpostorDistribution {
+    /// Measures the distances between the entries pointing to different links, for each
+    /// hash size of a list of links, `(hash, link)` pairs or any [`HashEntry`]
+    ///
+    /// Measuring every pair takes quadratic time: above `max_pairs` pairs for a hash size
+    /// (default: [`DEFAULT_MAX_PAIRS`]), a fixed pseudo-random sample of pairs is measured,
+    /// so the same dictionary always gives the same distribution.
+    pub fn from_entries<E: HashEntry>(
+        links: &[E],
+        max_pairs: impl Into<Option<usize>>,
+    ) -> Result<Self, String> {
+        let max_pairs: usize = max_pairs.into().unwrap_or(DEFAULT_MAX_PAIRS);
+
+        // Group the entries by hash length
+        let mut groups: Vec<(usize, Vec<(PackedHash, &str)>)> = Vec::new();
+        for entry in links {
+            let hash: PackedHash = PackedHash::parse(entry.hash())?;
+            let index: usize = match groups.binary_search_by_key(&hash.hex_len(), |g| g.0) {
+                Ok(index) => index,
+                Err(index) => {
+                    groups.insert(index, (hash.hex_len(), Vec::new()));
+                    index
+                }
+            };
+            groups[index].1.push((hash, entry.link()));
+        }
+
+        let distributions: Vec<Distribution> = groups
+            .iter()
+            .map(|(hex_len, entries)| Distribution::measure(*hex_len, entries, max_pairs))
+            .collect();
+
+        Ok(ImpostorDistribution { distributions })
+    }
+
+    /// Rebuilds a distribution from the number of impostor pairs at each distance, for
+    /// each hash length, as given by [`distance_counts`](Self::distance_counts)
+    ///
+    /// Lengths given twice keep their last counts, and the counts of each length are
+    /// padded with zeros up to its bit count.
+    pub fn from_distance_counts(counts: Vec<(usize, Vec<u64>)>) -> Result<Self, String> {
+        let mut distributions: Vec<Distribution> = Vec::new();
+        for (hex_len, mut counts) in counts {
+            let bit_count: usize = hex_len * 4;
+            if counts.len() > bit_count + 1 {
+                return Err(format!(
+                    "Impostor pairs of {} hex characters cannot be more than {} bits apart",
+                    hex_len, bit_count
+                ));
+            }
+            counts.resize(bit_count + 1, 0);
+
+            let distribution: Distribution = Distribution::from_counts(hex_len, &counts);
+            match distributions.binary_search_by_key(&hex_len, |d| d.hex_len) {
+                Ok(index) => distributions[index] = distribution,
+                Err(index) => distributions.insert(index, distribution),
+            }
+        }
+
+        Ok(ImpostorDistribution { distributions })
+    }
+
+    /// Hash lengths, in hex characters, with a measured distribution
+    pub fn hex_lens(&self) -> Vec<usize> {
+        self.distributions.iter().map(|d| d.hex_len).collect()
+    }
+
+    /// Number of impostor pairs at each distance, from 0 to the bit count, for the hashes
+    /// of `hex_len` hex characters
+    pub fn distance_counts(&self, hex_len: usize) -> Option<Vec<u64>> {
+        let distribution: &Distribution = self.distribution(hex_len)?;
+        let mut previous: u64 = 0;
+        Some(
+            distribution
+                .cumulative_counts
+                .iter()
+                .map(|&total| {
+                    let count: u64 = total - previous;
+                    previous = total;
+                    count
+                })
+                .collect(),
+        )
+    }
+
+    /// Number of impostor pairs measured for the hashes of `hex_len` hex characters
+    pub fn pair_count(&self, hex_len: usize) -> u64 {
+        self.distribution(hex_len).map_or(0, |d| d.pair_count)
+    }
+
+    /// Estimated probability that an unrelated image has a hash of `hex_len` hex characters
+    /// at most at `distance` bits from a dictionary entry
+    ///
+    /// Returns `None` if no impostor pair of that length was measured. The estimate is never
+    /// exactly 0: with `n` pairs, none of them that close, it is `1 / (n + 1)`.
+    pub fn false_match_probability(&self, hex_len: usize, distance: f64) -> Option<f64> {
+        let distribution: &Distribution = self.distribution(hex_len)?;
+        if distribution.pair_count == 0 {
+            return None;
+        }
+
+        let closer: u64 = if distance < 0.0 {
+            0
+        } else {
+            let index: usize =
+                (distance.floor() as usize).min(distribution.cumulative_counts.len() - 1);
+            distribution.cumulative_counts[index]
+        };
+
+        Some((closer + 1) as f64 / (distribution.pair_count + 1) as f64)
+    }
+
+    fn distribution(&self, hex_len: usize) -> Option<&Distribution> {
+        self.distributions
+            .binary_search_by_key(&hex_len, |d| d.hex_len)
+            .ok()
+            .map(|index| &self.distributions[index])
+    }
+}
+
+impl Distribution {
+    fn measure(hex_len: usize, entries: &[(PackedHash, &str)], max_pairs: usize) -> Self {
+        let bit_count: usize = hex_len * 4;
+        let mut counts: Vec<u64> = vec![0; bit_count + 1];
+
+        let mut add_pair = |a: &(PackedHash, &str), b: &(PackedHash, &str)| {
+            // Entries pointing to the same link are the same picture, not impostors
+            if a.1 != b.1 {
+                counts[a.0.hamming_distance(&b.0) as usize] += 1;
+            }
+        };
+
+        let n: usize = entries.len();
+        let total_pairs: usize = n * n.saturating_sub(1) / 2;
+        if total_pairs <= max_pairs {
+            for i in 0..n {
+                for j in (i + 1)..n {
+                    add_pair(&entries[i], &entries[j]);
+                }
+            }
+        } else {
+            // xorshift64, with a fixed seed so that the sample is reproducible
+            let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+            let mut next = |bound: usize| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % bound as u64) as usize
+            };
+            for _ in 0..max_pairs {
+                let i: usize = next(n);
+                let j: usize = (i + 1 + next(n - 1)) % n;
+                add_pair(&entries[i], &entries[j]);
+            }
+        }
+
+        Distribution::from_counts(hex_len, &counts)
+    }
+
+    fn from_counts(hex_len: usize, counts: &[u64]) -> Self {
+        let cumulative_counts: Vec<u64> = counts
+            .iter()
+            .scan(0, |total, &count| {
+                *total += count;
+                Some(*total)
+            })
+            .collect();
+
+        Distribution {
+            hex_len,
+            pair_count: cumulative_counts.last().copied().unwrap_or(0),
+            cumulative_counts,
+        }
+    }
+}
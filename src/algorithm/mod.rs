@@ -1,3 +1,18 @@
 pub mod ahash;
+pub mod batch;
+pub mod bit_weights;
+pub mod bk_tree;
+pub mod calibration;
+pub mod clustering;
+pub mod content_region;
+pub mod distance_metric;
+pub mod hash_dictionary;
+pub mod hash_index;
+pub mod hash_information;
 pub mod hash_proximity;
+pub mod match_confidence;
+pub mod multi_index;
+pub mod packed_hash;
 pub mod remove_borders;
+pub mod streaming_search;
+pub mod thumbnail_verification;
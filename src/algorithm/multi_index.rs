@@ -0,0 +1,455 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::algorithm::hash_index::HashIndex;
+use crate::algorithm::packed_hash::PackedHash;
+use crate::data_handle::dictionary_entry::HashEntry;
+
+/// A multi-index hashing (MIH) index for exact Hamming radius queries on large dictionaries.
+///
+/// Each hash is split into `substring_count` substrings, and each substring is indexed in
+/// its own hash table. If two hashes are within `r` bits of each other, at least one of
+/// their substrings is within `r / substring_count` bits (the pigeonhole principle), so
+/// looking up the few neighbours of each query substring finds every match. Candidates
+/// are then verified against the full hash.
+///
+/// All the hashes must have the same length. A good `substring_count` is about
+/// `bit_count / log2(entry_count)`, e.g. 4 substrings of 16 bits for 64-bit hashes.
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::hash_index::HashIndex;
+/// use img_hash_linker::algorithm::multi_index::MultiIndex;
+///
+/// let links = vec![
+///     ("ffff0000ffff0000".to_string(), "https://example.com".to_string()),
+///     ("0000ffff0000ffff".to_string(), "https://another.com".to_string()),
+/// ];
+///
+/// let index = MultiIndex::from_entries(&links, 4)?;
+///
+/// let matches = index.find_within("ffff0000ffff0003", 4)?;
+/// assert_eq!(matches.len(), 1);
+/// assert_eq!(matches[0].2, 2);
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultiIndex {
+    substring_count: usize,
+    hash_len: Option<usize>,
+    /// Bit ranges `(start, length)` of each substring
+    substrings: Vec<(usize, usize)>,
+    /// One table per substring, from substring value to entry positions
+    tables: Vec<HashMap<u64, Vec<usize>>>,
+    entries: Vec<MultiIndexEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct MultiIndexEntry {
+    hash: String,
+    link: String,
+    packed: PackedHash,
+}
+
+impl MultiIndex {
+    /// Creates an empty index splitting hashes into `substring_count` substrings
+    pub fn new(substring_count: usize) -> Result<Self, String> {
+        if substring_count == 0 {
+            return Err("Multi-index hashing needs at least one substring".to_string());
+        }
+
+        Ok(MultiIndex {
+            substring_count,
+            hash_len: None,
+            substrings: Vec::new(),
+            tables: vec![HashMap::new(); substring_count],
+            entries: Vec::new(),
+        })
+    }
+
+    /// Builds an index from a list of links, `(hash, link)` pairs or any [`HashEntry`]
+    pub fn from_entries<E: HashEntry>(links: &[E], substring_count: usize) -> Result<Self, String> {
+        let mut index: MultiIndex = MultiIndex::new(substring_count)?;
+        for entry in links {
+            index.insert(entry.hash(), entry.link())?;
+        }
+        Ok(index)
+    }
+
+    /// Number of `(hash, link)` entries in the index
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the index has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Length, in hex characters, of the hashes in the index (`None` while it is empty)
+    pub fn hash_len(&self) -> Option<usize> {
+        self.hash_len
+    }
+
+    /// Checks that a hash matches the length of the indexed hashes, and parses it
+    fn parse_hash(&self, hash: &str) -> Result<PackedHash, String> {
+        if let Some(hash_len) = self.hash_len
+            && hash.len() != hash_len
+        {
+            return Err(format!(
+                "Hash length mismatch: expected {} hex characters, got {} ({})",
+                hash_len,
+                hash.len(),
+                hash
+            ));
+        }
+
+        PackedHash::parse(hash)
+    }
+
+    /// Splits `bit_count` bits into substrings of nearly equal lengths
+    fn split_bits(&self, bit_count: usize) -> Result<Vec<(usize, usize)>, String> {
+        let count: usize = self.substring_count;
+        if bit_count < count || bit_count.div_ceil(count) > 64 {
+            return Err(format!(
+                "Cannot split {}-bit hashes into {} substrings of 1 to 64 bits",
+                bit_count, count
+            ));
+        }
+
+        let mut substrings: Vec<(usize, usize)> = Vec::with_capacity(count);
+        let mut start: usize = 0;
+        for i in 0..count {
+            // The first substrings take one extra bit when the split is uneven
+            let length: usize = bit_count / count + usize::from(i < bit_count % count);
+            substrings.push((start, length));
+            start += length;
+        }
+        Ok(substrings)
+    }
+}
+
+impl HashIndex for MultiIndex {
+    fn insert(&mut self, hash: &str, link: &str) -> Result<(), String> {
+        let packed: PackedHash = self.parse_hash(hash)?;
+
+        if self.hash_len.is_none() {
+            self.substrings = self.split_bits(hash.len() * 4)?;
+            self.hash_len = Some(hash.len());
+        }
+
+        let position: usize = self.entries.len();
+        for (table, &(start, length)) in self.tables.iter_mut().zip(&self.substrings) {
+            table
+                .entry(extract_bits(packed.words(), start, length))
+                .or_default()
+                .push(position);
+        }
+
+        self.entries.push(MultiIndexEntry {
+            hash: hash.to_string(),
+            link: link.to_string(),
+            packed,
+        });
+        Ok(())
+    }
+
+    fn find_within(&self, hash: &str, radius: u32) -> Result<Vec<(String, String, u32)>, String> {
+        let packed: PackedHash = self.parse_hash(hash)?;
+        let mut matches: Vec<(String, String, u32)> = Vec::new();
+
+        if self.entries.is_empty() {
+            return Ok(matches);
+        }
+
+        // At least one substring of every match is within this many bits of the query's
+        let substring_radius: usize = radius as usize / self.substring_count;
+
+        let probe_count: usize = self
+            .substrings
+            .iter()
+            .map(|&(_, length)| neighbour_count(length, substring_radius))
+            .sum();
+
+        // With a large radius, probing costs more than checking every entry
+        let candidates: Vec<usize> = if probe_count >= self.entries.len() {
+            (0..self.entries.len()).collect()
+        } else {
+            let mut seen: HashSet<usize> = HashSet::new();
+            let mut candidates: Vec<usize> = Vec::new();
+
+            for (table, &(start, length)) in self.tables.iter().zip(&self.substrings) {
+                let query_substring: u64 = extract_bits(packed.words(), start, length);
+                for_each_neighbour(query_substring, length, substring_radius, &mut |value| {
+                    if let Some(positions) = table.get(&value) {
+                        for &position in positions {
+                            if seen.insert(position) {
+                                candidates.push(position);
+                            }
+                        }
+                    }
+                });
+            }
+            candidates
+        };
+
+        // Verify the candidates against the full hash
+        for position in candidates {
+            let entry: &MultiIndexEntry = &self.entries[position];
+            let distance: u32 = packed.hamming_distance(&entry.packed);
+            if distance <= radius {
+                matches.push((entry.hash.clone(), entry.link.clone(), distance));
+            }
+        }
+
+        matches.sort_by_key(|(_, _, distance)| *distance);
+        Ok(matches)
+    }
+}
+
+/// Extracts `length` bits starting at bit `start` of the words of a [`PackedHash`]
+fn extract_bits(words: &[u64], start: usize, length: usize) -> u64 {
+    let word: usize = start / 64;
+    let offset: usize = start % 64;
+
+    let mut value: u64 = words[word] >> offset;
+    if offset + length > 64 {
+        value |= words[word + 1] << (64 - offset);
+    }
+
+    if length == 64 {
+        value
+    } else {
+        value & ((1u64 << length) - 1)
+    }
+}
+
+/// Number of values within `radius` bits of a `length`-bit value
+fn neighbour_count(length: usize, radius: usize) -> usize {
+    let mut total: usize = 0;
+    let mut combinations: usize = 1; // C(length, 0)
+    for flipped in 0..=radius.min(length) {
+        total = total.saturating_add(combinations);
+        combinations = combinations.saturating_mul(length - flipped) / (flipped + 1);
+    }
+    total
+}
+
+/// Calls `visit` with every `length`-bit value within `radius` bits of `value`
+fn for_each_neighbour(value: u64, length: usize, radius: usize, visit: &mut dyn FnMut(u64)) {
+    fn flip_from(
+        value: u64,
+        first_bit: usize,
+        length: usize,
+        remaining: usize,
+        visit: &mut dyn FnMut(u64),
+    ) {
+        visit(value);
+        if remaining == 0 {
+            return;
+        }
+        for bit in first_bit..length {
+            flip_from(value ^ (1 << bit), bit + 1, length, remaining - 1, visit);
+        }
+    }
+
+    flip_from(value, 0, length, radius, visit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small deterministic xorshift generator
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+
+        fn hash(&mut self, hex_len: usize) -> String {
+            (0..hex_len)
+                .map(|_| char::from_digit(self.next(16) as u32, 16).unwrap())
+                .collect()
+        }
+
+        /// Flips up to `max_flips` random bits of a hash
+        fn near(&mut self, hash: &str, max_flips: usize) -> String {
+            let mut nibbles: Vec<u32> = hash.chars().map(|c| c.to_digit(16).unwrap()).collect();
+            for _ in 0..self.next(max_flips + 1) {
+                let bit: usize = self.next(hash.len() * 4);
+                nibbles[hash.len() - 1 - bit / 4] ^= 1 << (bit % 4);
+            }
+            nibbles
+                .iter()
+                .map(|&nibble| char::from_digit(nibble, 16).unwrap())
+                .collect()
+        }
+    }
+
+    /// Clusters of similar hashes, so that queries have several matches
+    fn clustered_links(rng: &mut Rng, hex_len: usize, clusters: usize) -> Vec<(String, String)> {
+        let mut links: Vec<(String, String)> = Vec::new();
+        for cluster in 0..clusters {
+            let base: String = rng.hash(hex_len);
+            for _ in 0..8 {
+                links.push((
+                    rng.near(&base, 8),
+                    format!("https://example.com/{}", cluster),
+                ));
+            }
+        }
+        links
+    }
+
+    fn brute_force(
+        links: &[(String, String)],
+        hash: &str,
+        radius: u32,
+    ) -> Vec<(String, String, u32)> {
+        let query: PackedHash = PackedHash::parse(hash).unwrap();
+        let mut matches: Vec<(String, String, u32)> = links
+            .iter()
+            .filter_map(|(entry_hash, link)| {
+                let distance: u32 = query.hamming_distance(&PackedHash::parse(entry_hash).unwrap());
+                (distance <= radius).then(|| (entry_hash.clone(), link.clone(), distance))
+            })
+            .collect();
+        matches.sort();
+        matches
+    }
+
+    /// Checks `find_within` against a brute-force scan, for queries near the entries
+    fn assert_finds_every_match(hex_len: usize, substring_count: usize, radius: u32) {
+        let mut rng: Rng = Rng(0x2545_f491_4f6c_dd1d ^ (hex_len * 31 + substring_count) as u64);
+        let links: Vec<(String, String)> = clustered_links(&mut rng, hex_len, 100);
+        let index: MultiIndex = MultiIndex::from_entries(&links, substring_count).unwrap();
+
+        for _ in 0..50 {
+            let source: &str = &links[rng.next(links.len())].0;
+            let query: String = rng.near(source, radius as usize + 2);
+
+            let mut found: Vec<(String, String, u32)> = index.find_within(&query, radius).unwrap();
+            assert!(found.windows(2).all(|pair| pair[0].2 <= pair[1].2));
+            found.sort();
+            assert_eq!(
+                found,
+                brute_force(&links, &query, radius),
+                "{} bits in {} substrings, radius {}",
+                hex_len * 4,
+                substring_count,
+                radius
+            );
+        }
+    }
+
+    #[test]
+    fn even_splits_find_every_match() {
+        assert_finds_every_match(16, 4, 3);
+    }
+
+    #[test]
+    fn uneven_splits_find_every_match() {
+        let index: MultiIndex = MultiIndex::new(5).unwrap();
+        assert_eq!(
+            index.split_bits(64).unwrap(),
+            vec![(0, 13), (13, 13), (26, 13), (39, 13), (52, 12)]
+        );
+
+        assert_finds_every_match(16, 5, 4);
+        assert_finds_every_match(16, 3, 2);
+    }
+
+    #[test]
+    fn substrings_crossing_words_find_every_match() {
+        // 100 bits in 3 substrings: (0, 34), (34, 33) and (67, 33), the second one
+        // spanning the first two words
+        let index: MultiIndex = MultiIndex::new(3).unwrap();
+        assert_eq!(
+            index.split_bits(100).unwrap(),
+            vec![(0, 34), (34, 33), (67, 33)]
+        );
+
+        assert_finds_every_match(25, 3, 5);
+        assert_finds_every_match(32, 3, 4);
+    }
+
+    #[test]
+    fn radii_above_the_substring_count_find_every_match() {
+        // Each substring is searched within 1 or 2 bits
+        assert_finds_every_match(16, 4, 6);
+        assert_finds_every_match(16, 4, 8);
+        assert_finds_every_match(16, 2, 5);
+    }
+
+    #[test]
+    fn large_radii_fall_back_to_a_full_scan() {
+        let mut rng: Rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let links: Vec<(String, String)> = clustered_links(&mut rng, 16, 4);
+        let index: MultiIndex = MultiIndex::from_entries(&links, 4).unwrap();
+
+        // Probing the neighbours of each substring would cost more than the scan
+        let radius: u32 = 12;
+        let probe_count: usize = 4 * neighbour_count(16, radius as usize / 4);
+        assert!(probe_count >= links.len());
+
+        for (hash, _) in &links {
+            let query: String = rng.near(hash, 14);
+            let mut found: Vec<(String, String, u32)> = index.find_within(&query, radius).unwrap();
+            found.sort();
+            assert_eq!(found, brute_force(&links, &query, radius));
+        }
+    }
+
+    #[test]
+    fn empty_indexes_find_nothing() {
+        let index: MultiIndex = MultiIndex::new(4).unwrap();
+        assert_eq!(index.find_within("ffff0000ffff0000", 64), Ok(Vec::new()));
+        assert!(MultiIndex::new(0).is_err());
+    }
+
+    #[test]
+    fn hashes_must_split_into_substrings() {
+        let mut index: MultiIndex = MultiIndex::new(4).unwrap();
+        assert!(index.insert("fff", "https://example.com").is_ok());
+        assert!(index.insert("ffff", "https://example.com").is_err());
+
+        // 4 bits cannot be split into 5 substrings
+        let mut index: MultiIndex = MultiIndex::new(5).unwrap();
+        assert!(index.insert("f", "https://example.com").is_err());
+    }
+
+    #[test]
+    fn bits_are_extracted_across_words() {
+        let words: [u64; 2] = [0xf000_0000_0000_0001, 0x0000_0000_0000_0005];
+
+        assert_eq!(extract_bits(&words, 0, 1), 1);
+        assert_eq!(extract_bits(&words, 60, 4), 0xf);
+        assert_eq!(extract_bits(&words, 60, 8), 0x5f);
+        assert_eq!(extract_bits(&words, 62, 3), 0b111);
+        assert_eq!(extract_bits(&words, 0, 64), words[0]);
+        assert_eq!(extract_bits(&words, 64, 3), 0b101);
+    }
+
+    #[test]
+    fn every_neighbour_is_visited_once() {
+        for (length, radius) in [(1, 0), (1, 3), (8, 2), (13, 3), (12, 12)] {
+            let mut visited: Vec<u64> = Vec::new();
+            for_each_neighbour(0b1010, length, radius, &mut |value| visited.push(value));
+
+            let count: usize = visited.len();
+            visited.sort_unstable();
+            visited.dedup();
+            assert_eq!(visited.len(), count);
+            assert_eq!(count, neighbour_count(length, radius));
+            assert!(visited.iter().all(|value| {
+                value >> length == 0b1010 >> length
+                    && (value ^ 0b1010).count_ones() as usize <= radius
+            }));
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::algorithm::hash_proximity::{hamming_distance_words, parse_hex_hash};
+
+/// A hex hash parsed once into 64-bit words, compared with XOR and popcount.
+///
+/// The last 16 hex characters go in the first word, so bit `i` of the hash is bit
+/// `i % 64` of word `i / 64`, matching the bit order of the aHash algorithm.
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::packed_hash::PackedHash;
+///
+/// let hash1: PackedHash = "ffffffff00000000".parse()?;
+/// let hash2: PackedHash = "ffffffff00000003".parse()?;
+///
+/// assert_eq!(hash1.hamming_distance(&hash2), 2);
+/// assert_eq!(hash2.to_string(), "ffffffff00000003");
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PackedHash {
+    words: Vec<u64>,
+    hex_len: usize,
+}
+
+impl PackedHash {
+    /// Parses a hex hash string
+    pub fn parse(hash: &str) -> Result<Self, String> {
+        let mut words: Vec<u64> = parse_hex_hash(hash)?;
+
+        // Even an empty hash has a word, so that packed hashes are never zero-sized
+        if words.is_empty() {
+            words.push(0);
+        }
+
+        Ok(PackedHash {
+            words,
+            hex_len: hash.len(),
+        })
+    }
+
+    /// The 64-bit words of the hash, least significant first
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Length of the hash in hex characters
+    pub fn hex_len(&self) -> usize {
+        self.hex_len
+    }
+
+    /// Number of bits of the hash
+    pub fn bit_count(&self) -> usize {
+        self.hex_len * 4
+    }
+
+    /// Value of bit `index` of the hash, bit 0 being the least significant bit of the last hex character
+    pub fn bit(&self, index: usize) -> bool {
+        index < self.bit_count() && self.words[index / 64] >> (index % 64) & 1 == 1
+    }
+
+    /// Number of bits that differ between the two hashes
+    ///
+    /// Both hashes are expected to have the same length: extra words are ignored.
+    pub fn hamming_distance(&self, other: &PackedHash) -> u32 {
+        hamming_distance_words(&self.words, &other.words)
+    }
+
+    /// Fraction of bits that are equal in both hashes, 1.0 meaning identical hashes
+    pub fn proximity(&self, other: &PackedHash) -> f64 {
+        let bit_count: usize = self.bit_count().min(other.bit_count());
+
+        // Empty hashes are considered identical
+        if bit_count == 0 {
+            return 1.0;
+        }
+
+        1.0 - self.hamming_distance(other) as f64 / bit_count as f64
+    }
+}
+
+impl FromStr for PackedHash {
+    type Err = String;
+
+    fn from_str(hash: &str) -> Result<Self, Self::Err> {
+        PackedHash::parse(hash)
+    }
+}
+
+impl fmt::Display for PackedHash {
+    /// Formats the hash back into lowercase hex, with its original length
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for position in (0..self.hex_len).rev() {
+            let nibble: u64 = self.words[position / 16] >> (position % 16 * 4) & 0xf;
+            write!(f, "{:x}", nibble)?;
+        }
+        Ok(())
+    }
+}
+
+/// Many packed hashes of the same length, stored contiguously for fast linear scans.
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::packed_hash::{PackedHash, PackedHashes};
+///
+/// let mut hashes = PackedHashes::new();
+/// hashes.push(&"ffffffff00000000".parse()?)?;
+/// hashes.push(&"00000000ffffffff".parse()?)?;
+///
+/// let query: PackedHash = "ffffffff00000001".parse()?;
+/// let distances: Vec<u32> = hashes.distances(&query)?.collect();
+/// assert_eq!(distances, vec![1, 63]);
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PackedHashes {
+    words: Vec<u64>,
+    words_per_hash: usize,
+    hex_len: Option<usize>,
+}
+
+impl PackedHashes {
+    /// Creates an empty store
+    pub fn new() -> Self {
+        PackedHashes::default()
+    }
+
+    /// Number of hashes in the store
+    pub fn len(&self) -> usize {
+        self.words
+            .len()
+            .checked_div(self.words_per_hash)
+            .unwrap_or(0)
+    }
+
+    /// Whether the store has no hashes
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Length, in hex characters, of the hashes in the store (`None` while it is empty)
+    pub fn hex_len(&self) -> Option<usize> {
+        self.hex_len
+    }
+
+    /// Checks that a hash has the same length as the hashes of the store
+    pub fn check_len(&self, hash: &PackedHash) -> Result<(), String> {
+        match self.hex_len {
+            Some(hex_len) if hash.hex_len() != hex_len => Err(format!(
+                "Hash length mismatch: expected {} hex characters, got {} ({})",
+                hex_len,
+                hash.hex_len(),
+                hash
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Appends a hash, which must have the same length as the hashes already in the store
+    pub fn push(&mut self, hash: &PackedHash) -> Result<(), String> {
+        self.check_len(hash)?;
+
+        self.hex_len = Some(hash.hex_len());
+        self.words_per_hash = hash.words().len();
+        self.words.extend_from_slice(hash.words());
+        Ok(())
+    }
+
+    /// The words of the hash at `index`, as returned by [`PackedHash::words`]
+    pub fn get(&self, index: usize) -> Option<&[u64]> {
+        // An empty store has no words per hash, so every index would give an empty slice
+        if index >= self.len() {
+            return None;
+        }
+        let start: usize = index.checked_mul(self.words_per_hash)?;
+        self.words.get(start..start + self.words_per_hash)
+    }
+
+    /// Hamming distances between the query and every hash of the store, in order
+    pub fn distances<'a>(
+        &'a self,
+        query: &'a PackedHash,
+    ) -> Result<impl Iterator<Item = u32> + 'a, String> {
+        self.check_len(query)?;
+
+        Ok(self
+            .words
+            .chunks_exact(self.words_per_hash.max(1))
+            .map(|words| hamming_distance_words(query.words(), words)))
+    }
+}
+
+/// Parses up to 16 hex characters into a 64-bit word, `None` if any character is not a hex digit
+pub(crate) fn parse_hex_word(chunk: &[u8]) -> Option<u64> {
+    chunk.iter().try_fold(0u64, |word, &c| {
+        let nibble: u32 = (c as char).to_digit(16)?;
+        Some(word << 4 | nibble as u64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hamming distance counted one hex character at a time
+    fn nibble_distance(hash1: &str, hash2: &str) -> u32 {
+        hash1
+            .chars()
+            .zip(hash2.chars())
+            .map(|(c1, c2)| (c1.to_digit(16).unwrap() ^ c2.to_digit(16).unwrap()).count_ones())
+            .sum()
+    }
+
+    fn random_hashes(len: usize, count: usize) -> Vec<String> {
+        let mut state: u64 = 0xd1b5_4a32_d192_ed03;
+        (0..count)
+            .map(|_| {
+                (0..len)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        char::from_digit((state % 16) as u32, 16).unwrap()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn xor_and_popcount_count_the_differing_bits() {
+        for len in [0, 1, 15, 16, 17, 33, 64] {
+            let hashes: Vec<String> = random_hashes(len, 8);
+            for hash1 in &hashes {
+                for hash2 in &hashes {
+                    let packed1: PackedHash = hash1.parse().unwrap();
+                    let packed2: PackedHash = hash2.parse().unwrap();
+                    assert_eq!(
+                        packed1.hamming_distance(&packed2),
+                        nibble_distance(hash1, hash2),
+                        "{} {}",
+                        hash1,
+                        hash2
+                    );
+                }
+            }
+        }
+
+        let zeros: PackedHash = "0".repeat(40).parse().unwrap();
+        let ones: PackedHash = "f".repeat(40).parse().unwrap();
+        assert_eq!(zeros.hamming_distance(&ones), 160);
+        assert_eq!(zeros.proximity(&ones), 0.0);
+        assert_eq!(ones.proximity(&ones), 1.0);
+    }
+
+    #[test]
+    fn hex_words_of_any_length_and_case() {
+        assert_eq!(parse_hex_word(b""), Some(0));
+        assert_eq!(parse_hex_word(b"f"), Some(0xf));
+        assert_eq!(parse_hex_word(b"abc"), Some(0xabc));
+        assert_eq!(parse_hex_word(b"ABC"), Some(0xabc));
+        assert_eq!(parse_hex_word(b"aBcDeF0"), Some(0xabcdef0));
+        assert_eq!(parse_hex_word(b"ffffffffffffffff"), Some(u64::MAX));
+        assert_eq!(parse_hex_word(b"0g"), None);
+        assert_eq!(parse_hex_word(b" f"), None);
+    }
+
+    #[test]
+    fn odd_lengths_and_uppercase_hashes() {
+        // The first word holds the last 16 characters, the leftover ones go in the next word
+        let hash: PackedHash = "ABC0123456789abcdef".parse().unwrap();
+        assert_eq!(hash.words(), [0x0123_4567_89ab_cdef, 0xabc]);
+        assert_eq!(hash.hex_len(), 19);
+        assert_eq!(hash.to_string(), "abc0123456789abcdef");
+        assert_eq!(hash, "abc0123456789ABCDEF".parse().unwrap());
+
+        let hash: PackedHash = "1f".parse().unwrap();
+        assert!(hash.bit(0) && hash.bit(3) && hash.bit(4));
+        assert!(!hash.bit(5) && !hash.bit(8));
+
+        // Leading zeros are kept
+        assert_eq!("000f".parse::<PackedHash>().unwrap().to_string(), "000f");
+        assert_eq!(PackedHash::parse("").unwrap().words(), [0]);
+        assert!(PackedHash::parse("12z").is_err());
+    }
+
+    #[test]
+    fn stores_keep_hashes_of_one_length() {
+        let hashes: Vec<String> = random_hashes(20, 5);
+        let mut store: PackedHashes = PackedHashes::new();
+        assert!(store.is_empty());
+        assert_eq!(store.hex_len(), None);
+        assert_eq!(store.get(0), None);
+
+        for hash in &hashes {
+            store.push(&hash.parse().unwrap()).unwrap();
+        }
+        assert_eq!(store.len(), 5);
+        assert_eq!(store.hex_len(), Some(20));
+        assert!(store.push(&"ffff".parse().unwrap()).is_err());
+        assert!(store.distances(&"ffff".parse().unwrap()).is_err());
+
+        let query: PackedHash = hashes[2].parse().unwrap();
+        let distances: Vec<u32> = store.distances(&query).unwrap().collect();
+        for (index, hash) in hashes.iter().enumerate() {
+            assert_eq!(
+                store.get(index),
+                Some(hash.parse::<PackedHash>().unwrap().words())
+            );
+            assert_eq!(distances[index], nibble_distance(hash, &hashes[2]));
+        }
+        assert_eq!(store.get(5), None);
+    }
+}
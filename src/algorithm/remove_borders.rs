@@ -1,53 +1,696 @@
-use image::{DynamicImage, GenericImageView, Rgba};
+use image::{DynamicImage, GenericImageView};
+
+/// A rectangular region of an image, in pixel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CropRect {
+    /// Returns a rectangle covering the whole of an image with the given dimensions.
+    pub fn full(width: u32, height: u32) -> Self {
+        CropRect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    /// Maps this rectangle from an image of size `from` onto an image of size `to`.
+    ///
+    /// Useful to apply a crop detected on a downscaled copy to the original
+    /// high-resolution image. The result is clamped to the bounds of `to`.
+    pub fn scaled(&self, from: (u32, u32), to: (u32, u32)) -> CropRect {
+        let scale_x: f64 = to.0 as f64 / from.0.max(1) as f64;
+        let scale_y: f64 = to.1 as f64 / from.1.max(1) as f64;
+
+        let x: u32 = ((self.x as f64 * scale_x).floor() as u32).min(to.0);
+        let y: u32 = ((self.y as f64 * scale_y).floor() as u32).min(to.1);
+        let right: u32 = (((self.x + self.width) as f64 * scale_x).ceil() as u32).min(to.0);
+        let bottom: u32 = (((self.y + self.height) as f64 * scale_y).ceil() as u32).min(to.1);
+
+        CropRect {
+            x,
+            y,
+            width: right.saturating_sub(x),
+            height: bottom.saturating_sub(y),
+        }
+    }
+
+    /// Crops the given image to this rectangle.
+    pub fn apply(&self, img: &DynamicImage) -> DynamicImage {
+        img.crop_imm(self.x, self.y, self.width, self.height)
+    }
+}
+
+/// Options controlling how white borders are detected and removed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BorderRemovalOptions {
+    /// RGB values above this threshold (out of 255) are considered "white" (default: 240)
+    pub threshold: u8,
+    /// Number of pixels kept around the detected content, if possible (default: 1)
+    pub margin: u32,
+    /// Minimum width and height of the detected content for a crop to happen.
+    /// Smaller content is treated as noise and the image is left untouched (default: 2)
+    pub min_content_size: u32,
+    /// Fraction of non-border pixels (0.0 to 1.0) a row or column may contain while
+    /// still being trimmed, so that dust specks or compression artifacts in the margin
+    /// do not stop the trimming (default: 0.0)
+    pub noise_fraction: f64,
+    /// Maximum number of trimming passes. The first pass removes white borders, the
+    /// following ones remove nested uniform frames of any color, such as a frame
+    /// around a mat (default: 1)
+    ///
+    /// Nested frames are only trimmed if callers opt in with more passes, e.g. through
+    /// `Preprocessing::RemoveWhiteBordersWith`: trimming them by default would change the
+    /// hashes of framed images already in a dictionary. Use the same options to enroll
+    /// and to look images up.
+    pub max_passes: u32,
+    /// Maximum per-channel difference from the frame color for a pixel to be
+    /// considered part of a nested frame (default: 24)
+    pub frame_tolerance: u8,
+    /// Scan opposite edges of the image in parallel (default: false)
+    pub parallel: bool,
+}
+
+impl Default for BorderRemovalOptions {
+    fn default() -> Self {
+        BorderRemovalOptions {
+            threshold: 240,
+            margin: 1,
+            min_content_size: 2,
+            noise_fraction: 0.0,
+            max_passes: 1,
+            frame_tolerance: 24,
+            parallel: false,
+        }
+    }
+}
+
+/// The outcome of a border removal.
+#[derive(Debug, Clone)]
+pub struct BorderRemovalResult {
+    /// The cropped image (or a copy of the original, if nothing was cropped)
+    pub image: DynamicImage,
+    /// The region of the original image that was kept
+    pub crop: CropRect,
+    /// Whether any border was actually removed
+    pub cropped: bool,
+}
 
 /// Removes white borders from an image, if they exist.
 ///
 /// This function crops the image to remove white (or near-white) borders
 /// around the content. If the image has no borders, the original image is returned.
 pub fn remove_white_borders(img: &DynamicImage) -> DynamicImage {
+    remove_white_borders_with_options(img, &BorderRemovalOptions::default()).image
+}
+
+/// Removes white borders from an image using the given options, and reports the crop.
+///
+/// The returned `crop` can be reused to crop another version of the same image
+/// identically (see [`CropRect::scaled`]).
+pub fn remove_white_borders_with_options(
+    img: &DynamicImage,
+    options: &BorderRemovalOptions,
+) -> BorderRemovalResult {
     let (width, height) = img.dimensions();
 
-    // Define a threshold for "white" - allowing for some noise/variation
-    // RGB values above this threshold (out of 255) are considered "white"
-    let threshold: u8 = 240;
-
-    // Find the bounding box of non-white content
-    let mut min_x: u32 = width;
-    let mut min_y: u32 = height;
-    let mut max_x: u32 = 0;
-    let mut max_y: u32 = 0;
-
-    for y in 0..height {
-        for x in 0..width {
-            let pixel = img.get_pixel(x, y);
-            let Rgba([r, g, b, _]) = pixel;
-
-            // If this pixel is not white (any RGB channel is below threshold)
-            if r < threshold || g < threshold || b < threshold {
-                min_x = min_x.min(x);
-                min_y = min_y.min(y);
-                max_x = max_x.max(x);
-                max_y = max_y.max(y);
-            }
+    match detect_content_rect(img, options) {
+        Some(crop) => BorderRemovalResult {
+            image: crop.apply(img),
+            crop,
+            cropped: true,
+        },
+        None => BorderRemovalResult {
+            image: img.clone(),
+            crop: CropRect::full(width, height),
+            cropped: false,
+        },
+    }
+}
+
+/// Detects the region of the image to keep once white borders are removed.
+///
+/// Each edge is scanned inward on the raw pixel buffer, stopping at the first
+/// row or column containing content, so the cost depends on the border size
+/// rather than on the size of the image. When `max_passes` is greater than 1,
+/// uniform frames found inside the white border are trimmed as well.
+///
+/// Returns `None` if there is nothing to crop: no content was found, the content
+/// is smaller than `min_content_size`, or the content and its margin already fill the image.
+pub fn detect_content_rect(img: &DynamicImage, options: &BorderRemovalOptions) -> Option<CropRect> {
+    // Typed fast paths work directly on the 8-bit buffers, anything else is converted first
+    let converted;
+    let view: PixelView = match img {
+        DynamicImage::ImageLuma8(buffer) => {
+            PixelView::new(buffer.as_raw(), buffer.dimensions(), 1, 1)
         }
+        DynamicImage::ImageLumaA8(buffer) => {
+            PixelView::new(buffer.as_raw(), buffer.dimensions(), 2, 1)
+        }
+        DynamicImage::ImageRgb8(buffer) => {
+            PixelView::new(buffer.as_raw(), buffer.dimensions(), 3, 3)
+        }
+        DynamicImage::ImageRgba8(buffer) => {
+            PixelView::new(buffer.as_raw(), buffer.dimensions(), 4, 3)
+        }
+        _ => {
+            converted = img.to_rgba8();
+            PixelView::new(converted.as_raw(), converted.dimensions(), 4, 3)
+        }
+    };
+
+    let (width, height) = (view.width, view.height);
+    if width == 0 || height == 0 {
+        return None;
     }
 
-    // If no non-white pixels were found, or the bounding box is the whole image,
-    // return the original image
-    if min_x >= max_x
-        || min_y >= max_y
-        || (min_x == 0 && min_y == 0 && max_x == width - 1 && max_y == height - 1)
+    // The first pass removes the white border around the content
+    let full: Bounds = Bounds {
+        min_x: 0,
+        min_y: 0,
+        max_x: width - 1,
+        max_y: height - 1,
+    };
+    let mut bounds: Bounds = trim(&view, full, Background::White(options.threshold), options)?;
+
+    // The following passes remove nested frames, as long as the four corners of the
+    // remaining region share the same color and something is trimmed
+    for _ in 1..options.max_passes {
+        let Some(frame_color) = view.frame_color(bounds, options.frame_tolerance) else {
+            break;
+        };
+        match trim(
+            &view,
+            bounds,
+            Background::Color(frame_color, options.frame_tolerance),
+            options,
+        ) {
+            Some(trimmed) if trimmed != bounds => bounds = trimmed,
+            _ => break,
+        }
+    }
+
+    let Bounds {
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+    } = bounds;
+
+    // If the content is too small, or the bounding box is the whole image, there is nothing to crop
+    if max_x - min_x + 1 < options.min_content_size
+        || max_y - min_y + 1 < options.min_content_size
+        || bounds == full
     {
-        return img.clone();
+        return None;
+    }
+
+    // Add a small border around the content if possible
+    let margin: u32 = options.margin;
+    let min_x: u32 = min_x.saturating_sub(margin);
+    let min_y: u32 = min_y.saturating_sub(margin);
+    let max_x: u32 = max_x.saturating_add(margin).min(width - 1);
+    let max_y: u32 = max_y.saturating_add(margin).min(height - 1);
+
+    // The margin may cover the whole border, leaving nothing to crop either
+    let crop: CropRect = CropRect {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x + 1,
+        height: max_y - min_y + 1,
+    };
+    (crop != CropRect::full(width, height)).then_some(crop)
+}
+
+/// Inclusive pixel bounds of a region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bounds {
+    min_x: u32,
+    min_y: u32,
+    max_x: u32,
+    max_y: u32,
+}
+
+/// What a border pixel looks like.
+#[derive(Debug, Clone, Copy)]
+enum Background {
+    /// Every color channel is at or above the threshold
+    White(u8),
+    /// Every color channel is within the tolerance of the given color
+    Color([u8; 3], u8),
+}
+
+impl Background {
+    fn matches(&self, pixel: &[u8]) -> bool {
+        match *self {
+            Background::White(threshold) => pixel.iter().all(|&c| c >= threshold),
+            Background::Color(color, tolerance) => pixel
+                .iter()
+                .zip(color.iter())
+                .all(|(&c, &expected)| c.abs_diff(expected) <= tolerance),
+        }
+    }
+}
+
+/// Trims the rows and columns of `bounds` that are made of background, scanning
+/// inward from each edge. Returns `None` if the whole region is background.
+fn trim(
+    view: &PixelView,
+    bounds: Bounds,
+    background: Background,
+    options: &BorderRemovalOptions,
+) -> Option<Bounds> {
+    let noise: f64 = options.noise_fraction;
+    let is_content_row =
+        |y: u32| view.row_has_content(y, bounds.min_x, bounds.max_x + 1, background, noise);
+
+    let (top, bottom) = run_pair(
+        options.parallel,
+        || (bounds.min_y..=bounds.max_y).find(|&y| is_content_row(y)),
+        || {
+            (bounds.min_y..=bounds.max_y)
+                .rev()
+                .find(|&y| is_content_row(y))
+        },
+    );
+    let (Some(min_y), Some(max_y)) = (top, bottom) else {
+        return None;
+    };
+
+    // Rows outside of [min_y, max_y] are border, so columns only need to be checked within it
+    let is_content_column =
+        |x: u32| view.column_has_content(x, min_y, max_y + 1, background, noise);
+
+    let (left, right) = run_pair(
+        options.parallel,
+        || (bounds.min_x..=bounds.max_x).find(|&x| is_content_column(x)),
+        || {
+            (bounds.min_x..=bounds.max_x)
+                .rev()
+                .find(|&x| is_content_column(x))
+        },
+    );
+    let (Some(min_x), Some(max_x)) = (left, right) else {
+        return None;
+    };
+
+    Some(Bounds {
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+    })
+}
+
+/// Runs two independent scans, in parallel if requested.
+fn run_pair<A, B>(parallel: bool, a: A, b: B) -> (Option<u32>, Option<u32>)
+where
+    A: FnOnce() -> Option<u32> + Send,
+    B: FnOnce() -> Option<u32> + Send,
+{
+    if parallel {
+        rayon::join(a, b)
+    } else {
+        (a(), b())
+    }
+}
+
+/// A read-only view over an interleaved 8-bit pixel buffer.
+struct PixelView<'a> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    /// Number of channels per pixel in the buffer
+    channels: usize,
+    /// Number of leading channels holding color (the alpha channel is ignored)
+    color_channels: usize,
+}
+
+impl<'a> PixelView<'a> {
+    fn new(
+        data: &'a [u8],
+        (width, height): (u32, u32),
+        channels: usize,
+        color_channels: usize,
+    ) -> Self {
+        PixelView {
+            data,
+            width,
+            height,
+            channels,
+            color_channels,
+        }
+    }
+
+    /// Returns the color channels of the pixel at `(x, y)`
+    fn color(&self, x: u32, y: u32) -> &[u8] {
+        let offset: usize = (y as usize * self.width as usize + x as usize) * self.channels;
+        &self.data[offset..offset + self.color_channels]
+    }
+
+    /// Returns the color shared by the four corners of `bounds`, if any
+    fn frame_color(&self, bounds: Bounds, tolerance: u8) -> Option<[u8; 3]> {
+        let corner: &[u8] = self.color(bounds.min_x, bounds.min_y);
+        let mut color: [u8; 3] = [0; 3];
+        color[..corner.len()].copy_from_slice(corner);
+
+        let others: [(u32, u32); 3] = [
+            (bounds.max_x, bounds.min_y),
+            (bounds.min_x, bounds.max_y),
+            (bounds.max_x, bounds.max_y),
+        ];
+        others
+            .iter()
+            .all(|&(x, y)| Background::Color(color, tolerance).matches(self.color(x, y)))
+            .then_some(color)
+    }
+
+    /// Checks whether more than `noise_fraction` of the given pixels are not background.
+    /// Stops as soon as the answer is known.
+    fn has_content<'p>(
+        &self,
+        mut pixels: impl ExactSizeIterator<Item = &'p [u8]>,
+        background: Background,
+        noise_fraction: f64,
+    ) -> bool {
+        let allowed: usize = (pixels.len() as f64 * noise_fraction).floor() as usize;
+        let mut found: usize = 0;
+
+        pixels.any(|pixel| {
+            if !background.matches(&pixel[..self.color_channels]) {
+                found += 1;
+            }
+            found > allowed
+        })
+    }
+
+    /// Checks whether row `y` has content between columns `x_start` and `x_end` (exclusive)
+    fn row_has_content(
+        &self,
+        y: u32,
+        x_start: u32,
+        x_end: u32,
+        background: Background,
+        noise_fraction: f64,
+    ) -> bool {
+        let row_start: usize =
+            (y as usize * self.width as usize + x_start as usize) * self.channels;
+        let row_end: usize = (y as usize * self.width as usize + x_end as usize) * self.channels;
+
+        self.has_content(
+            self.data[row_start..row_end].chunks_exact(self.channels),
+            background,
+            noise_fraction,
+        )
+    }
+
+    /// Checks whether column `x` has content between rows `y_start` and `y_end` (exclusive)
+    fn column_has_content(
+        &self,
+        x: u32,
+        y_start: u32,
+        y_end: u32,
+        background: Background,
+        noise_fraction: f64,
+    ) -> bool {
+        let stride: usize = self.width as usize * self.channels;
+        let column_start: usize = y_start as usize * stride + x as usize * self.channels;
+        let column_end: usize = (y_end as usize - 1) * stride + x as usize * self.channels;
+
+        self.has_content(
+            self.data[column_start..column_end + self.channels]
+                .chunks(self.channels)
+                .step_by(stride / self.channels),
+            background,
+            noise_fraction,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma, Rgb, RgbImage, Rgba, RgbaImage};
+
+    /// A picture framed by a uniform gray frame, on a white border:
+    /// white up to 4 pixels from the edges, gray up to 8, and a dark center
+    fn framed_picture() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(24, 20, |x, y| {
+            let edge: u32 = x.min(y).min(23 - x).min(19 - y);
+            match edge {
+                0..4 => Rgb([255, 255, 255]),
+                4..8 => Rgb([128, 128, 128]),
+                _ => Rgb([(x * 10) as u8, (y * 10) as u8, 0]),
+            }
+        }))
+    }
+
+    #[test]
+    fn nested_frames_are_only_trimmed_on_opt_in() {
+        let options: BorderRemovalOptions = BorderRemovalOptions {
+            margin: 0,
+            ..Default::default()
+        };
+
+        // By default, only the white border is removed
+        let crop: Option<CropRect> = detect_content_rect(&framed_picture(), &options);
+        assert_eq!(
+            crop,
+            Some(CropRect {
+                x: 4,
+                y: 4,
+                width: 16,
+                height: 12
+            })
+        );
+
+        // With more passes, the gray frame is removed as well
+        let options: BorderRemovalOptions = BorderRemovalOptions {
+            max_passes: 3,
+            ..options
+        };
+        let crop: Option<CropRect> = detect_content_rect(&framed_picture(), &options);
+        assert_eq!(
+            crop,
+            Some(CropRect {
+                x: 8,
+                y: 8,
+                width: 8,
+                height: 4
+            })
+        );
+    }
+
+    /// A white 7x5 grayscale image with dark content from (2, 1) to (4, 3)
+    fn odd_sized_picture() -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(7, 5, |x, y| {
+            if (2..=4).contains(&x) && (1..=3).contains(&y) {
+                Luma([30])
+            } else {
+                Luma([255])
+            }
+        }))
+    }
+
+    #[test]
+    fn images_without_borders_are_not_cropped() {
+        let options: BorderRemovalOptions = BorderRemovalOptions::default();
+
+        let empty: DynamicImage = DynamicImage::new_rgb8(0, 0);
+        assert_eq!(detect_content_rect(&empty, &options), None);
+
+        let white: DynamicImage =
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(9, 5, Rgb([250, 250, 250])));
+        assert_eq!(detect_content_rect(&white, &options), None);
+
+        let uniform: DynamicImage =
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(9, 5, Rgb([40, 90, 200])));
+        assert_eq!(detect_content_rect(&uniform, &options), None);
+
+        let result: BorderRemovalResult = remove_white_borders_with_options(&uniform, &options);
+        assert!(!result.cropped);
+        assert_eq!(result.crop, CropRect::full(9, 5));
+        assert_eq!(result.image, uniform);
+    }
+
+    #[test]
+    fn odd_sized_borders_are_removed() {
+        let options: BorderRemovalOptions = BorderRemovalOptions {
+            margin: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            detect_content_rect(&odd_sized_picture(), &options),
+            Some(CropRect {
+                x: 2,
+                y: 1,
+                width: 3,
+                height: 3
+            })
+        );
+
+        // The margin is clamped to the image
+        let options: BorderRemovalOptions = BorderRemovalOptions {
+            margin: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            detect_content_rect(&odd_sized_picture(), &options),
+            Some(CropRect {
+                x: 1,
+                y: 0,
+                width: 5,
+                height: 5
+            })
+        );
+
+        // A margin covering the whole border leaves nothing to crop
+        let options: BorderRemovalOptions = BorderRemovalOptions {
+            margin: 2,
+            ..Default::default()
+        };
+        assert_eq!(detect_content_rect(&odd_sized_picture(), &options), None);
+        let result: BorderRemovalResult =
+            remove_white_borders_with_options(&odd_sized_picture(), &options);
+        assert!(!result.cropped);
+        assert_eq!(result.crop, CropRect::full(7, 5));
+    }
+
+    #[test]
+    fn alpha_is_ignored() {
+        let image: DynamicImage = DynamicImage::ImageRgba8(RgbaImage::from_fn(7, 5, |x, y| {
+            if (2..=4).contains(&x) && (1..=3).contains(&y) {
+                Rgba([30, 30, 30, 255])
+            } else {
+                Rgba([255, 255, 255, 0])
+            }
+        }));
+        let options: BorderRemovalOptions = BorderRemovalOptions {
+            margin: 0,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            detect_content_rect(&image, &options),
+            detect_content_rect(&odd_sized_picture(), &options)
+        );
+    }
+
+    #[test]
+    fn content_smaller_than_the_minimum_is_noise() {
+        let speck: DynamicImage = DynamicImage::ImageLuma8(GrayImage::from_fn(7, 5, |x, y| {
+            Luma(if (x, y) == (3, 2) { [0] } else { [255] })
+        }));
+        assert_eq!(
+            detect_content_rect(&speck, &BorderRemovalOptions::default()),
+            None
+        );
+
+        let options: BorderRemovalOptions = BorderRemovalOptions {
+            margin: 0,
+            min_content_size: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            detect_content_rect(&speck, &options),
+            Some(CropRect {
+                x: 3,
+                y: 2,
+                width: 1,
+                height: 1
+            })
+        );
+    }
+
+    #[test]
+    fn noise_fraction_ignores_specks_in_the_border() {
+        let mut image: GrayImage = odd_sized_picture().to_luma8();
+        image.put_pixel(0, 0, Luma([0]));
+        let image: DynamicImage = DynamicImage::ImageLuma8(image);
+
+        let options: BorderRemovalOptions = BorderRemovalOptions {
+            margin: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            detect_content_rect(&image, &options),
+            Some(CropRect {
+                x: 0,
+                y: 0,
+                width: 5,
+                height: 4
+            })
+        );
+
+        // One pixel out of the 7 of a row, or the 5 of a column, is tolerated
+        let options: BorderRemovalOptions = BorderRemovalOptions {
+            noise_fraction: 0.2,
+            ..options
+        };
+        assert_eq!(
+            detect_content_rect(&image, &options),
+            detect_content_rect(&odd_sized_picture(), &options)
+        );
     }
 
-    // Add a small border (1 pixel) around the content if possible
-    let border = 1;
-    min_x = min_x.saturating_sub(border);
-    min_y = min_y.saturating_sub(border);
-    max_x = (max_x + border).min(width - 1);
-    max_y = (max_y + border).min(height - 1);
+    #[test]
+    fn parallel_scans_give_the_same_crop() {
+        let options: BorderRemovalOptions = BorderRemovalOptions {
+            max_passes: 3,
+            parallel: true,
+            ..Default::default()
+        };
+        let sequential: BorderRemovalOptions = BorderRemovalOptions {
+            parallel: false,
+            ..options
+        };
 
-    // Crop the image to the bounding box
-    img.crop_imm(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+        assert_eq!(
+            detect_content_rect(&framed_picture(), &options),
+            detect_content_rect(&framed_picture(), &sequential)
+        );
+    }
+
+    #[test]
+    fn crops_are_scaled_and_clamped() {
+        let crop: CropRect = CropRect {
+            x: 1,
+            y: 1,
+            width: 3,
+            height: 2,
+        };
+        assert_eq!(
+            crop.scaled((5, 5), (10, 15)),
+            CropRect {
+                x: 2,
+                y: 3,
+                width: 6,
+                height: 6
+            }
+        );
+
+        // Odd ratios round outward, so the content is never cut
+        assert_eq!(
+            crop.scaled((5, 5), (7, 7)),
+            CropRect {
+                x: 1,
+                y: 1,
+                width: 5,
+                height: 4
+            }
+        );
+
+        // Empty sizes do not divide by zero
+        assert_eq!(crop.scaled((0, 0), (0, 0)), CropRect::full(0, 0));
+        assert_eq!(
+            CropRect::full(4, 4).scaled((4, 4), (2, 2)),
+            CropRect::full(2, 2)
+        );
+    }
 }
@@ -0,0 +1,412 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use crate::algorithm::distance_metric::DistanceMetric;
+use crate::algorithm::hash_proximity::{HashMatch, MatchOutcome, classify_matches};
+use crate::data_handle::dictionary_entry::HashEntry;
+
+/// Find the `k` hashes most similar to the given hash in a stream of entries
+/// Returns the matches above the threshold, sorted from the most to the least similar
+///
+/// Gives the same results as [`find_top_k_similar_hashes`](crate::algorithm::hash_proximity::find_top_k_similar_hashes),
+/// but reads the entries one at a time and only keeps the `k` best candidates, so that
+/// dictionaries larger than memory can be searched with bounded memory.
+///
+/// # Arguments
+///
+/// * `hash` - The hash to find similar hashes for
+/// * `entries` - The entries to search through, e.g. from
+///   [`stream_entries_from_csv`](crate::data_handle::load_csv::stream_entries_from_csv).
+///   Only the entries whose hash has the same length as `hash` are compared, and the
+///   search stops at the first error
+/// * `k` - The maximum number of matches to return
+/// * `proximity_threshold` - The minimum proximity to consider a hash similar (default: 0.95).
+///   Entries with their own threshold use it instead
+/// * `metric` - The metric used to compare hashes, any [`DistanceMetric`] implementation
+///
+/// # Returns
+///
+/// * `Ok(Vec<HashMatch>)` - Up to `k` matches, with their distance and proximity (empty if none is similar)
+/// * `Err(String)` - Error message if an entry cannot be read or the calculation fails
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::distance_metric::Hamming;
+/// use img_hash_linker::algorithm::streaming_search::stream_top_k_similar_hashes;
+/// use img_hash_linker::data_handle::load_csv::stream_entries_from_reader;
+///
+/// let csv = "hash,link
+/// ffffffff00000000,https://example.com
+/// ffffffff00000003,https://another.com
+/// 00000000ffffffff,https://third.com
+/// ";
+///
+/// let entries = stream_entries_from_reader(csv.as_bytes())?;
+/// let matches = stream_top_k_similar_hashes("ffffffff00000001", entries, 2, 0.9, &Hamming)?;
+/// assert_eq!(matches.len(), 2);
+/// assert_eq!(matches[0].link, "https://example.com");
+/// # Ok::<(), String>(())
+/// ```
+pub fn stream_top_k_similar_hashes<I, E, M>(
+    hash: &str,
+    entries: I,
+    k: usize,
+    proximity_threshold: impl Into<Option<f64>>,
+    metric: &M,
+) -> Result<Vec<HashMatch>, String>
+where
+    I: IntoIterator<Item = Result<E, String>>,
+    E: HashEntry,
+    M: DistanceMetric + ?Sized,
+{
+    // The worst of the best candidates is at the top of the heap
+    let mut best: BinaryHeap<Reverse<Candidate>> = BinaryHeap::new();
+
+    scan_entries(hash, entries, proximity_threshold, metric, |candidate| {
+        if best.len() < k {
+            best.push(Reverse(candidate));
+        } else if best.peek().is_some_and(|worst| candidate > worst.0) {
+            best.pop();
+            best.push(Reverse(candidate));
+        }
+    })?;
+
+    // Sorting the reversed candidates ascending sorts them from the most similar
+    Ok(best
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(candidate)| candidate.found)
+        .collect())
+}
+
+/// Look a hash up in a stream of entries, telling confident matches from ambiguous ones
+///
+/// Gives the same outcome as [`find_match`](crate::algorithm::hash_proximity::find_match),
+/// keeping only the best match of the two most similar links in memory.
+/// See [`stream_top_k_similar_hashes`] for how the entries are read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use img_hash_linker::algorithm::distance_metric::Hamming;
+/// use img_hash_linker::algorithm::hash_proximity::MatchOutcome;
+/// use img_hash_linker::algorithm::streaming_search::stream_find_match;
+/// use img_hash_linker::data_handle::load_csv::stream_entries_from_csv;
+///
+/// let entries = stream_entries_from_csv("huge_export.csv".to_string())?;
+/// if let MatchOutcome::Confident(found) =
+///     stream_find_match("ffffffff00000001", entries, None, None, &Hamming)?
+/// {
+///     println!("{}", found.link);
+/// }
+/// # Ok::<(), String>(())
+/// ```
+pub fn stream_find_match<I, E, M>(
+    hash: &str,
+    entries: I,
+    proximity_threshold: impl Into<Option<f64>>,
+    ambiguity_margin: impl Into<Option<f64>>,
+    metric: &M,
+) -> Result<MatchOutcome, String>
+where
+    I: IntoIterator<Item = Result<E, String>>,
+    E: HashEntry,
+    M: DistanceMetric + ?Sized,
+{
+    // The best candidate of each of the two most similar links, from the most similar
+    let mut best: Vec<Candidate> = Vec::with_capacity(3);
+
+    scan_entries(hash, entries, proximity_threshold, metric, |candidate| {
+        match best
+            .iter_mut()
+            .find(|kept| kept.found.link == candidate.found.link)
+        {
+            Some(kept) if candidate > *kept => *kept = candidate,
+            Some(_) => return,
+            None => best.push(candidate),
+        }
+        best.sort_by(|a, b| b.cmp(a));
+        best.truncate(2);
+    })?;
+
+    Ok(classify_matches(
+        hash,
+        best.into_iter().map(|candidate| candidate.found).collect(),
+        ambiguity_margin.into().unwrap_or(0.01),
+    ))
+}
+
+/// A match, along with the position of its entry to keep the original order for ties
+struct Candidate {
+    found: HashMatch,
+    position: usize,
+}
+
+impl Ord for Candidate {
+    /// The more similar candidate is the greater, then the earlier one
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.found
+            .proximity
+            .total_cmp(&other.found.proximity)
+            .then_with(|| other.position.cmp(&self.position))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+/// Compares the hash with every entry of the same length, passing those above their
+/// threshold to `keep`
+fn scan_entries<I, E, M>(
+    hash: &str,
+    entries: I,
+    proximity_threshold: impl Into<Option<f64>>,
+    metric: &M,
+    mut keep: impl FnMut(Candidate),
+) -> Result<(), String>
+where
+    I: IntoIterator<Item = Result<E, String>>,
+    E: HashEntry,
+    M: DistanceMetric + ?Sized,
+{
+    let minimum_proximity: f64 = proximity_threshold.into().unwrap_or(0.95);
+
+    let mut any_entry: bool = false;
+    let mut compared: bool = false;
+
+    for (position, entry) in entries.into_iter().enumerate() {
+        let entry: E = entry?;
+        any_entry = true;
+        if entry.hash().len() != hash.len() {
+            continue;
+        }
+        compared = true;
+
+        let distance: f64 = metric
+            .distance(hash, entry.hash())
+            .map_err(|e| format!("Failed to calculate distance: {}", e))?;
+        let proximity: f64 = metric.distance_to_proximity(distance, hash.len());
+
+        if proximity >= entry.threshold().unwrap_or(minimum_proximity) {
+            keep(Candidate {
+                found: HashMatch {
+                    hash: entry.hash().to_string(),
+                    link: entry.link().to_string(),
+                    distance,
+                    proximity,
+                    false_match_probability: None,
+                    thumbnail_similarity: None,
+                },
+                position,
+            });
+        }
+    }
+
+    // Same check as the in-memory searches, made once the whole stream was read
+    if any_entry && !compared {
+        return Err(format!(
+            "Hash length mismatch: no hash of {} hex characters in the dictionary ({})",
+            hash.len(),
+            hash
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::distance_metric::{Hamming, LegacyByteDifference, WeightedBits};
+    use crate::algorithm::hash_proximity::{find_match, find_top_k_similar_hashes};
+
+    fn stream(
+        links: &[(String, String)],
+    ) -> impl Iterator<Item = Result<(String, String), String>> {
+        links.iter().cloned().map(Ok)
+    }
+
+    /// Hashes around a few centres, with repeated hashes and links, and a second hash size
+    fn links() -> Vec<(String, String)> {
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let centres: [u64; 3] = [0xffff_0000_ffff_0000, 0x0123_4567_89ab_cdef, 0];
+        let mut links: Vec<(String, String)> = Vec::new();
+        for index in 0..60 {
+            let centre: u64 = centres[index % centres.len()];
+            let flips: u64 = (0..next() % 4).fold(0, |flips, _| flips | 1 << (next() % 64));
+            // Few links, so that several rows point to the same one
+            links.push((
+                format!("{:016x}", centre ^ flips),
+                format!("https://{}.com", next() % 7),
+            ));
+        }
+
+        // Same hashes as earlier rows, tying with them, under other links
+        links.push((links[0].0.clone(), "https://tie.com".to_string()));
+        links.push((links[1].0.clone(), links[0].1.clone()));
+        // Hashes of another size, never compared with the others
+        links.push(("fff".to_string(), "https://small.com".to_string()));
+        links.push(("ff0".to_string(), "https://0.com".to_string()));
+        links
+    }
+
+    fn queries() -> Vec<String> {
+        let mut queries: Vec<String> = links()
+            .into_iter()
+            .step_by(7)
+            .map(|(hash, _)| hash)
+            .collect();
+        queries.extend(
+            [
+                "ffff0000ffff0001",
+                "0123456789abcdee",
+                "8000000000000000",
+                "ff1",
+            ]
+            .map(String::from),
+        );
+        queries
+    }
+
+    fn assert_same_top_k<M: DistanceMetric>(metric: &M, threshold: f64) {
+        let links: Vec<(String, String)> = links();
+        for query in queries() {
+            for k in [0, 1, 2, 5, links.len() + 10] {
+                assert_eq!(
+                    stream_top_k_similar_hashes(&query, stream(&links), k, threshold, metric),
+                    find_top_k_similar_hashes(&query, &links, k, threshold, metric),
+                    "{} (k = {}, threshold = {})",
+                    query,
+                    k,
+                    threshold
+                );
+            }
+        }
+    }
+
+    fn assert_same_outcome<M: DistanceMetric>(metric: &M, threshold: f64) {
+        let links: Vec<(String, String)> = links();
+        for query in queries() {
+            for margin in [0.0, 0.01, 0.2] {
+                assert_eq!(
+                    stream_find_match(&query, stream(&links), threshold, margin, metric),
+                    find_match(&query, &links, threshold, margin, metric),
+                    "{} (margin = {}, threshold = {})",
+                    query,
+                    margin,
+                    threshold
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn top_k_matches_the_in_memory_search() {
+        for threshold in [0.0, 0.9, 0.99] {
+            assert_same_top_k(&Hamming, threshold);
+            assert_same_top_k(&LegacyByteDifference, threshold);
+            assert_same_top_k(
+                &WeightedBits::new((0..64).map(|bit| 1.0 + bit as f64 / 64.0).collect()),
+                threshold,
+            );
+        }
+    }
+
+    #[test]
+    fn outcomes_match_the_in_memory_search() {
+        for threshold in [0.0, 0.9, 0.99] {
+            assert_same_outcome(&Hamming, threshold);
+            assert_same_outcome(&LegacyByteDifference, threshold);
+        }
+    }
+
+    #[test]
+    fn ties_keep_the_order_of_the_entries() {
+        let links: Vec<(String, String)> = ["ffff", "fffe", "fffd", "fffe", "ffff"]
+            .iter()
+            .enumerate()
+            .map(|(index, hash)| (hash.to_string(), format!("https://{}.com", index)))
+            .collect();
+
+        let found: Vec<HashMatch> =
+            stream_top_k_similar_hashes("fffc", stream(&links), 4, 0.0, &Hamming).unwrap();
+        let found: Vec<&str> = found.iter().map(|found| found.link.as_str()).collect();
+        assert_eq!(
+            found,
+            [
+                "https://1.com",
+                "https://2.com",
+                "https://3.com",
+                "https://0.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn runner_up_is_another_link() {
+        let links: Vec<(String, String)> = [
+            ("ffff", "https://example.com"),
+            ("fffe", "https://example.com"),
+            ("fffd", "https://example.com"),
+            ("ff00", "https://another.com"),
+        ]
+        .map(|(hash, link)| (hash.to_string(), link.to_string()))
+        .to_vec();
+
+        let outcome: MatchOutcome =
+            stream_find_match("ffff", stream(&links), 0.0, 1.0, &Hamming).unwrap();
+        let MatchOutcome::Ambiguous { best, runner_up } = outcome else {
+            panic!("Expected an ambiguous match, got {:?}", outcome);
+        };
+        assert_eq!(best.hash, "ffff");
+        assert_eq!(runner_up.link, "https://another.com");
+
+        // Without another link, the best match is confident however close the others are
+        let outcome: MatchOutcome =
+            stream_find_match("fffe", stream(&links[..3]), 0.0, 1.0, &Hamming).unwrap();
+        assert!(matches!(outcome, MatchOutcome::Exact(_)));
+    }
+
+    #[test]
+    fn hash_sizes_and_errors_match_the_in_memory_search() {
+        let links: Vec<(String, String)> = links();
+        let missing_size: &str = "ffff0000";
+        assert!(
+            stream_top_k_similar_hashes(missing_size, stream(&links), 1, None, &Hamming).is_err()
+        );
+        assert!(find_top_k_similar_hashes(missing_size, &links, 1, None, &Hamming).is_err());
+
+        // An empty dictionary has no match rather than a size mismatch
+        let empty: Vec<(String, String)> = Vec::new();
+        assert_eq!(
+            stream_find_match(missing_size, stream(&empty), None, None, &Hamming),
+            Ok(MatchOutcome::NoMatch)
+        );
+
+        // The search stops at the first entry that cannot be read
+        let entries = stream(&links).take(2).chain([Err("Bad row".to_string())]);
+        assert_eq!(
+            stream_top_k_similar_hashes(&links[0].0, entries, 1, None, &Hamming),
+            Err("Bad row".to_string())
+        );
+    }
+}
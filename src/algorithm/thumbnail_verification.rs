@@ -0,0 +1,561 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage};
+
+use crate::algorithm::hash_proximity::{HashMatch, MatchOutcome, classify_matches};
+use crate::data_handle::dictionary_entry::HashEntry;
+
+/// How a query image is compared with a reference thumbnail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThumbnailSimilarity {
+    /// Structural similarity (SSIM), averaged over 8×8 windows: compares the local
+    /// structure, brightness and contrast of the images
+    #[default]
+    Ssim,
+    /// Normalized cross-correlation of the gray levels: robust to brightness and
+    /// contrast changes, but only sees the image as a whole
+    NormalizedCrossCorrelation,
+}
+
+/// Settings of the pixel-level verification of hash matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationOptions {
+    pub similarity: ThumbnailSimilarity,
+    /// Matches whose thumbnail is less similar to the query are rejected, from -1.0 to 1.0
+    /// (default: 0.5)
+    pub min_similarity: f64,
+    /// Side, in pixels, of the gray images compared (default: 32)
+    pub size: u32,
+    /// Directory the relative thumbnail paths start from: the directory of the dictionary,
+    /// see [`for_dictionary`](VerificationOptions::for_dictionary) (default: none, the
+    /// paths being used as written)
+    pub thumbnail_dir: Option<PathBuf>,
+}
+
+impl VerificationOptions {
+    /// The default options for the thumbnails of a dictionary file, whose paths are
+    /// relative to the directory of the dictionary
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use img_hash_linker::algorithm::thumbnail_verification::VerificationOptions;
+    ///
+    /// let options = VerificationOptions::for_dictionary("data/links.csv");
+    /// assert_eq!(options.thumbnail_dir.as_deref(), Some(Path::new("data")));
+    /// ```
+    pub fn for_dictionary(dict_path: impl AsRef<Path>) -> Self {
+        VerificationOptions {
+            thumbnail_dir: Some(
+                dict_path
+                    .as_ref()
+                    .parent()
+                    .map_or(PathBuf::new(), Path::to_path_buf),
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+/// The matches that passed verification, along with the thumbnails that could not be read.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VerifiedMatches {
+    /// The verified candidates, from the most similar, followed by the unverified ones
+    pub matches: Vec<HashMatch>,
+    /// One warning per thumbnail that could not be opened, its candidate being kept unverified
+    pub warnings: Vec<String>,
+}
+
+impl Default for VerificationOptions {
+    fn default() -> Self {
+        VerificationOptions {
+            similarity: ThumbnailSimilarity::default(),
+            min_similarity: 0.5,
+            size: 32,
+            thumbnail_dir: None,
+        }
+    }
+}
+
+/// Verifies hash matches against the reference thumbnails of their entries, re-ranking
+/// them by pixel similarity and rejecting the dissimilar ones.
+///
+/// Hashes only keep a coarse average of the image, so pictures with a similar layout
+/// may have close hashes. Each candidate whose entry has a thumbnail gets its
+/// [`thumbnail_similarity`](HashMatch::thumbnail_similarity) measured against the query:
+/// candidates below `min_similarity` are rejected, the others are sorted from the most
+/// similar. Candidates without a thumbnail cannot be verified: they are kept, after the
+/// verified ones, in their original order. So are the candidates whose thumbnail cannot
+/// be opened, with a warning: one missing file does not stop the lookup.
+///
+/// # Arguments
+///
+/// * `query` - The searched image, processed as before hashing (see
+///   [`preprocess_image`](crate::preprocess_image)), e.g. with its white borders removed
+/// * `candidates` - The matches of the query hash, e.g. from
+///   [`find_top_k_similar_hashes`](crate::algorithm::hash_proximity::find_top_k_similar_hashes)
+/// * `links` - The dictionary entries the candidates come from, with their `thumbnail`
+/// * `options` - The verification settings
+///
+/// # Returns
+///
+/// The candidates that passed verification, re-ranked, and the warnings about the
+/// thumbnails that could not be opened. Use [`classify_verified_matches`] to tell a
+/// confident match from an ambiguous one.
+///
+/// # Examples
+///
+/// ```no_run
+/// use img_hash_linker::algorithm::distance_metric::Hamming;
+/// use img_hash_linker::algorithm::hash_proximity::find_top_k_similar_hashes;
+/// use img_hash_linker::algorithm::thumbnail_verification::{VerificationOptions, verify_matches};
+/// use img_hash_linker::data_handle::load_csv::load_entries_from_csv;
+/// use img_hash_linker::{Preprocessing, compute_hash_with_preprocessing, preprocess_image};
+///
+/// let links = load_entries_from_csv("links.csv".to_string())?;
+/// let image = preprocess_image(image::open("path/to/image.jpg").unwrap(), &Preprocessing::default());
+/// let hash = compute_hash_with_preprocessing(image.clone(), &Preprocessing::None, None)?;
+///
+/// let candidates = find_top_k_similar_hashes(&hash, &links, 10, None, &Hamming)?;
+/// let options = VerificationOptions::for_dictionary("links.csv");
+/// let verified = verify_matches(&image, candidates, &links, &options);
+/// for warning in &verified.warnings {
+///     eprintln!("Warning: {}", warning);
+/// }
+/// if let Some(best) = verified.matches.first() {
+///     println!("{} (Similarity: {:.2})", best.link, best.thumbnail_similarity.unwrap_or(0.0));
+/// }
+/// # Ok::<(), String>(())
+/// ```
+pub fn verify_matches<E: HashEntry>(
+    query: &DynamicImage,
+    candidates: Vec<HashMatch>,
+    links: &[E],
+    options: &VerificationOptions,
+) -> VerifiedMatches {
+    let query: GrayImage = to_gray(query, options.size);
+
+    let mut verified: Vec<HashMatch> = Vec::new();
+    let mut unverified: Vec<HashMatch> = Vec::new();
+    let mut warnings: Vec<String> = Vec::new();
+
+    // The first thumbnail of each entry, found once rather than for each candidate
+    let mut thumbnails: HashMap<(&str, &str), &str> = HashMap::new();
+    for entry in links {
+        if let Some(thumbnail) = entry.thumbnail() {
+            thumbnails
+                .entry((entry.hash(), entry.link()))
+                .or_insert(thumbnail);
+        }
+    }
+
+    for mut candidate in candidates {
+        let thumbnail: Option<&str> = thumbnails
+            .get(&(candidate.hash.as_str(), candidate.link.as_str()))
+            .copied();
+
+        let Some(thumbnail) = thumbnail else {
+            unverified.push(candidate);
+            continue;
+        };
+
+        let path: PathBuf = match &options.thumbnail_dir {
+            Some(dir) => dir.join(thumbnail),
+            None => Path::new(thumbnail).to_path_buf(),
+        };
+        let reference: DynamicImage = match image::open(&path) {
+            Ok(reference) => reference,
+            Err(e) => {
+                warnings.push(format!(
+                    "Failed to open thumbnail {}, {} left unverified: {}",
+                    path.display(),
+                    candidate.link,
+                    e
+                ));
+                unverified.push(candidate);
+                continue;
+            }
+        };
+
+        let similarity: f64 = gray_similarity(
+            &query,
+            &to_gray(&reference, options.size),
+            options.similarity,
+        );
+        if similarity >= options.min_similarity {
+            candidate.thumbnail_similarity = Some(similarity);
+            verified.push(candidate);
+        }
+    }
+
+    // Sort from the most to the least similar, keeping the hash order for ties
+    verified.sort_by(|a, b| {
+        b.thumbnail_similarity
+            .partial_cmp(&a.thumbnail_similarity)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    verified.extend(unverified);
+
+    VerifiedMatches {
+        matches: verified,
+        warnings,
+    }
+}
+
+/// Turn verified matches, as returned by [`verify_matches`], into a [`MatchOutcome`]
+///
+/// Verification re-ranks the candidates, so the best one may have a lower hash proximity
+/// than the runner-up: when both were verified, the margin is measured on their thumbnail
+/// similarity instead. A verified best match is only ambiguous with an unverified
+/// runner-up whose hash is at least as close, within the margin. Without any verified
+/// candidate, this is [`classify_matches`].
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::algorithm::hash_proximity::{HashMatch, MatchOutcome};
+/// use img_hash_linker::algorithm::thumbnail_verification::classify_verified_matches;
+///
+/// let candidate = |link: &str, proximity: f64, similarity: f64| HashMatch {
+///     hash: "ffffffff00000000".to_string(),
+///     link: link.to_string(),
+///     distance: 0.0,
+///     proximity,
+///     false_match_probability: None,
+///     thumbnail_similarity: Some(similarity),
+/// };
+///
+/// // Promoted by its thumbnail despite a lower hash proximity
+/// let verified = vec![
+///     candidate("https://example.com", 0.96, 0.9),
+///     candidate("https://another.com", 0.98, 0.6),
+/// ];
+/// let outcome = classify_verified_matches("ffffffff00000001", verified, 0.01);
+/// assert!(matches!(outcome, MatchOutcome::Confident(found) if found.link == "https://example.com"));
+/// ```
+pub fn classify_verified_matches(
+    hash: &str,
+    verified: Vec<HashMatch>,
+    ambiguity_margin: f64,
+) -> MatchOutcome {
+    let Some(best_similarity) = verified.first().and_then(|best| best.thumbnail_similarity) else {
+        return classify_matches(hash, verified, ambiguity_margin);
+    };
+
+    let mut candidates = verified.into_iter();
+    // Safe to unwrap since the first candidate was just read
+    let best: HashMatch = candidates.next().unwrap();
+
+    // Other entries pointing to the same link do not make the match ambiguous
+    let runner_up: Option<HashMatch> = candidates.find(|candidate| candidate.link != best.link);
+
+    let ambiguous: bool =
+        runner_up
+            .as_ref()
+            .is_some_and(|runner_up| match runner_up.thumbnail_similarity {
+                Some(similarity) => best_similarity - similarity <= ambiguity_margin,
+                None => best.proximity - runner_up.proximity <= ambiguity_margin,
+            });
+
+    match runner_up {
+        Some(runner_up) if ambiguous => MatchOutcome::Ambiguous { best, runner_up },
+        _ if best.hash == hash => MatchOutcome::Exact(best),
+        _ => MatchOutcome::Confident(best),
+    }
+}
+
+/// Similarity between two images, from -1.0 (inverted) to 1.0 (identical)
+///
+/// Both images are converted to gray and resized to `size`×`size` pixels first.
+///
+/// # Examples
+///
+/// ```
+/// use image::{DynamicImage, GrayImage, Luma};
+/// use img_hash_linker::algorithm::thumbnail_verification::{ThumbnailSimilarity, image_similarity};
+///
+/// let gradient = GrayImage::from_fn(64, 64, |x, y| Luma([(x * 2 + y) as u8]));
+/// let inverted = GrayImage::from_fn(64, 64, |x, y| Luma([255 - (x * 2 + y) as u8]));
+///
+/// let (a, b) = (DynamicImage::ImageLuma8(gradient), DynamicImage::ImageLuma8(inverted));
+/// assert!(image_similarity(&a, &a, ThumbnailSimilarity::Ssim, 32) > 0.99);
+/// assert!(image_similarity(&a, &b, ThumbnailSimilarity::NormalizedCrossCorrelation, 32) < -0.99);
+/// ```
+pub fn image_similarity(
+    a: &DynamicImage,
+    b: &DynamicImage,
+    similarity: ThumbnailSimilarity,
+    size: u32,
+) -> f64 {
+    gray_similarity(&to_gray(a, size), &to_gray(b, size), similarity)
+}
+
+fn to_gray(image: &DynamicImage, size: u32) -> GrayImage {
+    image
+        .resize_exact(size.max(1), size.max(1), FilterType::Triangle)
+        .to_luma8()
+}
+
+fn gray_similarity(a: &GrayImage, b: &GrayImage, similarity: ThumbnailSimilarity) -> f64 {
+    let a: Vec<f64> = a.pixels().map(|p| p.0[0] as f64).collect();
+    let b: Vec<f64> = b.pixels().map(|p| p.0[0] as f64).collect();
+
+    match similarity {
+        ThumbnailSimilarity::NormalizedCrossCorrelation => normalized_cross_correlation(&a, &b),
+        ThumbnailSimilarity::Ssim => {
+            // Both images are square, with the same side
+            let side: usize = (a.len() as f64).sqrt() as usize;
+            mean_ssim(&a, &b, side)
+        }
+    }
+}
+
+fn normalized_cross_correlation(a: &[f64], b: &[f64]) -> f64 {
+    let (mean_a, var_a, mean_b, var_b, covariance) = statistics(a, b);
+    let denominator: f64 = (var_a * var_b).sqrt();
+
+    if denominator == 0.0 {
+        // Uniform images only correlate with uniform images of the same gray
+        return if var_a == var_b && (mean_a - mean_b).abs() < 1.0 {
+            1.0
+        } else {
+            0.0
+        };
+    }
+
+    covariance / denominator
+}
+
+/// Side, in pixels, of the windows SSIM is computed over
+const SSIM_WINDOW: usize = 8;
+
+fn mean_ssim(a: &[f64], b: &[f64], side: usize) -> f64 {
+    // The usual stabilizing constants, for gray levels from 0 to 255
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let window: usize = SSIM_WINDOW.min(side).max(1);
+    let mut total: f64 = 0.0;
+    let mut count: usize = 0;
+
+    for top in (0..side).step_by(window) {
+        for left in (0..side).step_by(window) {
+            let mut window_a: Vec<f64> = Vec::with_capacity(window * window);
+            let mut window_b: Vec<f64> = Vec::with_capacity(window * window);
+            for y in top..(top + window).min(side) {
+                let row: usize = y * side;
+                window_a.extend(&a[row + left..row + (left + window).min(side)]);
+                window_b.extend(&b[row + left..row + (left + window).min(side)]);
+            }
+
+            let (mean_a, var_a, mean_b, var_b, covariance) = statistics(&window_a, &window_b);
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        1.0
+    } else {
+        total / count as f64
+    }
+}
+
+/// Means, variances and covariance of two series of gray levels
+fn statistics(a: &[f64], b: &[f64]) -> (f64, f64, f64, f64, f64) {
+    let count: f64 = a.len().max(1) as f64;
+    let mean_a: f64 = a.iter().sum::<f64>() / count;
+    let mean_b: f64 = b.iter().sum::<f64>() / count;
+
+    let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        var_a += (x - mean_a) * (x - mean_a);
+        var_b += (y - mean_b) * (y - mean_b);
+        covariance += (x - mean_a) * (y - mean_b);
+    }
+
+    (
+        mean_a,
+        var_a / count,
+        mean_b,
+        var_b / count,
+        covariance / count,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, GrayImage, Luma};
+
+    fn candidate(link: &str, proximity: f64, similarity: Option<f64>) -> HashMatch {
+        HashMatch {
+            hash: "ffffffff00000000".to_string(),
+            link: link.to_string(),
+            distance: 0.0,
+            proximity,
+            false_match_probability: None,
+            thumbnail_similarity: similarity,
+        }
+    }
+
+    fn gradient(size: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(size, size, |x, y| {
+            Luma([((x * 255) / size.max(1) + y) as u8])
+        }))
+    }
+
+    #[test]
+    fn unreadable_thumbnails_leave_candidates_unverified() {
+        let links = vec![crate::data_handle::dictionary_entry::DictionaryEntry {
+            hash: "ffffffff00000000".to_string(),
+            link: "https://example.com".to_string(),
+            thumbnail: Some("missing.png".to_string()),
+            ..Default::default()
+        }];
+
+        let options: VerificationOptions =
+            VerificationOptions::for_dictionary("/nonexistent/links.csv");
+        let verified: VerifiedMatches = verify_matches(
+            &gradient(16),
+            vec![candidate("https://example.com", 1.0, None)],
+            &links,
+            &options,
+        );
+
+        assert_eq!(verified.matches.len(), 1);
+        assert_eq!(verified.matches[0].thumbnail_similarity, None);
+        assert_eq!(verified.warnings.len(), 1);
+        assert!(verified.warnings[0].contains("/nonexistent/missing.png"));
+    }
+
+    #[test]
+    fn thumbnails_are_found_by_hash_and_link() {
+        let entry = |hash: &str, link: &str, thumbnail: Option<&str>| {
+            crate::data_handle::dictionary_entry::DictionaryEntry {
+                hash: hash.to_string(),
+                link: link.to_string(),
+                thumbnail: thumbnail.map(String::from),
+                ..Default::default()
+            }
+        };
+        let links = vec![
+            entry("ffffffff00000000", "https://example.com", None),
+            entry("ffffffff00000000", "https://example.com", Some("first.png")),
+            entry(
+                "ffffffff00000000",
+                "https://example.com",
+                Some("second.png"),
+            ),
+            entry("00000000ffffffff", "https://another.com", Some("other.png")),
+        ];
+
+        let options: VerificationOptions =
+            VerificationOptions::for_dictionary("/nonexistent/links.csv");
+        let verified: VerifiedMatches = verify_matches(
+            &gradient(16),
+            vec![
+                candidate("https://example.com", 1.0, None),
+                candidate("https://another.com", 0.9, None),
+            ],
+            &links,
+            &options,
+        );
+
+        // The first thumbnail of the entry is used, and the other link has no entry
+        // with the candidate hash, so no thumbnail
+        assert_eq!(verified.matches.len(), 2);
+        assert_eq!(verified.warnings.len(), 1);
+        assert!(verified.warnings[0].contains("first.png"));
+    }
+
+    #[test]
+    fn dictionary_directory_of_a_bare_file_name() {
+        let options: VerificationOptions = VerificationOptions::for_dictionary("links.csv");
+        assert_eq!(options.thumbnail_dir, Some(PathBuf::new()));
+    }
+
+    #[test]
+    fn promoted_candidates_can_be_confident() {
+        let verified = vec![
+            candidate("https://a.com", 0.96, Some(0.9)),
+            candidate("https://b.com", 0.99, Some(0.5)),
+        ];
+        assert!(matches!(
+            classify_verified_matches("0", verified, 0.01),
+            MatchOutcome::Confident(found) if found.link == "https://a.com"
+        ));
+    }
+
+    #[test]
+    fn close_similarities_are_ambiguous() {
+        let verified = vec![
+            candidate("https://a.com", 0.96, Some(0.9)),
+            candidate("https://b.com", 0.99, Some(0.895)),
+        ];
+        assert!(matches!(
+            classify_verified_matches("0", verified, 0.01),
+            MatchOutcome::Ambiguous { .. }
+        ));
+    }
+
+    #[test]
+    fn unverified_runner_up_is_compared_by_proximity() {
+        let closer = vec![
+            candidate("https://a.com", 0.96, Some(0.9)),
+            candidate("https://b.com", 0.99, None),
+        ];
+        assert!(matches!(
+            classify_verified_matches("0", closer, 0.01),
+            MatchOutcome::Ambiguous { .. }
+        ));
+
+        let farther = vec![
+            candidate("https://a.com", 0.99, Some(0.9)),
+            candidate("https://b.com", 0.96, None),
+        ];
+        assert!(matches!(
+            classify_verified_matches("0", farther, 0.01),
+            MatchOutcome::Confident(_)
+        ));
+    }
+
+    #[test]
+    fn without_verified_candidates_the_hash_proximity_decides() {
+        assert_eq!(
+            classify_verified_matches("0", Vec::new(), 0.01),
+            MatchOutcome::NoMatch
+        );
+
+        let unverified = vec![candidate("https://a.com", 0.99, None)];
+        assert!(matches!(
+            classify_verified_matches("ffffffff00000000", unverified, 0.01),
+            MatchOutcome::Exact(_)
+        ));
+    }
+
+    #[test]
+    fn similarity_of_uniform_and_tiny_images() {
+        let black = DynamicImage::ImageLuma8(GrayImage::new(8, 8));
+        let white = DynamicImage::ImageLuma8(GrayImage::from_pixel(8, 8, Luma([255])));
+
+        for method in [
+            ThumbnailSimilarity::Ssim,
+            ThumbnailSimilarity::NormalizedCrossCorrelation,
+        ] {
+            assert!(image_similarity(&black, &black, method, 8) > 0.99);
+            assert!(image_similarity(&black, &white, method, 8) < 0.5);
+            // A size of 0 is compared as a single pixel
+            assert!(image_similarity(&gradient(7), &gradient(7), method, 0) > 0.99);
+        }
+
+        // Odd sizes do not fit whole SSIM windows
+        assert!(
+            image_similarity(&gradient(13), &gradient(13), ThumbnailSimilarity::Ssim, 13) > 0.99
+        );
+    }
+}
@@ -1,3 +1,9 @@
+use crate::algorithm::bk_tree::BkTree;
+use crate::algorithm::hash_index::HashIndex;
+use crate::algorithm::hash_information::DegenerateHashCheck;
+use crate::data_handle::dictionary_entry::DictionaryEntry;
+use crate::data_handle::dictionary_schema::{DictionaryColumns, DictionarySchema};
+use crate::data_handle::load_csv::is_valid_entry;
 use csv::{Reader, StringRecord, Writer};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
@@ -7,44 +13,238 @@ use std::path::Path;
 ///
 /// # Example
 ///
-/// ```
-/// fn main() {
-///     let data = vec![(
-///         "xxxxxxxxxxxxxxxx".to_string(),
-///         "https://example.com".to_string(),
-///     )];
+/// ```no_run
+/// use img_hash_linker::data_handle::add_data_to_csv_file::add_entry_to_data_file;
 ///
-///     add_entry_to_data_file(data, "example.csv".to_string())?;
-/// }
+/// let data = vec![(
+///     "xxxxxxxxxxxxxxxx".to_string(),
+///     "https://example.com".to_string(),
+/// )];
+///
+/// add_entry_to_data_file(data, "example.csv".to_string())?;
+/// # Ok::<(), String>(())
 /// ```
 pub fn add_entry_to_data_file(
     data: Vec<(String, String)>,
     dict_path: String,
+) -> Result<(), String> {
+    let schema: DictionarySchema = DictionarySchema::for_path(&dict_path);
+    add_entry_to_data_file_with_schema(data, dict_path, &schema)
+}
+
+/// Adds entries to a dictionary file in the given layout
+///
+/// The hash and link columns are found with the same names as when reading the file,
+/// and a new file is created with the first name of each column.
+///
+/// # Example
+///
+/// ```no_run
+/// use img_hash_linker::data_handle::add_data_to_csv_file::add_entry_to_data_file_with_schema;
+/// use img_hash_linker::data_handle::dictionary_schema::DictionarySchema;
+///
+/// let schema = DictionarySchema {
+///     link_columns: vec!["url".to_string(), "link".to_string()],
+///     delimiter: b';',
+///     ..Default::default()
+/// };
+///
+/// let data = vec![(
+///     "ffff0000ffff0000".to_string(),
+///     "https://example.com".to_string(),
+/// )];
+///
+/// add_entry_to_data_file_with_schema(data, "example.csv".to_string(), &schema)?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn add_entry_to_data_file_with_schema(
+    data: Vec<(String, String)>,
+    dict_path: String,
+    schema: &DictionarySchema,
 ) -> Result<(), String> {
     let path: &Path = Path::new(&dict_path);
 
     // If file doesn't exist, create it already with the headers
     if !path.exists() {
-        create_csv_file(&dict_path)?;
+        create_csv_file(&dict_path, schema)?;
     }
 
     // Get header positions
-    let (hash_index, link_index, num_columns) = get_header_positions(&dict_path)?;
+    let (hash_index, link_index, num_columns) = get_header_positions(&dict_path, schema)?;
 
     // Append data to file
-    append_data_to_csv(&dict_path, data, hash_index, link_index, num_columns)?;
+    append_data_to_csv(
+        &dict_path,
+        data,
+        hash_index,
+        link_index,
+        num_columns,
+        schema,
+    )?;
+
+    Ok(())
+}
+
+/// Adds entries to a CSV file with hash and link columns, detecting degenerate hashes
+///
+/// Every hash is checked before anything is written: with the `Reject` policy, a single
+/// degenerate hash prevents all the entries from being added. Returns the warnings
+/// of the `Warn` policy.
+///
+/// # Example
+///
+/// ```no_run
+/// use img_hash_linker::algorithm::hash_information::DegenerateHashCheck;
+/// use img_hash_linker::data_handle::add_data_to_csv_file::add_entry_to_data_file_checked;
+///
+/// let data = vec![(
+///     "ffff0000ffff0000".to_string(),
+///     "https://example.com".to_string(),
+/// )];
+///
+/// for warning in add_entry_to_data_file_checked(data, "example.csv".to_string(), &DegenerateHashCheck::default())? {
+///     eprintln!("{}", warning);
+/// }
+/// # Ok::<(), String>(())
+/// ```
+pub fn add_entry_to_data_file_checked(
+    data: Vec<(String, String)>,
+    dict_path: String,
+    check: &DegenerateHashCheck,
+) -> Result<Vec<String>, String> {
+    let mut warnings: Vec<String> = Vec::new();
+    for (hash, _) in &data {
+        if let Some(warning) = check.check_hash(hash)? {
+            warnings.push(warning);
+        }
+    }
+
+    add_entry_to_data_file(data, dict_path)?;
+
+    Ok(warnings)
+}
+
+/// Adds entries to a CSV file with hash and link columns, and to an index built from that file
+///
+/// Every entry is checked before anything is written: like when loading the file, it
+/// needs a hash and a URL, and its hash must be valid and match the length of the
+/// indexed hashes. A single invalid entry prevents all of them from being added, so
+/// the file and the index stay in sync.
+///
+/// # Example
+///
+/// ```no_run
+/// use img_hash_linker::algorithm::bk_tree::BkTree;
+/// use img_hash_linker::data_handle::add_data_to_csv_file::add_entry_to_data_file_with_index;
+/// use img_hash_linker::data_handle::load_csv::load_entries_from_csv;
+///
+/// let mut index = BkTree::from_entries(&load_entries_from_csv("example.csv".to_string())?)?;
+///
+/// let data = vec![(
+///     "ffff0000ffff0000".to_string(),
+///     "https://example.com".to_string(),
+/// )];
+///
+/// add_entry_to_data_file_with_index(data, "example.csv".to_string(), &mut index)?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn add_entry_to_data_file_with_index(
+    data: Vec<(String, String)>,
+    dict_path: String,
+    index: &mut BkTree,
+) -> Result<(), String> {
+    // Make sure every entry would be loaded back, with a hash matching the indexed ones
+    let hash_len: Option<usize> = index.hash_len().or(data.first().map(|(h, _)| h.len()));
+    for (hash, link) in &data {
+        if !is_valid_entry(hash, link) {
+            return Err(format!(
+                "Invalid entry: the hash must not be empty and the link must be a URL, got {} for {}",
+                hash, link
+            ));
+        }
+        index.parse_hash(hash)?;
+        if Some(hash.len()) != hash_len {
+            return Err(format!("Hash length mismatch: {}", hash));
+        }
+    }
+
+    add_entry_to_data_file(data.clone(), dict_path)?;
+
+    for (hash, link) in &data {
+        index.insert(hash, link)?;
+    }
 
     Ok(())
 }
 
-fn create_csv_file(file_path: &str) -> Result<(), String> {
+/// Writes dictionary entries as CSV, e.g. to save a cleaned-up dictionary
+///
+/// The `threshold` and `thumbnail` columns are only written if an entry has them.
+///
+/// # Example
+///
+/// ```
+/// use img_hash_linker::data_handle::add_data_to_csv_file::write_entries_to_csv;
+/// use img_hash_linker::data_handle::dictionary_entry::DictionaryEntry;
+///
+/// let entries = vec![DictionaryEntry::new(
+///     "ffff0000ffff0000".to_string(),
+///     "https://example.com".to_string(),
+/// )];
+///
+/// let mut output: Vec<u8> = Vec::new();
+/// write_entries_to_csv(&entries, &mut output)?;
+/// assert_eq!(output, b"hash,link\nffff0000ffff0000,https://example.com\n");
+/// # Ok::<(), String>(())
+/// ```
+pub fn write_entries_to_csv<W: Write>(
+    entries: &[DictionaryEntry],
+    output: W,
+) -> Result<(), String> {
+    write_entries_to_csv_with_schema(entries, output, &DictionarySchema::default())
+}
+
+/// Writes dictionary entries in the given layout, see [`write_entries_to_csv`]
+pub fn write_entries_to_csv_with_schema<W: Write>(
+    entries: &[DictionaryEntry],
+    output: W,
+    schema: &DictionarySchema,
+) -> Result<(), String> {
+    let with_threshold: bool = entries.iter().any(|entry| entry.threshold.is_some());
+    let with_thumbnail: bool = entries.iter().any(|entry| entry.thumbnail.is_some());
+
+    let mut writer: Writer<W> = schema.writer_builder().from_writer(output);
+
+    writer
+        .write_record(schema.headers(with_threshold, with_thumbnail))
+        .map_err(|e| format!("Failed to write headers: {}", e))?;
+
+    for entry in entries {
+        let mut row: Vec<String> = vec![entry.hash.clone(), entry.link.clone()];
+        if with_threshold {
+            row.push(entry.threshold.map_or(String::new(), |t| t.to_string()));
+        }
+        if with_thumbnail {
+            row.push(entry.thumbnail.clone().unwrap_or_default());
+        }
+        writer
+            .write_record(&row)
+            .map_err(|e| format!("Failed to write new record: {}", e))?;
+    }
+
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to flush CSV writer: {}", e))
+}
+
+fn create_csv_file(file_path: &str, schema: &DictionarySchema) -> Result<(), String> {
     let file: File =
         File::create(file_path).map_err(|e| format!("Failed to create CSV file: {}", e))?;
-    let mut writer: Writer<File> = Writer::from_writer(file);
+    let mut writer: Writer<File> = schema.writer_builder().from_writer(file);
 
     // Write headers
     writer
-        .write_record(&["hash", "link"])
+        .write_record(schema.headers(false, false))
         .map_err(|e| format!("Failed to write headers: {}", e))?;
 
     // Flush writer
@@ -54,25 +254,21 @@ fn create_csv_file(file_path: &str) -> Result<(), String> {
     Ok(())
 }
 
-fn get_header_positions(file_path: &str) -> Result<(usize, usize, usize), String> {
+fn get_header_positions(
+    file_path: &str,
+    schema: &DictionarySchema,
+) -> Result<(usize, usize, usize), String> {
     let file: File =
         File::open(file_path).map_err(|e| format!("Failed to open CSV file: {}", e))?;
-    let mut reader: Reader<File> = Reader::from_reader(file);
+    let mut reader: Reader<File> = schema.reader_builder().from_reader(file);
 
     let headers: &StringRecord = reader
         .headers()
         .map_err(|e| format!("Failed to read CSV headers: {}", e))?;
 
-    let hash_index: usize = headers
-        .iter()
-        .position(|h| h == "hash")
-        .ok_or("CSV file must contain a 'hash' header")?;
-    let link_index: usize = headers
-        .iter()
-        .position(|h| h == "link")
-        .ok_or("CSV file must contain a 'link' header")?;
+    let columns: DictionaryColumns = schema.columns(headers)?;
 
-    Ok((hash_index, link_index, headers.len()))
+    Ok((columns.hash, columns.link, headers.len()))
 }
 
 fn append_data_to_csv(
@@ -81,6 +277,7 @@ fn append_data_to_csv(
     hash_index: usize,
     link_index: usize,
     num_columns: usize,
+    schema: &DictionarySchema,
 ) -> Result<(), String> {
     if data.is_empty() {
         return Ok(());
@@ -91,12 +288,11 @@ fn append_data_to_csv(
 
     // Open file for CSV writing
     let file: File = OpenOptions::new()
-        .write(true)
         .append(true)
         .open(file_path)
         .map_err(|e| format!("Failed to open CSV file for append: {}", e))?;
 
-    let mut writer: Writer<File> = Writer::from_writer(file);
+    let mut writer: Writer<File> = schema.writer_builder().from_writer(file);
 
     // Write data
     for (hash, link) in data {
@@ -119,8 +315,7 @@ fn ensure_file_ends_with_newline(file_path: &str) -> Result<(), String> {
     // First open the file for reading to check if we need a newline
     let mut file: File = OpenOptions::new()
         .read(true)
-        .write(true) // Open for both reading and writing
-        .append(true)
+        .append(true) // Open for both reading and appending
         .open(file_path)
         .map_err(|e| format!("Failed to open CSV file: {}", e))?;
 
@@ -153,3 +348,146 @@ fn ensure_file_ends_with_newline(file_path: &str) -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::hash_information::DegenerateHashPolicy;
+    use crate::test_support::{temp_csv, temp_path};
+
+    fn entry(hash: &str, link: &str) -> (String, String) {
+        (hash.to_string(), link.to_string())
+    }
+
+    #[test]
+    fn new_files_get_the_headers() {
+        let path: String = temp_path("new.csv").display().to_string();
+        let added: Result<(), String> = add_entry_to_data_file(
+            vec![entry("ffff0000ffff0000", "https://a.com")],
+            path.clone(),
+        );
+        let contents: String = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(added, Ok(()));
+        assert_eq!(contents, "hash,link\nffff0000ffff0000,https://a.com\n");
+    }
+
+    #[test]
+    fn entries_are_appended_in_the_columns_of_the_file() {
+        let path: String = temp_csv("append", "link,notes,hash\nhttps://a.com,kept,f0f");
+        let added: Result<(), String> =
+            add_entry_to_data_file(vec![entry("0f0", "https://b.com")], path.clone());
+        let empty: Result<(), String> = add_entry_to_data_file(Vec::new(), path.clone());
+        let contents: String = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(added, Ok(()));
+        assert_eq!(empty, Ok(()));
+        assert_eq!(
+            contents,
+            "link,notes,hash\nhttps://a.com,kept,f0f\nhttps://b.com,,0f0\n"
+        );
+    }
+
+    #[test]
+    fn degenerate_hashes_are_checked_first() {
+        let path: String = temp_csv("degenerate", "hash,link\n");
+        let check: DegenerateHashCheck = DegenerateHashCheck {
+            policy: DegenerateHashPolicy::Reject,
+            ..Default::default()
+        };
+        let data: Vec<(String, String)> = vec![
+            entry("ffff0000ffff0000", "https://a.com"),
+            entry("ffffffffffffffff", "https://b.com"),
+        ];
+        let added: Result<Vec<String>, String> =
+            add_entry_to_data_file_checked(data.clone(), path.clone(), &check);
+        let contents: String = std::fs::read_to_string(&path).unwrap();
+
+        assert!(added.is_err());
+        assert_eq!(contents, "hash,link\n");
+
+        let check: DegenerateHashCheck = DegenerateHashCheck::default();
+        let warnings: Result<Vec<String>, String> =
+            add_entry_to_data_file_checked(data, path.clone(), &check);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(warnings.map(|warnings| warnings.len()), Ok(1));
+    }
+
+    #[test]
+    fn invalid_entries_reach_neither_the_file_nor_the_index() {
+        let csv: &str = "hash,link\nffff0000ffff0000,https://a.com\n";
+        let invalid: [(String, String); 5] = [
+            entry("", "https://b.com"),
+            entry("0000ffff0000ffff", ""),
+            entry("0000ffff0000ffff", "not a link"),
+            entry("zzzzffff0000ffff", "https://b.com"),
+            entry("0f0", "https://b.com"),
+        ];
+
+        for bad_entry in invalid {
+            let path: String = temp_csv("index", csv);
+            let mut index: BkTree =
+                BkTree::from_entries(&[entry("ffff0000ffff0000", "https://a.com")]).unwrap();
+
+            // The valid entry before the invalid one is not added either
+            let data: Vec<(String, String)> = vec![
+                entry("00000000ffffffff", "https://c.com"),
+                bad_entry.clone(),
+            ];
+            let added: Result<(), String> =
+                add_entry_to_data_file_with_index(data, path.clone(), &mut index);
+            let contents: String = std::fs::read_to_string(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert!(added.is_err(), "{:?} should be refused", bad_entry);
+            assert_eq!(contents, csv);
+            assert_eq!(index.len(), 1);
+        }
+    }
+
+    #[test]
+    fn valid_entries_reach_the_file_and_the_index() {
+        let path: String = temp_csv("index_valid", "hash,link\n");
+        let mut index: BkTree = BkTree::new();
+        let added: Result<(), String> = add_entry_to_data_file_with_index(
+            vec![entry("ffff0000ffff0000", "https://a.com")],
+            path.clone(),
+            &mut index,
+        );
+        let contents: String = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(added, Ok(()));
+        assert_eq!(contents, "hash,link\nffff0000ffff0000,https://a.com\n");
+        assert_eq!(
+            index.find_within("ffff0000ffff0001", 1),
+            Ok(vec![(
+                "ffff0000ffff0000".to_string(),
+                "https://a.com".to_string(),
+                1
+            )])
+        );
+    }
+
+    #[test]
+    fn settings_columns_are_only_written_when_used() {
+        let mut entries: Vec<DictionaryEntry> = vec![
+            DictionaryEntry::new("f0f".to_string(), "https://a.com".to_string()),
+            DictionaryEntry::new("0f0".to_string(), "https://b.com".to_string()),
+        ];
+        entries[1].threshold = Some(0.9);
+
+        let mut output: Vec<u8> = Vec::new();
+        write_entries_to_csv(&entries, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "hash,link,threshold\nf0f,https://a.com,\n0f0,https://b.com,0.9\n"
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        write_entries_to_csv(&[], &mut output).unwrap();
+        assert_eq!(output, b"hash,link\n");
+    }
+}
@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use csv::{Reader, StringRecord};
+
+use crate::algorithm::distance_metric::WeightedBits;
+
+/// Path of the bit weights file stored alongside a dictionary for a hash size, since each
+/// size has its own bits: `links.csv` has its 8×8 weights in `links.weights.8x8.csv`.
+pub fn bit_weights_path(dict_path: &str, hash_size: u32) -> PathBuf {
+    Path::new(dict_path).with_extension(format!("weights.{}x{}.csv", hash_size, hash_size))
+}
+
+/// Writes bit weights to a CSV file with `bit` and `weight` columns, one row per bit.
+///
+/// ## Arguments
+///
+/// * `weights` - The bit weights, as computed by `compute_bit_weights`
+/// * `path` - Path to the CSV file, see [`bit_weights_path`]
+///
+/// ## Returns
+///
+/// * `Ok(())` - The weights were written
+/// * `Err(String)` - Error message if the file cannot be created or written
+pub fn write_bit_weights(weights: &WeightedBits, path: String) -> Result<(), String> {
+    let mut writer = csv::Writer::from_path(&path)
+        .map_err(|e| format!("Failed to create file {}: {}", path, e))?;
+
+    writer
+        .write_record(["bit", "weight"])
+        .map_err(|e| format!("Failed to write headers: {}", e))?;
+
+    for (bit, weight) in weights.weights.iter().enumerate() {
+        writer
+            .write_record([bit.to_string(), weight.to_string()])
+            .map_err(|e| format!("Failed to write record: {}", e))?;
+    }
+
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to flush CSV writer: {}", e))
+}
+
+/// Loads bit weights from a CSV file written by [`write_bit_weights`].
+///
+/// Every bit from 0 to the last one must have a weight.
+///
+/// ## Arguments
+///
+/// * `path` - Path to the CSV file
+///
+/// ## Returns
+///
+/// * `Ok(WeightedBits)` - The weight of each bit
+/// * `Err(String)` - Error message if the file cannot be read, has an invalid or negative weight, or misses a bit
+pub fn load_bit_weights(path: String) -> Result<WeightedBits, String> {
+    let file: File = File::open(&path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let mut reader: Reader<File> = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(file);
+
+    let headers: StringRecord = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV headers: {}", e))?
+        .clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .ok_or(format!("CSV must have a column named '{}'", name))
+    };
+    let (bit_idx, weight_idx) = (column("bit")?, column("weight")?);
+
+    let mut weights: Vec<Option<f64>> = Vec::new();
+    for (row_idx, result) in reader.records().enumerate() {
+        let record: StringRecord =
+            result.map_err(|e| format!("Failed to read row {}: {}", row_idx + 1, e))?;
+
+        let field = |index: usize| {
+            record.get(index).ok_or(format!(
+                "Row {} has fewer columns than expected",
+                row_idx + 1
+            ))
+        };
+
+        let bit: usize = field(bit_idx)?
+            .parse()
+            .map_err(|e| format!("Row {} has an invalid bit: {}", row_idx + 1, e))?;
+        let weight: f64 = field(weight_idx)?
+            .parse()
+            .map_err(|e| format!("Row {} has an invalid weight: {}", row_idx + 1, e))?;
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(format!(
+                "Row {} has a negative or non-finite weight: {}",
+                row_idx + 1,
+                weight
+            ));
+        }
+
+        if bit >= weights.len() {
+            weights.resize(bit + 1, None);
+        }
+        weights[bit] = Some(weight);
+    }
+
+    let weights: Vec<f64> = weights
+        .into_iter()
+        .enumerate()
+        .map(|(bit, weight)| weight.ok_or(format!("Missing weight for bit {}", bit)))
+        .collect::<Result<_, _>>()?;
+
+    if weights.is_empty() {
+        return Err("CSV file contains no bit weights".to_string());
+    }
+
+    Ok(WeightedBits::new(weights))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_path;
+
+    #[test]
+    fn each_hash_size_has_its_own_file() {
+        assert_eq!(
+            bit_weights_path("dir/links.csv", 8),
+            PathBuf::from("dir/links.weights.8x8.csv")
+        );
+        assert_ne!(
+            bit_weights_path("links.json", 8),
+            bit_weights_path("links.json", 16)
+        );
+    }
+
+    #[test]
+    fn weights_round_trip() {
+        let weights: WeightedBits = WeightedBits::new(vec![0.05, 1.0, 0.5, 0.25]);
+        let path: PathBuf = temp_path("weights.csv");
+
+        write_bit_weights(&weights, path.display().to_string()).unwrap();
+        let loaded: Result<WeightedBits, String> = load_bit_weights(path.display().to_string());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, Ok(weights));
+    }
+}
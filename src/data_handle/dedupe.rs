@@ -0,0 +1,361 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+
+use csv::{Reader, StringRecord, Writer};
+
+use crate::data_handle::dictionary_entry::{DictionaryEntry, HashEntry};
+use crate::data_handle::dictionary_schema::{DictionaryColumns, DictionarySchema};
+use crate::data_handle::load_csv::{load_entries_from_csv, parse_entry};
+
+/// Which entry to keep when the same hash points to different links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Keep the first link, like `open_link_from_hash` does
+    #[default]
+    FirstWins,
+    /// Keep the link of the last row, e.g. when later rows correct earlier ones
+    LastWins,
+    /// Refuse the dictionary
+    Error,
+}
+
+/// The same hash pointing to different links.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashConflict {
+    /// The hash, as written in the kept row
+    pub hash: String,
+    /// The distinct links of the hash, in the order they first appear
+    pub links: Vec<String>,
+    /// The link kept by the policy
+    pub kept: String,
+}
+
+/// The result of deduplicating dictionary entries.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DedupeReport {
+    /// One entry per hash, in the order of the kept rows
+    pub entries: Vec<DictionaryEntry>,
+    /// Number of rows merged into an identical `(hash, link)` row
+    pub merged: usize,
+    /// The hashes pointing to different links, in the order they first appear
+    pub conflicts: Vec<HashConflict>,
+}
+
+/// Deduplicates dictionary entries, keeping one entry per hash.
+///
+/// Identical `(hash, link)` rows are merged into the first one, along with its settings
+/// (into the last one with `LastWins`). Identical hashes pointing to different links are
+/// conflicts, resolved by the policy.
+/// Hashes are compared as written, like `open_link_from_hash` and the exact matches of
+/// `HashDictionary` do: hashes differing by their case are distinct entries.
+///
+/// # Arguments
+///
+/// * `links` - The dictionary entries, `(hash, link)` pairs or any [`HashEntry`]
+/// * `policy` - Which link to keep for conflicting hashes
+///
+/// # Returns
+///
+/// * `Ok(DedupeReport)` - The deduplicated entries, with the merged rows and the conflicts
+/// * `Err(String)` - Error message listing the conflicts, with the `Error` policy
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::data_handle::dedupe::{DuplicatePolicy, dedupe_entries};
+///
+/// let links = vec![
+///     ("ffff0000ffff0000".to_string(), "https://example.com".to_string()),
+///     ("ffff0000ffff0000".to_string(), "https://example.com".to_string()),
+///     ("ffff0000ffff0000".to_string(), "https://another.com".to_string()),
+///     ("0000ffff0000ffff".to_string(), "https://third.com".to_string()),
+/// ];
+///
+/// let report = dedupe_entries(&links, DuplicatePolicy::LastWins)?;
+/// assert_eq!(report.entries.len(), 2);
+/// assert_eq!(report.merged, 1);
+/// assert_eq!(report.conflicts[0].kept, "https://another.com");
+///
+/// assert!(dedupe_entries(&links, DuplicatePolicy::Error).is_err());
+/// # Ok::<(), String>(())
+/// ```
+pub fn dedupe_entries<E: HashEntry>(
+    links: &[E],
+    policy: DuplicatePolicy,
+) -> Result<DedupeReport, String> {
+    dedupe_rows(links, policy).map(|(report, _)| report)
+}
+
+/// Deduplicates dictionary entries, along with the positions of the kept entries in `links`
+fn dedupe_rows<E: HashEntry>(
+    links: &[E],
+    policy: DuplicatePolicy,
+) -> Result<(DedupeReport, Vec<usize>), String> {
+    // The kept row of each link of each hash, in the order the links first appear
+    let mut hashes: Vec<Vec<(usize, &str)>> = Vec::new();
+    let mut hash_index: HashMap<String, usize> = HashMap::new();
+    let mut merged: usize = 0;
+
+    for (row, entry) in links.iter().enumerate() {
+        let index: usize = *hash_index
+            .entry(entry.hash().to_string())
+            .or_insert_with(|| {
+                hashes.push(Vec::new());
+                hashes.len() - 1
+            });
+
+        let rows: &mut Vec<(usize, &str)> = &mut hashes[index];
+        match rows.iter_mut().find(|(_, link)| *link == entry.link()) {
+            Some(kept) => {
+                merged += 1;
+                if policy == DuplicatePolicy::LastWins {
+                    kept.0 = row;
+                }
+            }
+            None => rows.push((row, entry.link())),
+        }
+    }
+
+    let mut kept_rows: Vec<usize> = Vec::new();
+    let mut conflicts: Vec<HashConflict> = Vec::new();
+    for rows in &hashes {
+        let kept: (usize, &str) = match policy {
+            DuplicatePolicy::LastWins => *rows.iter().max_by_key(|(row, _)| *row).unwrap(),
+            _ => rows[0],
+        };
+        kept_rows.push(kept.0);
+
+        if rows.len() > 1 {
+            conflicts.push(HashConflict {
+                hash: links[kept.0].hash().to_string(),
+                links: rows.iter().map(|(_, link)| link.to_string()).collect(),
+                kept: kept.1.to_string(),
+            });
+        }
+    }
+
+    if policy == DuplicatePolicy::Error && !conflicts.is_empty() {
+        let descriptions: Vec<String> = conflicts
+            .iter()
+            .map(|conflict| format!("{} ({})", conflict.hash, conflict.links.join(", ")))
+            .collect();
+        return Err(format!(
+            "Hashes pointing to different links: {}",
+            descriptions.join("; ")
+        ));
+    }
+
+    kept_rows.sort_unstable();
+    let entries: Vec<DictionaryEntry> = kept_rows
+        .iter()
+        .map(|&row| {
+            let entry: &E = &links[row];
+            DictionaryEntry {
+                hash: entry.hash().to_string(),
+                link: entry.link().to_string(),
+                threshold: entry.threshold(),
+                thumbnail: entry.thumbnail().map(str::to_string),
+                metadata: entry.metadata().cloned().unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    Ok((
+        DedupeReport {
+            entries,
+            merged,
+            conflicts,
+        },
+        kept_rows,
+    ))
+}
+
+/// Loads dictionary entries from a CSV file, deduplicated with the given policy.
+///
+/// See [`load_entries_from_csv`] for the file format and [`dedupe_entries`] for how
+/// duplicates are handled.
+///
+/// ## Arguments
+///
+/// * `path` - Path to the CSV file
+/// * `policy` - Which link to keep for conflicting hashes
+///
+/// ## Returns
+///
+/// * `Ok(DedupeReport)` - The deduplicated entries, with the merged rows and the conflicts
+/// * `Err(String)` - Error message if the file cannot be read, or listing the conflicts, with the `Error` policy
+pub fn load_deduped_entries_from_csv(
+    path: String,
+    policy: DuplicatePolicy,
+) -> Result<DedupeReport, String> {
+    dedupe_entries(&load_entries_from_csv(path)?, policy)
+}
+
+/// Deduplicates a CSV file, writing the kept rows as they are to `output`.
+///
+/// Unlike writing the entries of [`dedupe_entries`], every column of the kept rows is
+/// written back, including columns unknown to this crate, and the rows that are not
+/// dictionary entries (e.g. with an empty link) are kept as well: only the duplicate
+/// rows are dropped.
+///
+/// ## Arguments
+///
+/// * `path` - Path to the CSV file (or to a TSV file, with a `.tsv` extension)
+/// * `output` - Where to write the deduplicated CSV data
+/// * `policy` - Which link to keep for conflicting hashes
+///
+/// ## Returns
+///
+/// * `Ok(DedupeReport)` - The deduplicated entries, with the merged rows and the conflicts
+/// * `Err(String)` - Error message if the file cannot be read or the output written, or
+///   listing the conflicts, with the `Error` policy (nothing is written then)
+pub fn dedupe_csv_file<W: Write>(
+    path: String,
+    output: W,
+    policy: DuplicatePolicy,
+) -> Result<DedupeReport, String> {
+    let schema: DictionarySchema = DictionarySchema::for_path(&path);
+    dedupe_csv_file_with_schema(path, output, policy, &schema)
+}
+
+/// Deduplicates a dictionary file in the given layout, see [`dedupe_csv_file`]
+pub fn dedupe_csv_file_with_schema<W: Write>(
+    path: String,
+    output: W,
+    policy: DuplicatePolicy,
+    schema: &DictionarySchema,
+) -> Result<DedupeReport, String> {
+    let file: File = File::open(&path).map_err(|e| format!("Failed to open CSV file: {}", e))?;
+    let mut reader: Reader<File> = schema.reader_builder().from_reader(file);
+
+    let headers: StringRecord = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV headers: {}", e))?
+        .clone();
+    let columns: DictionaryColumns = schema.columns(&headers)?;
+
+    // Every record, and the position of the record of each entry
+    let mut records: Vec<StringRecord> = Vec::new();
+    let mut entries: Vec<DictionaryEntry> = Vec::new();
+    let mut entry_rows: Vec<usize> = Vec::new();
+    for (row_idx, result) in reader.records().enumerate() {
+        let record: StringRecord =
+            result.map_err(|e| format!("Failed to read row {}: {}", row_idx + 1, e))?;
+        if let Some(entry) = parse_entry(&record, &columns, row_idx, true)? {
+            entries.push(entry);
+            entry_rows.push(row_idx);
+        }
+        records.push(record);
+    }
+
+    let (report, kept): (DedupeReport, Vec<usize>) = dedupe_rows(&entries, policy)?;
+    let kept: HashSet<usize> = kept.into_iter().map(|index| entry_rows[index]).collect();
+    let dropped: HashSet<usize> = entry_rows
+        .into_iter()
+        .filter(|row| !kept.contains(row))
+        .collect();
+
+    let mut writer: Writer<W> = schema.writer_builder().from_writer(output);
+    writer
+        .write_record(&headers)
+        .map_err(|e| format!("Failed to write headers: {}", e))?;
+    for (row_idx, record) in records.iter().enumerate() {
+        if !dropped.contains(&row_idx) {
+            writer
+                .write_record(record)
+                .map_err(|e| format!("Failed to write record: {}", e))?;
+        }
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to flush CSV writer: {}", e))?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_csv;
+
+    const CSV: &str = "hash,link,notes\n\
+        ffff0000ffff0000,https://a.com,\"first, note\"\n\
+        ffff0000ffff0000,https://a.com,duplicate\n\
+        ffff0000ffff0000,https://b.com,conflict\n\
+        0000ffff0000ffff,,no link\n";
+
+    /// Deduplicates the contents of a temporary CSV file
+    fn dedupe_csv(name: &str, policy: DuplicatePolicy) -> Result<(DedupeReport, String), String> {
+        let path: String = temp_csv(name, CSV);
+
+        let mut output: Vec<u8> = Vec::new();
+        let report: Result<DedupeReport, String> =
+            dedupe_csv_file(path.clone(), &mut output, policy);
+        std::fs::remove_file(&path).unwrap();
+
+        report.map(|report| (report, String::from_utf8(output).unwrap()))
+    }
+
+    #[test]
+    fn kept_rows_keep_all_their_columns() {
+        let (report, output) = dedupe_csv("first", DuplicatePolicy::FirstWins).unwrap();
+        assert_eq!(report.merged, 1);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(
+            output,
+            "hash,link,notes\n\
+            ffff0000ffff0000,https://a.com,\"first, note\"\n\
+            0000ffff0000ffff,,no link\n"
+        );
+
+        let (_, output) = dedupe_csv("last", DuplicatePolicy::LastWins).unwrap();
+        assert_eq!(
+            output,
+            "hash,link,notes\n\
+            ffff0000ffff0000,https://b.com,conflict\n\
+            0000ffff0000ffff,,no link\n"
+        );
+    }
+
+    #[test]
+    fn conflicts_are_refused_with_the_error_policy() {
+        let error: String = dedupe_csv("error", DuplicatePolicy::Error).unwrap_err();
+        assert!(error.contains("https://a.com, https://b.com"));
+    }
+
+    #[test]
+    fn empty_and_distinct_entries_are_unchanged() {
+        let report: DedupeReport =
+            dedupe_entries::<(String, String)>(&[], DuplicatePolicy::Error).unwrap();
+        assert_eq!(report, DedupeReport::default());
+
+        let links: Vec<(String, String)> = vec![
+            ("f0f".to_string(), "https://a.com".to_string()),
+            ("0f0".to_string(), "https://a.com".to_string()),
+        ];
+        let report: DedupeReport = dedupe_entries(&links, DuplicatePolicy::Error).unwrap();
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(report.merged, 0);
+    }
+
+    #[test]
+    fn hashes_are_compared_as_written() {
+        let links: Vec<(String, String)> = vec![
+            ("ffff".to_string(), "https://a.com".to_string()),
+            ("FFFF".to_string(), "https://b.com".to_string()),
+            ("FFFF".to_string(), "https://c.com".to_string()),
+        ];
+        let report: DedupeReport = dedupe_entries(&links, DuplicatePolicy::FirstWins).unwrap();
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].hash, "FFFF");
+
+        // Each kept link is the first one of its hash, as found by an exact lookup
+        for entry in &report.entries {
+            let first: &(String, String) =
+                links.iter().find(|(hash, _)| *hash == entry.hash).unwrap();
+            assert_eq!(entry.link, first.1);
+        }
+    }
+}
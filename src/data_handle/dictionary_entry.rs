@@ -0,0 +1,92 @@
+use serde_json::{Map, Value};
+
+/// An entry of a hash-link dictionary, as used by the search functions.
+///
+/// Implemented for `(hash, link)` tuples and for [`DictionaryEntry`], which also carries
+/// the optional per-entry settings.
+pub trait HashEntry {
+    /// The hash of the entry
+    fn hash(&self) -> &str;
+
+    /// The link of the entry
+    fn link(&self) -> &str;
+
+    /// The minimum proximity for this entry to match, overriding the search threshold
+    fn threshold(&self) -> Option<f64> {
+        None
+    }
+
+    /// The path of a reference thumbnail of the entry's image, to verify matches
+    fn thumbnail(&self) -> Option<&str> {
+        None
+    }
+
+    /// The other fields of the entry, kept when the dictionary is written back
+    fn metadata(&self) -> Option<&Map<String, Value>> {
+        None
+    }
+}
+
+impl HashEntry for (String, String) {
+    fn hash(&self) -> &str {
+        &self.0
+    }
+
+    fn link(&self) -> &str {
+        &self.1
+    }
+}
+
+/// A dictionary entry, with its optional per-entry settings.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DictionaryEntry {
+    pub hash: String,
+    pub link: String,
+    /// The minimum proximity for this entry to match, e.g. stricter for logos on white
+    /// or looser for visually distinctive images (from the optional `threshold` column)
+    pub threshold: Option<f64>,
+    /// The path of a reference thumbnail of the image, relative to the dictionary
+    /// (from the optional `thumbnail` column), see `thumbnail_verification`
+    pub thumbnail: Option<String>,
+    /// The other fields of the entry, written back as they were read (JSON dictionaries
+    /// only, see `json_dictionary`)
+    pub metadata: Map<String, Value>,
+}
+
+impl DictionaryEntry {
+    pub fn new(hash: String, link: String) -> Self {
+        DictionaryEntry {
+            hash,
+            link,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<(String, String)> for DictionaryEntry {
+    fn from((hash, link): (String, String)) -> Self {
+        DictionaryEntry::new(hash, link)
+    }
+}
+
+impl HashEntry for DictionaryEntry {
+    fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    fn link(&self) -> &str {
+        &self.link
+    }
+
+    fn threshold(&self) -> Option<f64> {
+        self.threshold
+    }
+
+    fn thumbnail(&self) -> Option<&str> {
+        self.thumbnail.as_deref()
+    }
+
+    fn metadata(&self) -> Option<&Map<String, Value>> {
+        Some(&self.metadata)
+    }
+}
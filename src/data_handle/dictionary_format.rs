@@ -0,0 +1,330 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::data_handle::add_data_to_csv_file::write_entries_to_csv_with_schema;
+use crate::data_handle::dictionary_entry::DictionaryEntry;
+use crate::data_handle::dictionary_schema::DictionarySchema;
+use crate::data_handle::json_dictionary::{
+    read_entries_from_json, read_entries_from_json_lines, write_entries_to_json,
+    write_entries_to_json_lines,
+};
+use crate::data_handle::load_csv::stream_entries_from_reader_with_schema;
+
+/// The file format of a dictionary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DictionaryFormat {
+    /// CSV, or TSV for `.tsv` files, see `load_csv`
+    #[default]
+    Csv,
+    /// A JSON array of entries, see `json_dictionary`
+    Json,
+    /// JSON Lines, one entry per line, see `json_dictionary`
+    JsonLines,
+}
+
+impl DictionaryFormat {
+    /// The format of a file from its extension: `.json` for JSON, `.jsonl` or `.ndjson`
+    /// for JSON Lines, and CSV otherwise
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let extension: Option<String> = path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("json") => DictionaryFormat::Json,
+            Some("jsonl") | Some("ndjson") => DictionaryFormat::JsonLines,
+            _ => DictionaryFormat::Csv,
+        }
+    }
+}
+
+/// Loads dictionary entries from a file, in the format given by its extension.
+///
+/// Files with an unknown extension are read as CSV, see [`DictionaryFormat::from_path`]
+/// and [`load_dictionary_with_format`].
+///
+/// ## Arguments
+///
+/// * `path` - Path to the dictionary file
+///
+/// ## Returns
+///
+/// * `Ok(Vec<DictionaryEntry>)` - The entries of the dictionary, with their settings and metadata
+/// * `Err(String)` - Error message if the file cannot be read or is not a valid dictionary
+pub fn load_dictionary(path: String) -> Result<Vec<DictionaryEntry>, String> {
+    let format: DictionaryFormat = DictionaryFormat::from_path(&path);
+    load_dictionary_with_format(path, format)
+}
+
+/// Loads dictionary entries from a file in the given format, along with their optional
+/// settings and, for JSON, their metadata.
+///
+/// The format applies whatever the extension of the file: a CSV dictionary may be named
+/// `links.txt`. CSV files are comma-separated, unless their extension is `.tsv`.
+///
+/// ## Arguments
+///
+/// * `path` - Path to the dictionary file
+/// * `format` - The format of the file, whatever its extension
+///
+/// ## Returns
+///
+/// * `Ok(Vec<DictionaryEntry>)` - The valid entries, in order
+/// * `Err(String)` - Error message if the file cannot be read or is not a valid dictionary
+///
+/// # Examples
+///
+/// ```no_run
+/// use img_hash_linker::data_handle::dictionary_format::{
+///     DictionaryFormat, load_dictionary_with_format, save_dictionary,
+/// };
+///
+/// // A JSON Lines export without the usual extension
+/// let entries = load_dictionary_with_format("export.txt".to_string(), DictionaryFormat::JsonLines)?;
+/// save_dictionary(&entries, "links.json".to_string())?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn load_dictionary_with_format(
+    path: String,
+    format: DictionaryFormat,
+) -> Result<Vec<DictionaryEntry>, String> {
+    let path_ref: &Path = Path::new(&path);
+    if !path_ref.exists() {
+        return Err(format!("File does not exist: {}", path_ref.display()));
+    }
+
+    let file: File = File::open(path_ref).map_err(|e| format!("Failed to open file: {}", e))?;
+    match format {
+        DictionaryFormat::Csv => {
+            // Read the opened file, as `load_entries_from_csv` requires a `.csv` or `.tsv` extension
+            let entries: Vec<DictionaryEntry> =
+                stream_entries_from_reader_with_schema(file, &DictionarySchema::for_path(&path))?
+                    .collect::<Result<_, String>>()?;
+            if entries.is_empty() {
+                return Err("CSV file contains no valid links".to_string());
+            }
+            Ok(entries)
+        }
+        DictionaryFormat::Json => read_entries_from_json(file),
+        DictionaryFormat::JsonLines => read_entries_from_json_lines(file),
+    }
+}
+
+/// Writes dictionary entries to a file, in the format given by its extension.
+///
+/// See [`DictionaryFormat::from_path`] and [`save_dictionary_with_format`].
+///
+/// ## Arguments
+///
+/// * `entries` - The entries to write
+/// * `path` - Path to the dictionary file, created or replaced
+///
+/// ## Returns
+///
+/// * `Ok(())` - If the file was written
+/// * `Err(String)` - Error message if the file cannot be created or written
+pub fn save_dictionary(entries: &[DictionaryEntry], path: String) -> Result<(), String> {
+    let format: DictionaryFormat = DictionaryFormat::from_path(&path);
+    save_dictionary_with_format(entries, path, format)
+}
+
+/// Writes dictionary entries to a file in the given format.
+///
+/// JSON files keep the metadata of the entries, CSV files only their hash, link,
+/// threshold and thumbnail.
+///
+/// ## Arguments
+///
+/// * `entries` - The entries to write
+/// * `path` - Path to the dictionary file, created or replaced
+/// * `format` - The format of the file, whatever its extension
+///
+/// ## Returns
+///
+/// * `Ok(())` - If the file was written
+/// * `Err(String)` - Error message if the file cannot be created or written
+pub fn save_dictionary_with_format(
+    entries: &[DictionaryEntry],
+    path: String,
+    format: DictionaryFormat,
+) -> Result<(), String> {
+    let file: File =
+        File::create(&path).map_err(|e| format!("Failed to create file {}: {}", path, e))?;
+    let output: BufWriter<File> = BufWriter::new(file);
+
+    write_dictionary_with_format(entries, output, format, &DictionarySchema::for_path(&path))
+}
+
+/// Writes dictionary entries to any writer in the given format, e.g. to the standard output.
+///
+/// See [`save_dictionary_with_format`].
+///
+/// ## Arguments
+///
+/// * `entries` - The entries to write
+/// * `output` - Where to write the entries
+/// * `format` - The format to write
+/// * `schema` - The layout of CSV output, e.g. `DictionarySchema::for_path` of the file
+///   the entries come from (unused for JSON)
+///
+/// ## Returns
+///
+/// * `Ok(())` - If the entries were written
+/// * `Err(String)` - Error message if the output cannot be written
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::data_handle::dictionary_entry::DictionaryEntry;
+/// use img_hash_linker::data_handle::dictionary_format::{
+///     DictionaryFormat, write_dictionary_with_format,
+/// };
+/// use img_hash_linker::data_handle::dictionary_schema::DictionarySchema;
+///
+/// let entries = vec![DictionaryEntry::new(
+///     "ffff0000ffff0000".to_string(),
+///     "https://example.com".to_string(),
+/// )];
+///
+/// let mut output: Vec<u8> = Vec::new();
+/// write_dictionary_with_format(
+///     &entries,
+///     &mut output,
+///     DictionaryFormat::JsonLines,
+///     &DictionarySchema::default(),
+/// )?;
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "{\"hash\":\"ffff0000ffff0000\",\"link\":\"https://example.com\"}\n"
+/// );
+/// # Ok::<(), String>(())
+/// ```
+pub fn write_dictionary_with_format<W: Write>(
+    entries: &[DictionaryEntry],
+    output: W,
+    format: DictionaryFormat,
+    schema: &DictionarySchema,
+) -> Result<(), String> {
+    match format {
+        DictionaryFormat::Csv => write_entries_to_csv_with_schema(entries, output, schema),
+        DictionaryFormat::Json => write_entries_to_json(entries, output),
+        DictionaryFormat::JsonLines => write_entries_to_json_lines(entries, output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_path;
+    use serde_json::json;
+
+    fn entries() -> Vec<DictionaryEntry> {
+        let mut entries: Vec<DictionaryEntry> = vec![
+            DictionaryEntry::new(
+                "ffff0000ffff0000".to_string(),
+                "https://example.com".to_string(),
+            ),
+            DictionaryEntry::new("0f0".to_string(), "https://another.com".to_string()),
+        ];
+        entries[0].threshold = Some(0.9);
+        entries[1].thumbnail = Some("thumbs/another.png".to_string());
+        entries
+    }
+
+    #[test]
+    fn formats_are_given_by_the_extension() {
+        let formats: [(&str, DictionaryFormat); 9] = [
+            ("links.json", DictionaryFormat::Json),
+            ("dir/LINKS.JSON", DictionaryFormat::Json),
+            ("links.jsonl", DictionaryFormat::JsonLines),
+            ("links.ndjson", DictionaryFormat::JsonLines),
+            ("links.csv", DictionaryFormat::Csv),
+            ("links.tsv", DictionaryFormat::Csv),
+            ("links.txt", DictionaryFormat::Csv),
+            ("links", DictionaryFormat::Csv),
+            ("links.json.bak", DictionaryFormat::Csv),
+        ];
+        for (path, format) in formats {
+            assert_eq!(DictionaryFormat::from_path(path), format, "{}", path);
+        }
+    }
+
+    #[test]
+    fn dictionaries_round_trip_in_every_format() {
+        for name in ["links.csv", "links.tsv", "links.json", "links.jsonl"] {
+            let path: String = temp_path(name).display().to_string();
+            save_dictionary(&entries(), path.clone()).unwrap();
+            let contents: String = std::fs::read_to_string(&path).unwrap();
+            let loaded: Result<Vec<DictionaryEntry>, String> = load_dictionary(path.clone());
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(loaded, Ok(entries()), "{}", name);
+            assert_eq!(contents.contains('\t'), name.ends_with(".tsv"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn metadata_is_only_kept_in_json() {
+        let mut with_metadata: Vec<DictionaryEntry> = entries();
+        with_metadata[0]
+            .metadata
+            .insert("title".to_string(), json!("Example"));
+
+        for (name, kept) in [
+            ("meta.csv", false),
+            ("meta.json", true),
+            ("meta.ndjson", true),
+        ] {
+            let path: String = temp_path(name).display().to_string();
+            save_dictionary(&with_metadata, path.clone()).unwrap();
+            let loaded: Vec<DictionaryEntry> = load_dictionary(path.clone()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(loaded[0].metadata.contains_key("title"), kept, "{}", name);
+        }
+    }
+
+    #[test]
+    fn explicit_formats_ignore_the_extension() {
+        let path: String = temp_path("export.txt").display().to_string();
+        for format in [
+            DictionaryFormat::Csv,
+            DictionaryFormat::Json,
+            DictionaryFormat::JsonLines,
+        ] {
+            save_dictionary_with_format(&entries(), path.clone(), format).unwrap();
+            assert_eq!(
+                load_dictionary_with_format(path.clone(), format),
+                Ok(entries()),
+                "{:?}",
+                format
+            );
+        }
+
+        // Unknown extensions are read as CSV
+        save_dictionary_with_format(&entries(), path.clone(), DictionaryFormat::Csv).unwrap();
+        let loaded: Result<Vec<DictionaryEntry>, String> = load_dictionary(path.clone());
+        let as_json: Result<Vec<DictionaryEntry>, String> =
+            load_dictionary_with_format(path.clone(), DictionaryFormat::Json);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, Ok(entries()));
+        assert!(as_json.is_err());
+    }
+
+    #[test]
+    fn missing_and_empty_files_are_refused() {
+        let missing: String = temp_path("missing.csv").display().to_string();
+        assert!(load_dictionary(missing).is_err());
+
+        let path: String = temp_path("empty.txt").display().to_string();
+        std::fs::write(&path, "hash,link\n,https://example.com\n").unwrap();
+        let loaded: Result<Vec<DictionaryEntry>, String> =
+            load_dictionary_with_format(path.clone(), DictionaryFormat::Csv);
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use std::path::Path;
+
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+
+/// When the fields of a dictionary file are quoted on writing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quoting {
+    /// Only the fields containing the delimiter, the quote or a line break
+    #[default]
+    Necessary,
+    /// Every field
+    Always,
+    /// Every field that is not a number
+    NonNumeric,
+    /// No field, for formats without quoting (fields must not contain the delimiter)
+    Never,
+}
+
+/// The layout of a dictionary file, shared by the functions reading and writing it.
+///
+/// Columns are found by name, regardless of case and surrounding spaces, and each
+/// column may have several names: the first one is used when creating a file, and
+/// any of them is recognized when reading one.
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::data_handle::dictionary_schema::DictionarySchema;
+/// use img_hash_linker::data_handle::load_csv::stream_entries_from_reader_with_schema;
+///
+/// // A tab-separated export with its own column names
+/// let schema = DictionarySchema {
+///     hash_columns: vec!["hash".to_string(), "phash".to_string()],
+///     link_columns: vec!["link".to_string(), "url".to_string()],
+///     ..DictionarySchema::tsv()
+/// };
+///
+/// let tsv = "phash\tURL\nffff0000ffff0000\thttps://example.com\n";
+/// let entries: Vec<_> = stream_entries_from_reader_with_schema(tsv.as_bytes(), &schema)?
+///     .collect::<Result<_, _>>()?;
+/// assert_eq!(entries[0].link, "https://example.com");
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DictionarySchema {
+    /// Names of the hash column (default: `hash`)
+    pub hash_columns: Vec<String>,
+    /// Names of the link column (default: `link`)
+    pub link_columns: Vec<String>,
+    /// Names of the optional threshold column (default: `threshold`)
+    pub threshold_columns: Vec<String>,
+    /// Names of the optional thumbnail column (default: `thumbnail`)
+    pub thumbnail_columns: Vec<String>,
+    /// Field delimiter (default: `,`)
+    pub delimiter: u8,
+    /// Quote character (default: `"`)
+    pub quote: u8,
+    /// When fields are quoted on writing (default: only when necessary)
+    pub quoting: Quoting,
+}
+
+impl Default for DictionarySchema {
+    fn default() -> Self {
+        DictionarySchema {
+            hash_columns: vec!["hash".to_string()],
+            link_columns: vec!["link".to_string()],
+            threshold_columns: vec!["threshold".to_string()],
+            thumbnail_columns: vec!["thumbnail".to_string()],
+            delimiter: b',',
+            quote: b'"',
+            quoting: Quoting::default(),
+        }
+    }
+}
+
+impl DictionarySchema {
+    /// The default schema, with tab-separated fields
+    pub fn tsv() -> Self {
+        DictionarySchema {
+            delimiter: b'\t',
+            ..Default::default()
+        }
+    }
+
+    /// The default schema for a file, tab-separated for `.tsv` files and comma-separated otherwise
+    ///
+    /// This is the schema used by the functions taking a path without a schema.
+    pub fn for_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("tsv") => DictionarySchema::tsv(),
+            _ => DictionarySchema::default(),
+        }
+    }
+
+    /// Finds the known columns in the headers of a dictionary file
+    pub(crate) fn columns(&self, headers: &StringRecord) -> Result<DictionaryColumns, String> {
+        let required = |names: &[String]| {
+            find_column(headers, names).ok_or(format!(
+                "CSV must have a column named '{}'",
+                names.join("' or '")
+            ))
+        };
+
+        Ok(DictionaryColumns {
+            hash: required(&self.hash_columns)?,
+            link: required(&self.link_columns)?,
+            threshold: find_column(headers, &self.threshold_columns),
+            thumbnail: find_column(headers, &self.thumbnail_columns),
+        })
+    }
+
+    /// Headers of a new dictionary file
+    pub(crate) fn headers(&self, with_threshold: bool, with_thumbnail: bool) -> Vec<&str> {
+        let mut headers: Vec<&str> = vec![
+            primary_name(&self.hash_columns, "hash"),
+            primary_name(&self.link_columns, "link"),
+        ];
+        if with_threshold {
+            headers.push(primary_name(&self.threshold_columns, "threshold"));
+        }
+        if with_thumbnail {
+            headers.push(primary_name(&self.thumbnail_columns, "thumbnail"));
+        }
+        headers
+    }
+
+    /// A CSV reader builder for this schema, with headers
+    pub(crate) fn reader_builder(&self) -> ReaderBuilder {
+        let mut builder: ReaderBuilder = ReaderBuilder::new();
+        builder.delimiter(self.delimiter).quote(self.quote);
+        builder
+    }
+
+    /// A CSV writer builder for this schema
+    pub(crate) fn writer_builder(&self) -> WriterBuilder {
+        let mut builder: WriterBuilder = WriterBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .quote_style(match self.quoting {
+                Quoting::Necessary => csv::QuoteStyle::Necessary,
+                Quoting::Always => csv::QuoteStyle::Always,
+                Quoting::NonNumeric => csv::QuoteStyle::NonNumeric,
+                Quoting::Never => csv::QuoteStyle::Never,
+            });
+        builder
+    }
+}
+
+/// Positions of the known columns of a dictionary file
+pub(crate) struct DictionaryColumns {
+    pub hash: usize,
+    pub link: usize,
+    pub threshold: Option<usize>,
+    pub thumbnail: Option<usize>,
+}
+
+/// Position of the first column with one of the names, trying the names in order
+fn find_column(headers: &StringRecord, names: &[String]) -> Option<usize> {
+    names.iter().find_map(|name| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name.trim()))
+    })
+}
+
+fn primary_name<'a>(names: &'a [String], default: &'a str) -> &'a str {
+    names.first().map_or(default, String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_handle::add_data_to_csv_file::write_entries_to_csv_with_schema;
+    use crate::data_handle::dictionary_entry::DictionaryEntry;
+    use crate::data_handle::load_csv::stream_entries_from_reader_with_schema;
+
+    fn entries() -> Vec<DictionaryEntry> {
+        let mut entries: Vec<DictionaryEntry> = vec![
+            DictionaryEntry::new(
+                "ffff0000ffff0000".to_string(),
+                "https://example.com/a,b".to_string(),
+            ),
+            DictionaryEntry::new("0123".to_string(), "https://another.com".to_string()),
+        ];
+        entries[0].threshold = Some(0.9);
+        entries[1].thumbnail = Some("thumbs/another.png".to_string());
+        entries
+    }
+
+    fn write(entries: &[DictionaryEntry], schema: &DictionarySchema) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        write_entries_to_csv_with_schema(entries, &mut output, schema).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn read(contents: &str, schema: &DictionarySchema) -> Result<Vec<DictionaryEntry>, String> {
+        stream_entries_from_reader_with_schema(contents.as_bytes(), schema)?.collect()
+    }
+
+    fn aliased() -> DictionarySchema {
+        DictionarySchema {
+            hash_columns: vec!["phash".to_string(), "hash".to_string()],
+            link_columns: vec!["url".to_string(), "link".to_string()],
+            threshold_columns: vec!["min_proximity".to_string()],
+            thumbnail_columns: vec!["preview".to_string(), "thumbnail".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn aliases_are_written_with_their_first_name_and_read_with_any() {
+        let schema: DictionarySchema = aliased();
+        let written: String = write(&entries(), &schema);
+        assert!(written.starts_with("phash,url,min_proximity,preview\n"));
+        assert_eq!(read(&written, &schema), Ok(entries()));
+
+        let other_names: &str =
+            " HASH ,Link,thumbnail\n0123,https://another.com,thumbs/another.png\n";
+        let loaded: Vec<DictionaryEntry> = read(other_names, &schema).unwrap();
+        assert_eq!(loaded, entries()[1..]);
+    }
+
+    #[test]
+    fn ambiguous_aliases_use_the_first_name() {
+        let contents: &str = "hash,link,phash\n0000,https://example.com,ffff\n";
+        let loaded: Vec<DictionaryEntry> = read(contents, &aliased()).unwrap();
+        assert_eq!(loaded[0].hash, "ffff");
+
+        let loaded: Vec<DictionaryEntry> = read(contents, &DictionarySchema::default()).unwrap();
+        assert_eq!(loaded[0].hash, "0000");
+    }
+
+    #[test]
+    fn missing_required_columns_list_their_names() {
+        let error: String = read("phash,address\nffff,https://example.com\n", &aliased())
+            .map(|_| ())
+            .unwrap_err();
+        assert!(error.contains("'url' or 'link'"), "{}", error);
+
+        assert!(read("link\nhttps://example.com\n", &DictionarySchema::default()).is_err());
+    }
+
+    #[test]
+    fn other_delimiters_and_quotes_round_trip() {
+        let schema: DictionarySchema = DictionarySchema {
+            delimiter: b';',
+            quote: b'\'',
+            ..Default::default()
+        };
+        let mut quoted: Vec<DictionaryEntry> = entries();
+        quoted[0].link = "https://example.com/a;'b'".to_string();
+
+        let written: String = write(&quoted, &schema);
+        assert!(written.starts_with("hash;link;threshold;thumbnail\n"));
+        assert!(
+            written.contains("'https://example.com/a;''b'''"),
+            "{}",
+            written
+        );
+        assert_eq!(read(&written, &schema), Ok(quoted.clone()));
+        assert_ne!(read(&written, &DictionarySchema::default()), Ok(quoted));
+    }
+
+    #[test]
+    fn quoting_modes() {
+        let entry: [DictionaryEntry; 1] = [DictionaryEntry::new(
+            "0123".to_string(),
+            "https://example.com/a,b".to_string(),
+        )];
+        let written = |quoting: Quoting| {
+            write(
+                &entry,
+                &DictionarySchema {
+                    quoting,
+                    ..Default::default()
+                },
+            )
+        };
+
+        assert_eq!(
+            written(Quoting::Necessary),
+            "hash,link\n0123,\"https://example.com/a,b\"\n"
+        );
+        assert_eq!(
+            written(Quoting::Always),
+            "\"hash\",\"link\"\n\"0123\",\"https://example.com/a,b\"\n"
+        );
+        assert_eq!(
+            written(Quoting::NonNumeric),
+            "\"hash\",\"link\"\n0123,\"https://example.com/a,b\"\n"
+        );
+        assert_eq!(
+            written(Quoting::Never),
+            "hash,link\n0123,https://example.com/a,b\n"
+        );
+
+        for quoting in [Quoting::Necessary, Quoting::Always, Quoting::NonNumeric] {
+            let schema: DictionarySchema = DictionarySchema {
+                quoting,
+                ..Default::default()
+            };
+            assert_eq!(read(&written(quoting), &schema), Ok(entry.to_vec()));
+        }
+    }
+
+    #[test]
+    fn tsv_files_are_detected_by_their_extension() {
+        assert_eq!(
+            DictionarySchema::for_path("links.tsv"),
+            DictionarySchema::tsv()
+        );
+        assert_eq!(DictionarySchema::for_path("dir/LINKS.TSV").delimiter, b'\t');
+        for path in ["links.csv", "links.txt", "links", "tsv", "links.tsv.bak"] {
+            assert_eq!(
+                DictionarySchema::for_path(path),
+                DictionarySchema::default(),
+                "{}",
+                path
+            );
+        }
+    }
+}
@@ -0,0 +1,578 @@
+use std::fs::{self, File, Permissions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use csv::{Reader, StringRecord, Writer};
+
+use crate::algorithm::packed_hash::PackedHash;
+use crate::data_handle::dictionary_entry::DictionaryEntry;
+use crate::data_handle::dictionary_schema::{DictionaryColumns, DictionarySchema};
+use crate::data_handle::load_csv::{is_valid_entry, is_valid_threshold};
+
+/// The entries an edit applies to.
+pub enum EntrySelector<'a> {
+    /// The entries with this hash, regardless of its case
+    Hash(&'a str),
+    /// The entries with this link
+    Link(&'a str),
+    /// The entries for which the predicate returns `true`
+    Predicate(&'a dyn Fn(&DictionaryEntry) -> bool),
+}
+
+impl EntrySelector<'_> {
+    /// Whether the selector applies to the entry
+    pub fn matches(&self, entry: &DictionaryEntry) -> bool {
+        match self {
+            EntrySelector::Hash(hash) => entry.hash.eq_ignore_ascii_case(hash),
+            EntrySelector::Link(link) => entry.link == *link,
+            EntrySelector::Predicate(predicate) => predicate(entry),
+        }
+    }
+}
+
+/// Updates the entries of a CSV file selected by hash, by link or by predicate.
+///
+/// The `update` function changes the hash, link, threshold or thumbnail of each selected
+/// entry. Only the changed fields are written back: the other columns, including columns
+/// unknown to this crate, keep their values and their order, and the other rows are
+/// copied byte for byte. The file is rewritten safely, through a temporary file that
+/// replaces it once complete and keeps its permissions, and is left untouched if no
+/// entry is selected or if any update is invalid.
+///
+/// A changed hash must be a hex hash of the same length as the one it replaces, a changed
+/// link must be a URL and a changed threshold must be a proximity from 0.0 to 1.0, as
+/// entries breaking these rules would be skipped or refused when loading the dictionary.
+///
+/// # Arguments
+///
+/// * `dict_path` - Path to the CSV file
+/// * `selector` - The entries to update
+/// * `update` - The change to apply to each selected entry
+///
+/// # Returns
+///
+/// * `Ok(usize)` - The number of updated entries
+/// * `Err(String)` - Error message if the file cannot be read or written, if an update is
+///   invalid, or if the update sets a threshold or thumbnail and the file has no such column
+///
+/// # Example
+///
+/// ```no_run
+/// use img_hash_linker::data_handle::edit_csv_file::{EntrySelector, update_entry};
+///
+/// let updated = update_entry(
+///     "links.csv".to_string(),
+///     &EntrySelector::Link("https://old.example.com"),
+///     |entry| entry.link = "https://new.example.com".to_string(),
+/// )?;
+/// println!("{} entries updated", updated);
+/// # Ok::<(), String>(())
+/// ```
+pub fn update_entry(
+    dict_path: String,
+    selector: &EntrySelector,
+    update: impl Fn(&mut DictionaryEntry),
+) -> Result<usize, String> {
+    let schema: DictionarySchema = DictionarySchema::for_path(&dict_path);
+    update_entry_with_schema(dict_path, selector, update, &schema)
+}
+
+/// Updates the entries of a dictionary file in the given layout, see [`update_entry`]
+pub fn update_entry_with_schema(
+    dict_path: String,
+    selector: &EntrySelector,
+    update: impl Fn(&mut DictionaryEntry),
+    schema: &DictionarySchema,
+) -> Result<usize, String> {
+    rewrite_csv_file(&dict_path, schema, |columns, record| {
+        let entry: DictionaryEntry = record_entry(columns, record);
+        if !selector.matches(&entry) {
+            return Ok(RecordEdit::Keep);
+        }
+
+        let mut updated: DictionaryEntry = entry.clone();
+        update(&mut updated);
+        check_update(&entry, &updated)?;
+
+        let mut fields: Vec<String> = record.iter().map(str::to_string).collect();
+        if updated.hash != entry.hash {
+            fields[columns.hash] = updated.hash;
+        }
+        if updated.link != entry.link {
+            fields[columns.link] = updated.link;
+        }
+        if updated.threshold != entry.threshold {
+            let index: usize = columns
+                .threshold
+                .ok_or("CSV file has no 'threshold' column to update")?;
+            fields[index] = updated.threshold.map_or(String::new(), |t| t.to_string());
+        }
+        if updated.thumbnail != entry.thumbnail {
+            let index: usize = columns
+                .thumbnail
+                .ok_or("CSV file has no 'thumbnail' column to update")?;
+            fields[index] = updated.thumbnail.unwrap_or_default();
+        }
+
+        Ok(RecordEdit::Replace(StringRecord::from(fields)))
+    })
+}
+
+/// Removes the entries of a CSV file selected by hash, by link or by predicate.
+///
+/// The other rows are kept as they are, byte for byte. The file is rewritten safely,
+/// through a temporary file that replaces it once complete and keeps its permissions,
+/// and is left untouched if no entry is selected.
+///
+/// # Arguments
+///
+/// * `dict_path` - Path to the CSV file
+/// * `selector` - The entries to remove
+///
+/// # Returns
+///
+/// * `Ok(usize)` - The number of removed entries
+/// * `Err(String)` - Error message if the file cannot be read or written
+///
+/// # Example
+///
+/// ```no_run
+/// use img_hash_linker::data_handle::edit_csv_file::{EntrySelector, remove_entry};
+///
+/// // Remove the entries of retired images
+/// let is_retired = |entry: &img_hash_linker::data_handle::dictionary_entry::DictionaryEntry| {
+///     entry.link.starts_with("https://example.com/retired/")
+/// };
+/// let removed = remove_entry("links.csv".to_string(), &EntrySelector::Predicate(&is_retired))?;
+/// println!("{} entries removed", removed);
+/// # Ok::<(), String>(())
+/// ```
+pub fn remove_entry(dict_path: String, selector: &EntrySelector) -> Result<usize, String> {
+    let schema: DictionarySchema = DictionarySchema::for_path(&dict_path);
+    remove_entry_with_schema(dict_path, selector, &schema)
+}
+
+/// Removes the entries of a dictionary file in the given layout, see [`remove_entry`]
+pub fn remove_entry_with_schema(
+    dict_path: String,
+    selector: &EntrySelector,
+    schema: &DictionarySchema,
+) -> Result<usize, String> {
+    rewrite_csv_file(&dict_path, schema, |columns, record| {
+        if selector.matches(&record_entry(columns, record)) {
+            Ok(RecordEdit::Remove)
+        } else {
+            Ok(RecordEdit::Keep)
+        }
+    })
+}
+
+/// Checks that the changed fields of an updated entry are valid
+fn check_update(entry: &DictionaryEntry, updated: &DictionaryEntry) -> Result<(), String> {
+    if (updated.hash != entry.hash || updated.link != entry.link)
+        && !is_valid_entry(&updated.hash, &updated.link)
+    {
+        return Err(format!(
+            "Invalid update of {}: the hash must not be empty and the link must be a URL, got {} for {}",
+            entry.hash, updated.hash, updated.link
+        ));
+    }
+
+    if updated.hash != entry.hash {
+        PackedHash::parse(&updated.hash)
+            .map_err(|e| format!("Invalid hash {} for {}: {}", updated.hash, updated.link, e))?;
+        if updated.hash.len() != entry.hash.len() {
+            return Err(format!(
+                "Hash length mismatch: {} cannot replace {}",
+                updated.hash, entry.hash
+            ));
+        }
+    }
+
+    if updated.threshold != entry.threshold
+        && let Some(threshold) = updated.threshold
+        && !is_valid_threshold(threshold)
+    {
+        return Err(format!(
+            "Invalid threshold for {} (expected 0.0 to 1.0): {}",
+            updated.hash, threshold
+        ));
+    }
+
+    Ok(())
+}
+
+/// The entry of a record, as the selectors and updates see it
+fn record_entry(columns: &DictionaryColumns, record: &StringRecord) -> DictionaryEntry {
+    let field = |index: usize| record.get(index).map(str::trim).unwrap_or_default();
+
+    DictionaryEntry {
+        hash: field(columns.hash).to_string(),
+        link: field(columns.link).to_string(),
+        threshold: columns
+            .threshold
+            .and_then(|index| field(index).parse().ok()),
+        thumbnail: columns
+            .thumbnail
+            .map(field)
+            .filter(|thumbnail| !thumbnail.is_empty())
+            .map(str::to_string),
+        ..Default::default()
+    }
+}
+
+/// What to do with a record when rewriting a CSV file
+enum RecordEdit {
+    Keep,
+    Replace(StringRecord),
+    Remove,
+}
+
+/// Rewrites a CSV file record by record, through a temporary file
+///
+/// The header and the kept records are copied byte for byte from the original file,
+/// so only the replaced records are re-serialized, and the temporary file gets the
+/// permissions of the original before replacing it.
+///
+/// Returns the number of removed or changed records.
+fn rewrite_csv_file(
+    dict_path: &str,
+    schema: &DictionarySchema,
+    mut edit: impl FnMut(&DictionaryColumns, &StringRecord) -> Result<RecordEdit, String>,
+) -> Result<usize, String> {
+    let path: &Path = Path::new(dict_path);
+    let file: File = File::open(path).map_err(|e| format!("Failed to open CSV file: {}", e))?;
+    let permissions: Permissions = file
+        .metadata()
+        .map_err(|e| format!("Failed to read CSV file metadata: {}", e))?
+        .permissions();
+    let mut reader: Reader<File> = schema.reader_builder().from_reader(file);
+
+    // A second handle on the file, to copy the bytes of the kept records
+    let mut original: BufReader<File> =
+        BufReader::new(File::open(path).map_err(|e| format!("Failed to open CSV file: {}", e))?);
+
+    let headers: StringRecord = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV headers: {}", e))?
+        .clone();
+    let columns: DictionaryColumns = schema.columns(&headers)?;
+
+    // Write next to the file, so that renaming it over the file is atomic
+    let temp_path: PathBuf = temporary_path(path);
+    let result: Result<usize, String> = (|| {
+        let mut output: BufWriter<File> = BufWriter::new(
+            File::create(&temp_path)
+                .map_err(|e| format!("Failed to create temporary file: {}", e))?,
+        );
+
+        // Copies, or skips, the original bytes up to `end`, where the next record starts.
+        // The reader may place that start within the line terminator of the record, so the
+        // line terminators and blank lines found there still belong to the record.
+        let mut copied: u64 = 0;
+        let mut transfer = |end: Option<u64>, keep: bool, output: &mut BufWriter<File>| {
+            let error = |e: io::Error| format!("Failed to copy CSV file: {}", e);
+
+            let mut bytes =
+                (&mut original).take(end.map_or(u64::MAX, |end| end.saturating_sub(copied)));
+            copied += if keep {
+                io::copy(&mut bytes, output)
+            } else {
+                io::copy(&mut bytes, &mut io::sink())
+            }
+            .map_err(error)?;
+
+            loop {
+                let buffer: &[u8] = original.fill_buf().map_err(error)?;
+                let length: usize = buffer
+                    .iter()
+                    .take_while(|&&byte| byte == b'\r' || byte == b'\n')
+                    .count();
+                if length == 0 {
+                    return Ok::<(), String>(());
+                }
+                if keep {
+                    output.write_all(&buffer[..length]).map_err(error)?;
+                }
+                original.consume(length);
+                copied += length as u64;
+            }
+        };
+
+        // The bytes of a record end where the next one starts, the header being kept as is
+        let mut edited: usize = 0;
+        let mut previous: RecordEdit = RecordEdit::Keep;
+        for (row_idx, result) in reader.records().enumerate() {
+            let record: StringRecord =
+                result.map_err(|e| format!("Failed to read row {}: {}", row_idx + 1, e))?;
+            let start: Option<u64> = record.position().map(|position| position.byte());
+
+            let current: RecordEdit = match edit(&columns, &record)? {
+                RecordEdit::Replace(replacement) if replacement == record => RecordEdit::Keep,
+                RecordEdit::Keep => RecordEdit::Keep,
+                current => {
+                    edited += 1;
+                    current
+                }
+            };
+
+            write_record(previous, start, &mut transfer, &mut output, schema)?;
+            previous = current;
+        }
+        write_record(previous, None, &mut transfer, &mut output, schema)?;
+
+        output
+            .into_inner()
+            .map_err(|e| format!("Failed to write temporary file: {}", e))?
+            .sync_all()
+            .map_err(|e| format!("Failed to write temporary file: {}", e))?;
+
+        Ok(edited)
+    })();
+
+    match result {
+        Ok(0) => {
+            let _ = fs::remove_file(&temp_path);
+            Ok(0)
+        }
+        Ok(edited) => {
+            fs::set_permissions(&temp_path, permissions)
+                .and_then(|_| fs::rename(&temp_path, path))
+                .map_err(|e| {
+                    let _ = fs::remove_file(&temp_path);
+                    format!("Failed to replace CSV file: {}", e)
+                })?;
+            Ok(edited)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Writes a record of the rewritten file, whose original bytes end at `end`
+fn write_record(
+    edit: RecordEdit,
+    end: Option<u64>,
+    transfer: &mut impl FnMut(Option<u64>, bool, &mut BufWriter<File>) -> Result<(), String>,
+    output: &mut BufWriter<File>,
+    schema: &DictionarySchema,
+) -> Result<(), String> {
+    match edit {
+        RecordEdit::Keep => transfer(end, true, output),
+        RecordEdit::Remove => transfer(end, false, output),
+        RecordEdit::Replace(replacement) => {
+            transfer(end, false, output)?;
+            let mut writer: Writer<&mut BufWriter<File>> =
+                schema.writer_builder().from_writer(output);
+            writer
+                .write_record(&replacement)
+                .map_err(|e| format!("Failed to write record: {}", e))?;
+            writer
+                .flush()
+                .map_err(|e| format!("Failed to write record: {}", e))
+        }
+    }
+}
+
+/// A hidden file next to the given one, e.g. `.links.csv.tmp` for `links.csv`
+fn temporary_path(path: &Path) -> PathBuf {
+    let name: String = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{temp_csv, temp_path};
+
+    const CSV: &str = "hash,link,notes\nffff0000ffff0000,https://a.com,kept\n";
+
+    type Update = fn(&mut DictionaryEntry);
+
+    #[test]
+    fn invalid_updates_leave_the_file_untouched() {
+        let updates: [(&str, Update); 5] = [
+            ("not_hex", |entry| {
+                entry.hash = "zzzz0000ffff0000".to_string()
+            }),
+            ("other_length", |entry| entry.hash = "f0f".to_string()),
+            ("empty_hash", |entry| entry.hash.clear()),
+            ("not_url", |entry| entry.link = "not a link".to_string()),
+            ("threshold", |entry| entry.threshold = Some(1.5)),
+        ];
+
+        for (name, update) in updates {
+            let path: String = temp_csv(name, CSV);
+            let result: Result<usize, String> = update_entry(
+                path.clone(),
+                &EntrySelector::Hash("FFFF0000FFFF0000"),
+                update,
+            );
+            let contents: String = fs::read_to_string(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            assert!(result.is_err(), "{} should be refused", name);
+            assert_eq!(contents, CSV);
+            assert!(!temporary_path(Path::new(&path)).exists());
+        }
+    }
+
+    #[test]
+    fn valid_updates_keep_the_other_columns() {
+        let path: String = temp_csv("valid", CSV);
+        let updated: Result<usize, String> = update_entry(
+            path.clone(),
+            &EntrySelector::Link("https://a.com"),
+            |entry| {
+                entry.hash = "0000ffff0000ffff".to_string();
+                entry.link = "https://b.com".to_string();
+            },
+        );
+        let contents: String = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(updated, Ok(1));
+        assert_eq!(
+            contents,
+            "hash,link,notes\n0000ffff0000ffff,https://b.com,kept\n"
+        );
+    }
+
+    #[test]
+    fn unselected_files_are_left_untouched() {
+        let path: String = temp_csv("no_match", CSV);
+        let updated: Result<usize, String> = update_entry(
+            path.clone(),
+            &EntrySelector::Link("https://missing.com"),
+            |entry| entry.link = "https://b.com".to_string(),
+        );
+        let removed: Result<usize, String> =
+            remove_entry(path.clone(), &EntrySelector::Hash("0000ffff0000ffff"));
+        let contents: String = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(updated, Ok(0));
+        assert_eq!(removed, Ok(0));
+        assert_eq!(contents, CSV);
+        assert!(!temporary_path(Path::new(&path)).exists());
+    }
+
+    #[test]
+    fn unchanged_updates_are_not_counted() {
+        let path: String = temp_csv("unchanged", CSV);
+        let updated: Result<usize, String> =
+            update_entry(path.clone(), &EntrySelector::Link("https://a.com"), |_| {});
+        let contents: String = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(updated, Ok(0));
+        assert_eq!(contents, CSV);
+    }
+
+    #[test]
+    fn removal_keeps_the_other_rows() {
+        let csv: &str = "hash,link,notes\n\
+                         ffff0000ffff0000,https://a.com,first\n\
+                         ,,not an entry\n\
+                         0000ffff0000ffff,https://b.com,second\n\
+                         f0f0f0f0f0f0f0f0,https://a.com,third\n";
+        let path: String = temp_csv("remove", csv);
+        let removed: Result<usize, String> =
+            remove_entry(path.clone(), &EntrySelector::Link("https://a.com"));
+        let contents: String = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(removed, Ok(2));
+        assert_eq!(
+            contents,
+            "hash,link,notes\n,,not an entry\n0000ffff0000ffff,https://b.com,second\n"
+        );
+        assert!(!temporary_path(Path::new(&path)).exists());
+    }
+
+    #[test]
+    fn missing_columns_are_reported() {
+        let path: String = temp_csv("no_threshold", CSV);
+        let updated: Result<usize, String> = update_entry(
+            path.clone(),
+            &EntrySelector::Link("https://a.com"),
+            |entry| entry.threshold = Some(0.9),
+        );
+        let contents: String = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(updated.is_err());
+        assert_eq!(contents, CSV);
+        assert!(!temporary_path(Path::new(&path)).exists());
+
+        let missing: String = temp_path("missing.csv").display().to_string();
+        assert!(remove_entry(missing, &EntrySelector::Link("https://a.com")).is_err());
+    }
+
+    #[test]
+    fn temporary_files_are_hidden_next_to_the_file() {
+        assert_eq!(
+            temporary_path(Path::new("data/links.csv")),
+            PathBuf::from("data/.links.csv.tmp")
+        );
+    }
+
+    #[test]
+    fn other_rows_are_copied_byte_for_byte() {
+        let csv: &str = "hash,link,notes\r\n\
+                         \"ffff0000ffff0000\",https://a.com,\"quoted\"\r\n\
+                         \r\n\
+                         0000ffff0000ffff,https://b.com,\"x, y\"";
+
+        let path: String = temp_csv("raw_update", csv);
+        let updated: Result<usize, String> = update_entry(
+            path.clone(),
+            &EntrySelector::Link("https://b.com"),
+            |entry| entry.link = "https://c.com".to_string(),
+        );
+        let contents: String = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(updated, Ok(1));
+        assert_eq!(
+            contents,
+            "hash,link,notes\r\n\
+             \"ffff0000ffff0000\",https://a.com,\"quoted\"\r\n\
+             \r\n\
+             0000ffff0000ffff,https://c.com,\"x, y\"\n"
+        );
+
+        let path: String = temp_csv("raw_remove", csv);
+        let removed: Result<usize, String> =
+            remove_entry(path.clone(), &EntrySelector::Link("https://a.com"));
+        let contents: String = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(removed, Ok(1));
+        assert_eq!(
+            contents,
+            "hash,link,notes\r\n0000ffff0000ffff,https://b.com,\"x, y\""
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permissions_are_kept() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path: String = temp_csv("permissions", CSV);
+        fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+        let removed: Result<usize, String> =
+            remove_entry(path.clone(), &EntrySelector::Link("https://a.com"));
+        let mode: u32 = fs::metadata(&path).unwrap().permissions().mode();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(removed, Ok(1));
+        assert_eq!(mode & 0o777, 0o640);
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use csv::{Reader, StringRecord};
+
+use crate::algorithm::match_confidence::ImpostorDistribution;
+
+/// Path of the impostor distribution file stored alongside a dictionary: `links.csv` has
+/// its distribution in `links.impostors.csv`.
+pub fn impostors_path(dict_path: &str) -> PathBuf {
+    Path::new(dict_path).with_extension("impostors.csv")
+}
+
+/// Writes an impostor distribution to a CSV file with `hex_len`, `distance` and `count`
+/// columns, one row per distance of each hash length.
+///
+/// ## Arguments
+///
+/// * `impostors` - The impostor distribution, as measured by `ImpostorDistribution::from_entries`
+/// * `path` - Path to the CSV file, see [`impostors_path`]
+///
+/// ## Returns
+///
+/// * `Ok(())` - The distribution was written
+/// * `Err(String)` - The file could not be created or written
+pub fn write_impostor_distribution(
+    impostors: &ImpostorDistribution,
+    path: String,
+) -> Result<(), String> {
+    let mut writer = csv::Writer::from_path(&path)
+        .map_err(|e| format!("Failed to create file {}: {}", path, e))?;
+
+    writer
+        .write_record(["hex_len", "distance", "count"])
+        .map_err(|e| format!("Failed to write headers: {}", e))?;
+
+    for hex_len in impostors.hex_lens() {
+        let counts: Vec<u64> = impostors.distance_counts(hex_len).unwrap_or_default();
+        for (distance, count) in counts.iter().enumerate() {
+            writer
+                .write_record([hex_len.to_string(), distance.to_string(), count.to_string()])
+                .map_err(|e| format!("Failed to write record: {}", e))?;
+        }
+    }
+
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to flush CSV writer: {}", e))
+}
+
+/// Loads an impostor distribution from a CSV file written by [`write_impostor_distribution`].
+///
+/// Distances missing from the file have no impostor pairs.
+///
+/// ## Arguments
+///
+/// * `path` - Path to the CSV file
+///
+/// ## Returns
+///
+/// * `Ok(ImpostorDistribution)` - The distribution, for every hash length in the file
+/// * `Err(String)` - The file could not be read, or has an invalid row
+pub fn load_impostor_distribution(path: String) -> Result<ImpostorDistribution, String> {
+    let file: File = File::open(&path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let mut reader: Reader<File> = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(file);
+
+    let headers: StringRecord = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV headers: {}", e))?
+        .clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .ok_or(format!("CSV must have a column named '{}'", name))
+    };
+    let (hex_len_idx, distance_idx, count_idx) =
+        (column("hex_len")?, column("distance")?, column("count")?);
+
+    // Counts per distance, for each hash length in order of appearance
+    let mut counts: Vec<(usize, Vec<u64>)> = Vec::new();
+    for (row_idx, result) in reader.records().enumerate() {
+        let record: StringRecord =
+            result.map_err(|e| format!("Failed to read row {}: {}", row_idx + 1, e))?;
+
+        let field = |index: usize, name: &str| {
+            record
+                .get(index)
+                .ok_or(format!(
+                    "Row {} has fewer columns than expected",
+                    row_idx + 1
+                ))?
+                .parse::<u64>()
+                .map_err(|e| format!("Row {} has an invalid {}: {}", row_idx + 1, name, e))
+        };
+
+        let hex_len: usize = field(hex_len_idx, "hash length")? as usize;
+        let distance: usize = field(distance_idx, "distance")? as usize;
+        let count: u64 = field(count_idx, "count")?;
+
+        let index: usize = match counts.iter().position(|(len, _)| *len == hex_len) {
+            Some(index) => index,
+            None => {
+                counts.push((hex_len, Vec::new()));
+                counts.len() - 1
+            }
+        };
+        let distance_counts: &mut Vec<u64> = &mut counts[index].1;
+        if distance >= distance_counts.len() {
+            distance_counts.resize(distance + 1, 0);
+        }
+        distance_counts[distance] = count;
+    }
+
+    ImpostorDistribution::from_distance_counts(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_path;
+
+    #[test]
+    fn distribution_round_trips() {
+        let links: Vec<(String, String)> = vec![
+            (
+                "ffffffff00000000".to_string(),
+                "https://example.com".to_string(),
+            ),
+            (
+                "00000000ffffffff".to_string(),
+                "https://another.com".to_string(),
+            ),
+            (
+                "ffff0000ffff0000".to_string(),
+                "https://third.com".to_string(),
+            ),
+            ("1ff".to_string(), "https://example.com".to_string()),
+            ("000".to_string(), "https://another.com".to_string()),
+        ];
+        let impostors: ImpostorDistribution =
+            ImpostorDistribution::from_entries(&links, None).unwrap();
+
+        let path: PathBuf = temp_path("impostors.csv");
+        write_impostor_distribution(&impostors, path.display().to_string()).unwrap();
+        let loaded: ImpostorDistribution =
+            load_impostor_distribution(path.display().to_string()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, impostors);
+        assert_eq!(loaded.pair_count(16), 3);
+        assert_eq!(loaded.pair_count(3), 1);
+    }
+
+    #[test]
+    fn missing_distances_have_no_pairs() {
+        let path: PathBuf = temp_path("sparse.impostors.csv");
+        std::fs::write(&path, "hex_len,distance,count\n16,40,2\n").unwrap();
+        let loaded: ImpostorDistribution =
+            load_impostor_distribution(path.display().to_string()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.pair_count(16), 2);
+        assert_eq!(loaded.false_match_probability(16, 39.0), Some(1.0 / 3.0));
+        assert_eq!(loaded.false_match_probability(16, 64.0), Some(1.0));
+    }
+
+    #[test]
+    fn distances_beyond_the_bit_count_are_rejected() {
+        let path: PathBuf = temp_path("invalid.impostors.csv");
+        std::fs::write(&path, "hex_len,distance,count\n3,13,1\n").unwrap();
+        let loaded: Result<ImpostorDistribution, String> =
+            load_impostor_distribution(path.display().to_string());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(loaded.is_err());
+    }
+}
@@ -0,0 +1,361 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use serde_json::{Map, Value};
+
+use crate::data_handle::dictionary_entry::DictionaryEntry;
+use crate::data_handle::load_csv::{is_valid_entry, is_valid_threshold};
+
+/// The fields read into the entry itself, the other ones being kept as metadata
+const ENTRY_FIELDS: [&str; 4] = ["hash", "link", "threshold", "thumbnail"];
+
+/// Reads dictionary entries from a JSON array of objects.
+///
+/// Each object has a `hash` and a `link`, and optionally a `threshold` and a `thumbnail`,
+/// with the same rules as the columns of a CSV dictionary: entries with an empty hash or
+/// without a proper URL are skipped, and thresholds must be from 0.0 to 1.0. The other
+/// fields are kept in the [`metadata`](DictionaryEntry::metadata) of the entry, so that
+/// writing the entries back preserves them.
+///
+/// ## Arguments
+///
+/// * `input` - The JSON document
+///
+/// ## Returns
+///
+/// * `Ok(Vec<DictionaryEntry>)` - The valid entries, in order
+/// * `Err(String)` - Error message if the document is not an array of objects, if a field
+///   has the wrong type or if no entry is valid
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::data_handle::json_dictionary::read_entries_from_json;
+///
+/// let json = r#"[
+///     {"hash": "ffff0000ffff0000", "link": "https://example.com", "title": "Example"},
+///     {"hash": "0000ffff0000ffff", "link": "https://another.com", "threshold": 0.95}
+/// ]"#;
+///
+/// let entries = read_entries_from_json(json.as_bytes())?;
+/// assert_eq!(entries[0].metadata["title"], "Example");
+/// assert_eq!(entries[1].threshold, Some(0.95));
+/// # Ok::<(), String>(())
+/// ```
+pub fn read_entries_from_json<R: Read>(input: R) -> Result<Vec<DictionaryEntry>, String> {
+    let document: Value =
+        serde_json::from_reader(input).map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+    let Value::Array(values) = document else {
+        return Err("JSON dictionary must be an array of entries".to_string());
+    };
+
+    let mut entries: Vec<DictionaryEntry> = Vec::new();
+    for (entry_idx, value) in values.into_iter().enumerate() {
+        if let Some(entry) = parse_entry(value, &format!("Entry {}", entry_idx + 1))? {
+            entries.push(entry);
+        }
+    }
+
+    if entries.is_empty() {
+        return Err("JSON file contains no valid links".to_string());
+    }
+
+    Ok(entries)
+}
+
+/// Reads dictionary entries from JSON Lines, one object per line.
+///
+/// Blank lines are ignored. The objects follow the same rules as in
+/// [`read_entries_from_json`].
+///
+/// ## Arguments
+///
+/// * `input` - The JSON Lines document
+///
+/// ## Returns
+///
+/// * `Ok(Vec<DictionaryEntry>)` - The valid entries, in order
+/// * `Err(String)` - Error message if a line is not a JSON object, if a field has the
+///   wrong type or if no entry is valid
+///
+/// # Examples
+///
+/// ```
+/// use img_hash_linker::data_handle::json_dictionary::read_entries_from_json_lines;
+///
+/// let jsonl = "{\"hash\": \"ffff0000ffff0000\", \"link\": \"https://example.com\"}\n\
+///              {\"hash\": \"\", \"link\": \"https://skipped.com\"}\n";
+///
+/// let entries = read_entries_from_json_lines(jsonl.as_bytes())?;
+/// assert_eq!(entries.len(), 1);
+/// # Ok::<(), String>(())
+/// ```
+pub fn read_entries_from_json_lines<R: Read>(input: R) -> Result<Vec<DictionaryEntry>, String> {
+    let mut entries: Vec<DictionaryEntry> = Vec::new();
+
+    for (line_idx, line) in BufReader::new(input).lines().enumerate() {
+        let line: String =
+            line.map_err(|e| format!("Failed to read line {}: {}", line_idx + 1, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let value: Value = serde_json::from_str(&line)
+            .map_err(|e| format!("Failed to parse line {}: {}", line_idx + 1, e))?;
+        if let Some(entry) = parse_entry(value, &format!("Line {}", line_idx + 1))? {
+            entries.push(entry);
+        }
+    }
+
+    if entries.is_empty() {
+        return Err("JSON file contains no valid links".to_string());
+    }
+
+    Ok(entries)
+}
+
+/// Writes dictionary entries as a JSON array of objects, one per entry.
+///
+/// The `threshold` and `thumbnail` fields are only written for the entries that have
+/// them, followed by the metadata of the entry.
+///
+/// ## Arguments
+///
+/// * `entries` - The entries to write
+/// * `output` - Where to write them, e.g. a file
+///
+/// ## Returns
+///
+/// * `Ok(())` - If the entries were written
+/// * `Err(String)` - Error message if writing fails
+pub fn write_entries_to_json<W: Write>(
+    entries: &[DictionaryEntry],
+    mut output: W,
+) -> Result<(), String> {
+    let document: Value = Value::Array(entries.iter().map(entry_to_json).collect());
+
+    serde_json::to_writer_pretty(&mut output, &document)
+        .map_err(|e| format!("Failed to write JSON: {}", e))?;
+    writeln!(output).map_err(|e| format!("Failed to write JSON: {}", e))?;
+    output
+        .flush()
+        .map_err(|e| format!("Failed to flush JSON writer: {}", e))
+}
+
+/// Writes dictionary entries as JSON Lines, one object per line.
+///
+/// The objects are the same as with [`write_entries_to_json`].
+///
+/// ## Arguments
+///
+/// * `entries` - The entries to write
+/// * `output` - Where to write them, e.g. a file
+///
+/// ## Returns
+///
+/// * `Ok(())` - If the entries were written
+/// * `Err(String)` - Error message if writing fails
+pub fn write_entries_to_json_lines<W: Write>(
+    entries: &[DictionaryEntry],
+    mut output: W,
+) -> Result<(), String> {
+    for entry in entries {
+        serde_json::to_writer(&mut output, &entry_to_json(entry))
+            .map_err(|e| format!("Failed to write JSON: {}", e))?;
+        writeln!(output).map_err(|e| format!("Failed to write JSON: {}", e))?;
+    }
+
+    output
+        .flush()
+        .map_err(|e| format!("Failed to flush JSON writer: {}", e))
+}
+
+/// Parses a JSON object into an entry, or `None` if its hash or link is empty or not valid
+fn parse_entry(value: Value, position: &str) -> Result<Option<DictionaryEntry>, String> {
+    let Value::Object(fields) = value else {
+        return Err(format!("{} is not a JSON object", position));
+    };
+
+    let hash: String = string_field(&fields, "hash", position)?.unwrap_or_default();
+    let link: String = string_field(&fields, "link", position)?.unwrap_or_default();
+
+    if !is_valid_entry(&hash, &link) {
+        return Ok(None);
+    }
+
+    let threshold: Option<f64> = match fields.get("threshold") {
+        None | Some(Value::Null) => None,
+        Some(value) => match value.as_f64() {
+            Some(threshold) if is_valid_threshold(threshold) => Some(threshold),
+            _ => {
+                return Err(format!(
+                    "{} has an invalid threshold (expected 0.0 to 1.0): {}",
+                    position, value
+                ));
+            }
+        },
+    };
+
+    let thumbnail: Option<String> =
+        string_field(&fields, "thumbnail", position)?.filter(|thumbnail| !thumbnail.is_empty());
+
+    let metadata: Map<String, Value> = fields
+        .into_iter()
+        .filter(|(name, _)| !ENTRY_FIELDS.contains(&name.as_str()))
+        .collect();
+
+    Ok(Some(DictionaryEntry {
+        hash,
+        link,
+        threshold,
+        thumbnail,
+        metadata,
+    }))
+}
+
+/// The trimmed value of a string field, or `None` if the field is missing or null
+fn string_field(
+    fields: &Map<String, Value>,
+    name: &str,
+    position: &str,
+) -> Result<Option<String>, String> {
+    match fields.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.trim().to_string())),
+        Some(value) => Err(format!(
+            "{} has an invalid {} (expected a string): {}",
+            position, name, value
+        )),
+    }
+}
+
+fn entry_to_json(entry: &DictionaryEntry) -> Value {
+    let mut fields: Map<String, Value> = Map::new();
+    fields.insert("hash".to_string(), Value::from(entry.hash.as_str()));
+    fields.insert("link".to_string(), Value::from(entry.link.as_str()));
+    if let Some(threshold) = entry.threshold {
+        fields.insert("threshold".to_string(), Value::from(threshold));
+    }
+    if let Some(thumbnail) = &entry.thumbnail {
+        fields.insert("thumbnail".to_string(), Value::from(thumbnail.as_str()));
+    }
+
+    for (name, value) in &entry.metadata {
+        if !ENTRY_FIELDS.contains(&name.as_str()) {
+            fields.insert(name.clone(), value.clone());
+        }
+    }
+
+    Value::Object(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entries() -> Vec<DictionaryEntry> {
+        let mut metadata: Map<String, Value> = Map::new();
+        metadata.insert("title".to_string(), json!("Example"));
+        metadata.insert("tags".to_string(), json!(["art", 3, null]));
+
+        vec![
+            DictionaryEntry {
+                hash: "ffff0000ffff0000".to_string(),
+                link: "https://example.com".to_string(),
+                threshold: Some(0.95),
+                thumbnail: Some("thumbs/example.png".to_string()),
+                metadata,
+            },
+            DictionaryEntry {
+                hash: "0f0".to_string(),
+                link: "https://another.com".to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let mut json: Vec<u8> = Vec::new();
+        write_entries_to_json(&entries(), &mut json).unwrap();
+        assert_eq!(read_entries_from_json(json.as_slice()), Ok(entries()));
+
+        let mut json_lines: Vec<u8> = Vec::new();
+        write_entries_to_json_lines(&entries(), &mut json_lines).unwrap();
+        assert_eq!(String::from_utf8_lossy(&json_lines).lines().count(), 2);
+        assert_eq!(
+            read_entries_from_json_lines(json_lines.as_slice()),
+            Ok(entries())
+        );
+    }
+
+    #[test]
+    fn metadata_cannot_shadow_the_entry_fields() {
+        let mut entry: DictionaryEntry = entries().remove(1);
+        entry
+            .metadata
+            .insert("link".to_string(), json!("https://shadow.com"));
+
+        let mut json: Vec<u8> = Vec::new();
+        write_entries_to_json(&[entry], &mut json).unwrap();
+        let loaded: Vec<DictionaryEntry> = read_entries_from_json(json.as_slice()).unwrap();
+
+        assert_eq!(loaded[0].link, "https://another.com");
+        assert!(loaded[0].metadata.is_empty());
+    }
+
+    #[test]
+    fn empty_documents_have_no_entries() {
+        assert!(read_entries_from_json("[]".as_bytes()).is_err());
+        assert!(read_entries_from_json_lines("".as_bytes()).is_err());
+        assert!(read_entries_from_json_lines("\n  \n".as_bytes()).is_err());
+
+        // Writing no entries gives documents that are valid, but empty
+        let mut json: Vec<u8> = Vec::new();
+        write_entries_to_json(&[], &mut json).unwrap();
+        assert_eq!(String::from_utf8_lossy(&json), "[]\n");
+
+        let mut json_lines: Vec<u8> = Vec::new();
+        write_entries_to_json_lines(&[], &mut json_lines).unwrap();
+        assert!(json_lines.is_empty());
+    }
+
+    #[test]
+    fn invalid_documents_are_refused() {
+        let documents: [&str; 5] = [
+            r#"{"hash": "ffff", "link": "https://example.com"}"#,
+            r#"[["ffff", "https://example.com"]]"#,
+            r#"[{"hash": 15, "link": "https://example.com"}]"#,
+            r#"[{"hash": "ffff", "link": "https://example.com", "threshold": 2}]"#,
+            r#"[{"hash": "ffff", "link": "https://example.com", "threshold": "high"}]"#,
+        ];
+        for document in documents {
+            assert!(
+                read_entries_from_json(document.as_bytes()).is_err(),
+                "{}",
+                document
+            );
+        }
+
+        assert!(read_entries_from_json_lines("[]\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn fields_are_trimmed_and_empty_ones_dropped() {
+        let json: &str = r#"[
+            {"hash": " ffff ", "link": " https://example.com ", "thumbnail": "", "threshold": null},
+            {"hash": "ffff", "link": "not a link"}
+        ]"#;
+
+        let loaded: Vec<DictionaryEntry> = read_entries_from_json(json.as_bytes()).unwrap();
+        assert_eq!(
+            loaded,
+            vec![DictionaryEntry {
+                hash: "ffff".to_string(),
+                link: "https://example.com".to_string(),
+                ..Default::default()
+            }]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use csv::{Reader, StringRecord};
+
+/// Two images, labeled as showing the same picture or not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabeledPair {
+    pub image_a: PathBuf,
+    pub image_b: PathBuf,
+    /// Whether both images show the same picture
+    pub same: bool,
+}
+
+/// Loads labeled image pairs from a CSV file with `image_a`, `image_b` and `same` columns.
+///
+/// `same` accepts `true`/`false`, `yes`/`no` or `1`/`0`. Relative image paths are
+/// resolved from the directory of the CSV file.
+///
+/// ## Arguments
+///
+/// * `path` - Path to the CSV file
+///
+/// ## Returns
+///
+/// * `Ok(Vec<LabeledPair>)` - The pairs of the file, in order
+/// * `Err(String)` - Error message if the file cannot be read or has an invalid row
+pub fn load_labeled_pairs(path: String) -> Result<Vec<LabeledPair>, String> {
+    let path_ref: &Path = path.as_ref();
+    let base_dir: &Path = path_ref.parent().unwrap_or(Path::new(""));
+
+    let file: File = File::open(path_ref).map_err(|e| format!("Failed to open file: {}", e))?;
+    let mut reader: Reader<File> = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(file);
+
+    let headers: StringRecord = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV headers: {}", e))?
+        .clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .ok_or(format!("CSV must have a column named '{}'", name))
+    };
+    let (a_idx, b_idx, same_idx) = (column("image_a")?, column("image_b")?, column("same")?);
+
+    let mut pairs: Vec<LabeledPair> = Vec::new();
+    for (row_idx, result) in reader.records().enumerate() {
+        let record: StringRecord =
+            result.map_err(|e| format!("Failed to read row {}: {}", row_idx + 1, e))?;
+
+        let field = |index: usize| {
+            record.get(index).ok_or(format!(
+                "Row {} has fewer columns than expected",
+                row_idx + 1
+            ))
+        };
+
+        let same: bool = match field(same_idx)?.to_lowercase().as_str() {
+            "true" | "yes" | "1" => true,
+            "false" | "no" | "0" => false,
+            other => {
+                return Err(format!(
+                    "Row {} has an invalid 'same' value: {}",
+                    row_idx + 1,
+                    other
+                ));
+            }
+        };
+
+        pairs.push(LabeledPair {
+            image_a: base_dir.join(field(a_idx)?),
+            image_b: base_dir.join(field(b_idx)?),
+            same,
+        });
+    }
+
+    if pairs.is_empty() {
+        return Err("CSV file contains no labeled pairs".to_string());
+    }
+
+    Ok(pairs)
+}
+
+/// Builds labeled image pairs from a directory with one sub-directory per picture.
+///
+/// Every two images of the same sub-directory form a "same" pair, and every two
+/// images of different sub-directories form a "different" pair:
+///
+/// ```text
+/// pairs/
+/// ├── artwork-1/
+/// │   ├── photo.jpg
+/// │   └── scan.png
+/// └── artwork-2/
+///     └── photo.jpg
+/// ```
+///
+/// Only the files with an image extension, such as `.jpg` or `.png`, are used: other
+/// files, like notes or `.DS_Store`, are skipped.
+///
+/// ## Arguments
+///
+/// * `path` - Path to the directory
+///
+/// ## Returns
+///
+/// * `Ok(Vec<LabeledPair>)` - The pairs of images of the same picture and of different pictures
+/// * `Err(String)` - Error message if the directory cannot be read or contains no image pairs
+pub fn labeled_pairs_from_directory(path: String) -> Result<Vec<LabeledPair>, String> {
+    let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+
+    for label_dir in sorted_entries(Path::new(&path))? {
+        if !label_dir.is_dir() {
+            continue;
+        }
+
+        let images: Vec<PathBuf> = sorted_entries(&label_dir)?
+            .into_iter()
+            .filter(|image| is_image_file(image))
+            .collect();
+        if !images.is_empty() {
+            groups.push(images);
+        }
+    }
+
+    let mut pairs: Vec<LabeledPair> = Vec::new();
+    for (group_idx, group) in groups.iter().enumerate() {
+        for (image_idx, image_a) in group.iter().enumerate() {
+            // Pairs within the same group, then with the images of the following groups
+            let same_group = group[image_idx + 1..].iter().map(|image_b| (image_b, true));
+            let other_groups = groups[group_idx + 1..]
+                .iter()
+                .flatten()
+                .map(|image_b| (image_b, false));
+
+            for (image_b, same) in same_group.chain(other_groups) {
+                pairs.push(LabeledPair {
+                    image_a: image_a.clone(),
+                    image_b: image_b.clone(),
+                    same,
+                });
+            }
+        }
+    }
+
+    if pairs.is_empty() {
+        return Err(format!("Directory contains no image pairs: {}", path));
+    }
+
+    Ok(pairs)
+}
+
+/// Whether the path is a file with the extension of an image format
+fn is_image_file(path: &Path) -> bool {
+    path.is_file() && image::ImageFormat::from_path(path).is_ok()
+}
+
+fn sorted_entries(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read directory {}: {}", dir.display(), e))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read directory {}: {}", dir.display(), e))?;
+
+    entries.sort();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{temp_csv, temp_path};
+
+    /// Creates a directory of labeled images, with one sub-directory per picture
+    fn pairs_directory(groups: &[(&str, &[&str])]) -> PathBuf {
+        let dir: PathBuf = temp_path("pairs");
+        for (label, files) in groups {
+            fs::create_dir_all(dir.join(label)).unwrap();
+            for file in *files {
+                fs::write(dir.join(label).join(file), "").unwrap();
+            }
+        }
+        dir
+    }
+
+    #[test]
+    fn pairs_are_loaded_relative_to_the_csv_file() {
+        let path: String = temp_csv(
+            "labeled",
+            "image_a,image_b,same\na.jpg,b.jpg,yes\na.jpg,/abs/c.jpg,0\nb.jpg,c.jpg, TRUE \n",
+        );
+        let pairs: Result<Vec<LabeledPair>, String> = load_labeled_pairs(path.clone());
+        fs::remove_file(&path).unwrap();
+
+        let base_dir: &Path = Path::new(&path).parent().unwrap();
+        let pairs: Vec<LabeledPair> = pairs.unwrap();
+        assert_eq!(
+            pairs[0],
+            LabeledPair {
+                image_a: base_dir.join("a.jpg"),
+                image_b: base_dir.join("b.jpg"),
+                same: true,
+            }
+        );
+        assert_eq!(pairs[1].image_b, PathBuf::from("/abs/c.jpg"));
+        assert!(!pairs[1].same);
+        assert!(pairs[2].same);
+    }
+
+    #[test]
+    fn invalid_csv_files_are_refused() {
+        let files: [(&str, &str); 4] = [
+            ("no_pairs", "image_a,image_b,same\n"),
+            ("missing_column", "image_a,image_b\na.jpg,b.jpg\n"),
+            ("invalid_label", "image_a,image_b,same\na.jpg,b.jpg,maybe\n"),
+            ("short_row", "same,image_a,image_b\nyes,a.jpg\n"),
+        ];
+
+        for (name, contents) in files {
+            let path: String = temp_csv(name, contents);
+            let pairs: Result<Vec<LabeledPair>, String> = load_labeled_pairs(path.clone());
+            fs::remove_file(&path).unwrap();
+            assert!(pairs.is_err(), "{} should be refused", name);
+        }
+    }
+
+    #[test]
+    fn directories_give_same_and_different_pairs() {
+        let dir: PathBuf = pairs_directory(&[
+            (
+                "artwork-1",
+                &["photo.jpg", "scan.PNG", "notes.txt", ".DS_Store"],
+            ),
+            ("artwork-2", &["photo.jpeg"]),
+            ("empty", &[]),
+        ]);
+        fs::write(dir.join("readme.md"), "").unwrap();
+        let pairs: Result<Vec<LabeledPair>, String> =
+            labeled_pairs_from_directory(dir.display().to_string());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let pairs: Vec<LabeledPair> = pairs.unwrap();
+        let names: Vec<(String, String, bool)> = pairs
+            .iter()
+            .map(|pair| {
+                let name = |path: &Path| path.file_name().unwrap().to_string_lossy().to_string();
+                (name(&pair.image_a), name(&pair.image_b), pair.same)
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("photo.jpg".to_string(), "scan.PNG".to_string(), true),
+                ("photo.jpg".to_string(), "photo.jpeg".to_string(), false),
+                ("scan.PNG".to_string(), "photo.jpeg".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn directories_without_pairs_are_refused() {
+        let dir: PathBuf = pairs_directory(&[("artwork-1", &["photo.jpg", "notes.txt"])]);
+        let pairs: Result<Vec<LabeledPair>, String> =
+            labeled_pairs_from_directory(dir.display().to_string());
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(pairs.is_err());
+
+        let missing: String = temp_path("missing").display().to_string();
+        assert!(labeled_pairs_from_directory(missing).is_err());
+    }
+}
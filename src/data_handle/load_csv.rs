@@ -1,23 +1,36 @@
-use std::{fs::File, path::Path};
+use std::{fs::File, io::Read, path::Path};
 
 use csv::{Reader, StringRecord};
 use url::Url;
 
-/// Validates a CSV file and returns a reader, hash index, and link index.
+use crate::data_handle::dictionary_entry::DictionaryEntry;
+use crate::data_handle::dictionary_schema::{DictionaryColumns, DictionarySchema};
+
+/// Validates a CSV file and returns a reader and the column positions.
 ///
 /// ## Arguments
 ///
 /// * `path` - Path to the CSV file
+/// * `schema` - The layout of the file
 ///
 /// ## Returns
-fn validate_csv_file(path: &String) -> Result<(Reader<File>, usize, usize), String> {
-    // Check if the file exists and is a CSV
+///
+/// * `Ok((Reader<File>, DictionaryColumns))` - A reader positioned after the headers, and the positions of the columns
+/// * `Err(String)` - Error message if the file does not exist, is not a CSV or lacks a required column
+fn validate_csv_file(
+    path: &String,
+    schema: &DictionarySchema,
+) -> Result<(Reader<File>, DictionaryColumns), String> {
+    // Check if the file exists and is a CSV (or TSV)
     let path_ref: &Path = path.as_ref();
     if !path_ref.exists() {
         return Err(format!("File does not exist: {}", path_ref.display()));
     }
 
-    if path_ref.extension().and_then(|ext| ext.to_str()) != Some("csv") {
+    if !matches!(
+        path_ref.extension().and_then(|ext| ext.to_str()),
+        Some("csv" | "tsv")
+    ) {
         return Err(format!("File is not a CSV: {}", path_ref.display()));
     }
 
@@ -27,10 +40,19 @@ fn validate_csv_file(path: &String) -> Result<(Reader<File>, usize, usize), Stri
         Err(e) => return Err(format!("Failed to open file: {}", e)),
     };
 
-    let mut reader: Reader<File> = csv::ReaderBuilder::new()
+    validate_csv_reader(file, schema)
+}
+
+/// Validates the headers of CSV data and returns a reader and the column positions.
+fn validate_csv_reader<R: Read>(
+    input: R,
+    schema: &DictionarySchema,
+) -> Result<(Reader<R>, DictionaryColumns), String> {
+    let mut reader: Reader<R> = schema
+        .reader_builder()
         .flexible(false)
         .trim(csv::Trim::All)
-        .from_reader(file);
+        .from_reader(input);
 
     // Get headers
     let headers: StringRecord = match reader.headers() {
@@ -38,62 +60,221 @@ fn validate_csv_file(path: &String) -> Result<(Reader<File>, usize, usize), Stri
         Err(e) => return Err(format!("Failed to read CSV headers: {}", e)),
     };
 
-    // Check column count and find hash and link columns
-    let mut hash_index: Option<usize> = None;
-    let mut link_index: Option<usize> = None;
+    // Find the hash and link columns, and the optional ones
+    let columns: DictionaryColumns = schema.columns(&headers)?;
+
+    Ok((reader, columns))
+}
+
+/// Dictionary entries read lazily from CSV data, one record at a time.
+///
+/// Rows are read and validated as in [`load_entries_from_csv`]: rows with an empty hash
+/// or link, or with a link that is not a URL, are skipped, and invalid rows give an error.
+/// Only the current record is held in memory, so dictionaries larger than memory can be
+/// searched, e.g. with [`stream_top_k_similar_hashes`](crate::algorithm::streaming_search::stream_top_k_similar_hashes).
+pub struct CsvEntries<R: Read> {
+    reader: Reader<R>,
+    columns: DictionaryColumns,
+    record: StringRecord,
+    row_idx: usize,
+    /// Set after a read error, to stop rather than read the same broken record again
+    failed: bool,
+}
 
-    for (i, header) in headers.iter().enumerate() {
-        let lower_header: String = header.to_lowercase();
-        if lower_header == "hash" {
-            hash_index = Some(i);
-        } else if lower_header == "link" {
-            link_index = Some(i);
+impl<R: Read> CsvEntries<R> {
+    fn new(reader: Reader<R>, columns: DictionaryColumns) -> Self {
+        CsvEntries {
+            reader,
+            columns,
+            record: StringRecord::new(),
+            row_idx: 0,
+            failed: false,
         }
     }
+}
+
+impl<R: Read> Iterator for CsvEntries<R> {
+    type Item = Result<DictionaryEntry, String>;
 
-    // Check if both hash and link columns were found
-    if hash_index.is_none() || link_index.is_none() {
-        return Err("CSV must have columns named 'hash' and 'link'".to_string());
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed {
+            let row_idx: usize = self.row_idx;
+            match self.reader.read_record(&mut self.record) {
+                Ok(true) => self.row_idx += 1,
+                Ok(false) => return None,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(format!("Failed to read row {}: {}", row_idx + 1, e)));
+                }
+            }
+
+            match parse_entry(&self.record, &self.columns, row_idx, true) {
+                Ok(Some(entry)) => return Some(Ok(entry)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        None
     }
+}
+
+/// Reads dictionary entries lazily from CSV data, from a file, a network stream or any reader.
+///
+/// The headers are validated right away, the rows as they are read. See [`CsvEntries`].
+///
+/// ## Arguments
+///
+/// * `input` - The CSV data, with `hash` and `link` columns and an optional `threshold` column
+///
+/// ## Returns
+///
+/// * `Ok(CsvEntries<R>)` - An iterator over the entries, giving an error for each invalid row
+/// * `Err(String)` - Error message if the headers cannot be read or lack a required column
+pub fn stream_entries_from_reader<R: Read>(input: R) -> Result<CsvEntries<R>, String> {
+    stream_entries_from_reader_with_schema(input, &DictionarySchema::default())
+}
+
+/// Reads dictionary entries lazily from data in the given layout. See [`CsvEntries`].
+///
+/// ## Arguments
+///
+/// * `input` - The dictionary data
+/// * `schema` - The layout of the data: column names, delimiter and quoting
+///
+/// ## Returns
+///
+/// * `Ok(CsvEntries<R>)` - An iterator over the entries, giving an error for each invalid row
+/// * `Err(String)` - Error message if the headers cannot be read or lack a required column
+pub fn stream_entries_from_reader_with_schema<R: Read>(
+    input: R,
+    schema: &DictionarySchema,
+) -> Result<CsvEntries<R>, String> {
+    let (reader, columns) = validate_csv_reader(input, schema)?;
+    Ok(CsvEntries::new(reader, columns))
+}
 
-    Ok((reader, hash_index.unwrap(), link_index.unwrap()))
+/// Reads dictionary entries lazily from a CSV file. See [`CsvEntries`].
+///
+/// ## Arguments
+///
+/// * `path` - Path to the CSV file (or to a TSV file, with a `.tsv` extension)
+///
+/// ## Returns
+///
+/// * `Ok(CsvEntries<File>)` - An iterator over the entries, giving an error for each invalid row
+/// * `Err(String)` - Error message if the file cannot be opened, is not a CSV or lacks a required column
+pub fn stream_entries_from_csv(path: String) -> Result<CsvEntries<File>, String> {
+    let schema: DictionarySchema = DictionarySchema::for_path(&path);
+    stream_entries_from_csv_with_schema(path, &schema)
+}
+
+/// Reads dictionary entries lazily from a file in the given layout. See [`CsvEntries`].
+///
+/// ## Arguments
+///
+/// * `path` - Path to the CSV file
+/// * `schema` - The layout of the file: column names, delimiter and quoting
+///
+/// ## Returns
+///
+/// * `Ok(CsvEntries<File>)` - An iterator over the entries, giving an error for each invalid row
+/// * `Err(String)` - Error message if the file cannot be opened, is not a CSV or lacks a required column
+pub fn stream_entries_from_csv_with_schema(
+    path: String,
+    schema: &DictionarySchema,
+) -> Result<CsvEntries<File>, String> {
+    let (reader, columns) = validate_csv_file(&path, schema)?;
+    Ok(CsvEntries::new(reader, columns))
 }
 
 /// Loads data from a CSV file and returns a vector of hash-link pairs.
 ///
+/// Only the hash and link of each row are returned: the `threshold` and `thumbnail`
+/// columns are ignored, so searching these pairs applies the search threshold to every
+/// entry. Use [`load_entries_from_csv`] instead, which keeps the settings of each entry
+/// and gives entries that the search functions and indexes accept as well.
+///
 /// ## Arguments
 ///
 /// * `path` - Path to the CSV file
 ///
 /// ## Returns
+///
+/// * `Ok(Vec<(String, String)>)` - The `(hash, link)` pair of each valid row
+/// * `Err(String)` - Error message if the file cannot be read or contains no valid links
+#[deprecated(
+    note = "ignores the threshold and thumbnail columns, use `load_entries_from_csv` instead"
+)]
 pub fn load_data_from_csv(path: String) -> Result<Vec<(String, String)>, String> {
+    let schema: DictionarySchema = DictionarySchema::for_path(&path);
+    let entries: Vec<DictionaryEntry> = read_entries(path, &schema, false)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| (entry.hash, entry.link))
+        .collect())
+}
+
+/// Loads dictionary entries from a CSV file, along with their optional settings.
+///
+/// Besides the `hash` and `link` columns, an optional `threshold` column sets the
+/// minimum proximity (from 0.0 to 1.0) for each entry to match, overriding the
+/// threshold given to the search functions. Empty values keep the search threshold.
+/// An optional `thumbnail` column gives the path of a reference thumbnail of each
+/// entry's image, relative to the CSV file, to verify matches pixel by pixel.
+///
+/// ## Arguments
+///
+/// * `path` - Path to the CSV file (or to a TSV file, with a `.tsv` extension)
+///
+/// ## Returns
+///
+/// * `Ok(Vec<DictionaryEntry>)` - The entry of each valid row, with its settings
+/// * `Err(String)` - Error message if the file cannot be read, has an invalid threshold or contains no valid links
+pub fn load_entries_from_csv(path: String) -> Result<Vec<DictionaryEntry>, String> {
+    let schema: DictionarySchema = DictionarySchema::for_path(&path);
+    read_entries(path, &schema, true)
+}
+
+/// Loads dictionary entries from a file in the given layout, along with their optional settings.
+///
+/// See [`load_entries_from_csv`].
+///
+/// ## Arguments
+///
+/// * `path` - Path to the CSV file
+/// * `schema` - The layout of the file: column names, delimiter and quoting
+///
+/// ## Returns
+///
+/// * `Ok(Vec<DictionaryEntry>)` - The entry of each valid row, with its settings
+/// * `Err(String)` - Error message if the file cannot be read, has an invalid threshold or contains no valid links
+pub fn load_entries_from_csv_with_schema(
+    path: String,
+    schema: &DictionarySchema,
+) -> Result<Vec<DictionaryEntry>, String> {
+    read_entries(path, schema, true)
+}
+
+fn read_entries(
+    path: String,
+    schema: &DictionarySchema,
+    read_settings: bool,
+) -> Result<Vec<DictionaryEntry>, String> {
     // Validate the CSV file and get column indices and reader
-    let (mut reader, hash_idx, link_idx) = validate_csv_file(&path)?;
+    let (mut reader, columns) = validate_csv_file(&path, schema)?;
 
     // Read the records
-    let mut links: Vec<(String, String)> = Vec::new();
+    let mut links: Vec<DictionaryEntry> = Vec::new();
     for (row_idx, result) in reader.records().enumerate() {
         let record: StringRecord = match result {
             Ok(record) => record,
             Err(e) => return Err(format!("Failed to read row {}: {}", row_idx + 1, e)),
         };
 
-        if record.len() <= hash_idx || record.len() <= link_idx {
-            return Err(format!(
-                "Row {} has fewer columns than expected",
-                row_idx + 1
-            ));
-        }
-
-        let hash: String = record[hash_idx].trim().to_string();
-        let link: String = record[link_idx].trim().to_string();
-
-        if !hash.is_empty() && !link.is_empty() {
-            // Validate that the link is a proper URL
-            match Url::parse(&link) {
-                Ok(_) => links.push((hash, link)),
-                Err(_) => {}
-            }
+        if let Some(entry) = parse_entry(&record, &columns, row_idx, read_settings)? {
+            links.push(entry);
         }
     }
 
@@ -103,3 +284,124 @@ pub fn load_data_from_csv(path: String) -> Result<Vec<(String, String)>, String>
 
     Ok(links)
 }
+
+/// Parses a record into an entry, or `None` if its hash or link is empty or not valid
+pub(crate) fn parse_entry(
+    record: &StringRecord,
+    columns: &DictionaryColumns,
+    row_idx: usize,
+    read_settings: bool,
+) -> Result<Option<DictionaryEntry>, String> {
+    if record.len() <= columns.hash || record.len() <= columns.link {
+        return Err(format!(
+            "Row {} has fewer columns than expected",
+            row_idx + 1
+        ));
+    }
+
+    let hash: String = record[columns.hash].trim().to_string();
+    let link: String = record[columns.link].trim().to_string();
+
+    if !is_valid_entry(&hash, &link) {
+        return Ok(None);
+    }
+
+    let mut entry: DictionaryEntry = DictionaryEntry::new(hash, link);
+    if read_settings {
+        entry.threshold = parse_threshold(record, columns.threshold, row_idx)?;
+        entry.thumbnail = columns
+            .thumbnail
+            .and_then(|index| record.get(index))
+            .map(str::trim)
+            .filter(|thumbnail| !thumbnail.is_empty())
+            .map(str::to_string);
+    }
+
+    Ok(Some(entry))
+}
+
+fn parse_threshold(
+    record: &StringRecord,
+    index: Option<usize>,
+    row_idx: usize,
+) -> Result<Option<f64>, String> {
+    let Some(value) = index.and_then(|index| record.get(index)).map(str::trim) else {
+        return Ok(None);
+    };
+
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    match value.parse::<f64>() {
+        Ok(threshold) if is_valid_threshold(threshold) => Ok(Some(threshold)),
+        _ => Err(format!(
+            "Row {} has an invalid threshold (expected 0.0 to 1.0): {}",
+            row_idx + 1,
+            value
+        )),
+    }
+}
+
+/// Whether an entry is kept: entries with an empty hash or without a proper URL are skipped
+pub(crate) fn is_valid_entry(hash: &str, link: &str) -> bool {
+    !hash.is_empty() && !link.is_empty() && Url::parse(link).is_ok()
+}
+
+/// Whether a per-entry threshold is a valid proximity, from 0.0 to 1.0
+pub(crate) fn is_valid_threshold(threshold: f64) -> bool {
+    (0.0..=1.0).contains(&threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_csv;
+
+    #[test]
+    #[allow(deprecated)]
+    fn pairs_leave_out_the_entry_settings() {
+        let path: String = temp_csv(
+            "settings",
+            "hash,link,threshold,thumbnail\nffff0000ffff0000,https://a.com,0.9,a.png\n",
+        );
+        let pairs: Result<Vec<(String, String)>, String> = load_data_from_csv(path.clone());
+        let entries: Result<Vec<DictionaryEntry>, String> = load_entries_from_csv(path.clone());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            pairs.unwrap(),
+            vec![("ffff0000ffff0000".to_string(), "https://a.com".to_string())]
+        );
+        let entries: Vec<DictionaryEntry> = entries.unwrap();
+        assert_eq!(entries[0].threshold, Some(0.9));
+        assert_eq!(entries[0].thumbnail.as_deref(), Some("a.png"));
+    }
+
+    #[test]
+    fn invalid_rows_are_skipped_and_empty_files_refused() {
+        let path: String = temp_csv(
+            "invalid",
+            "hash,link\n,https://a.com\nffff0000ffff0000,not a link\n0000ffff0000ffff,https://b.com\n",
+        );
+        let entries: Result<Vec<DictionaryEntry>, String> = load_entries_from_csv(path.clone());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(entries.unwrap().len(), 1);
+
+        let path: String = temp_csv("empty", "hash,link\n");
+        let entries: Result<Vec<DictionaryEntry>, String> = load_entries_from_csv(path.clone());
+        std::fs::remove_file(&path).unwrap();
+        assert!(entries.is_err());
+    }
+
+    #[test]
+    fn thresholds_out_of_range_are_errors() {
+        let path: String = temp_csv(
+            "threshold",
+            "hash,link,threshold\nffff0000ffff0000,https://a.com,1.5\n",
+        );
+        let entries: Result<Vec<DictionaryEntry>, String> = load_entries_from_csv(path.clone());
+        std::fs::remove_file(&path).unwrap();
+        assert!(entries.unwrap_err().contains("invalid threshold"));
+    }
+}
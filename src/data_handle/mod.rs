@@ -1,2 +1,11 @@
 pub mod add_data_to_csv_file;
+pub mod bit_weights_file;
+pub mod dedupe;
+pub mod dictionary_entry;
+pub mod dictionary_format;
+pub mod dictionary_schema;
+pub mod edit_csv_file;
+pub mod impostors_file;
+pub mod json_dictionary;
+pub mod labeled_pairs;
 pub mod load_csv;
@@ -1,10 +1,31 @@
 use image::DynamicImage;
 
-use crate::algorithm::{ahash, remove_borders};
+use crate::algorithm::ahash;
+use crate::algorithm::content_region::{self, ContentRegionOptions};
+use crate::algorithm::hash_information::HashInformation;
+use crate::algorithm::remove_borders::{self, BorderRemovalOptions};
+use crate::data_handle::dictionary_entry::HashEntry;
 
 pub mod algorithm;
 pub mod data_handle;
 
+#[cfg(test)]
+mod test_support;
+
+/// The processing applied to an image before its hash is computed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Preprocessing {
+    /// Hash the image as is
+    None,
+    /// Remove white (or near-white) borders around the content
+    #[default]
+    RemoveWhiteBorders,
+    /// Remove white borders using custom options
+    RemoveWhiteBordersWith(BorderRemovalOptions),
+    /// Only keep the dominant photographic region, such as an artwork in a screenshot
+    ExtractContentRegion(ContentRegionOptions),
+}
+
 /// Computes a perceptual hash for the given image.
 ///
 /// This function processes an image by optionally removing white borders and then
@@ -27,26 +48,63 @@ pub mod data_handle;
 ///
 /// # Examples
 ///
-/// ```
-/// use image::DynamicImage;
+/// ```no_run
+/// use img_hash_linker::compute_hash;
+///
+/// let image = image::open("path/to/image.jpg").unwrap();
 ///
 /// // Compute hash with border removal and custom size
-/// let hash = compute_hash(image, true, Some(10))?;
+/// let hash = compute_hash(image.clone(), true, Some(10))?;
 ///
 /// // Compute hash with default settings
 /// let hash = compute_hash(image, false, None)?;
+/// # Ok::<(), String>(())
 /// ```
 pub fn compute_hash(
     image: DynamicImage,
     remove_white_border: bool,
     hash_size: Option<u32>,
 ) -> Result<String, String> {
-    let processed_img: DynamicImage = if remove_white_border {
-        remove_borders::remove_white_borders(&image)
+    let preprocessing: Preprocessing = if remove_white_border {
+        Preprocessing::RemoveWhiteBorders
     } else {
-        image
+        Preprocessing::None
     };
 
+    compute_hash_with_preprocessing(image, &preprocessing, hash_size)
+}
+
+/// Computes a perceptual hash for the given image, after applying the given preprocessing.
+///
+/// This is the same as [`compute_hash`], but allows choosing how the image is prepared
+/// before hashing, for example extracting the dominant picture from a screenshot
+/// instead of removing white borders.
+///
+/// # Arguments
+///
+/// * `image` - A `DynamicImage` containing the image to hash
+/// * `preprocessing` - The processing applied to the image before hashing
+/// * `hash_size` - An optional hash size. If `None` is provided, the default size of 8 will be used.
+///
+/// # Examples
+///
+/// ```no_run
+/// use img_hash_linker::algorithm::content_region::ContentRegionOptions;
+/// use img_hash_linker::{Preprocessing, compute_hash_with_preprocessing};
+///
+/// let screenshot = image::open("path/to/screenshot.png").unwrap();
+/// let preprocessing = Preprocessing::ExtractContentRegion(ContentRegionOptions::default());
+///
+/// let hash = compute_hash_with_preprocessing(screenshot, &preprocessing, None)?;
+/// # Ok::<(), String>(())
+/// ```
+pub fn compute_hash_with_preprocessing(
+    image: DynamicImage,
+    preprocessing: &Preprocessing,
+    hash_size: Option<u32>,
+) -> Result<String, String> {
+    let processed_img: DynamicImage = preprocess_image(image, preprocessing);
+
     if let Some(hash_size) = hash_size {
         Ok(ahash::compute_image_hash(&processed_img, hash_size))
     } else {
@@ -54,6 +112,98 @@ pub fn compute_hash(
     }
 }
 
+/// Computes a perceptual hash for the given image, along with how much information it carries.
+///
+/// This is the same as [`compute_hash_with_preprocessing`], but also reports the
+/// [`HashInformation`] of the hash (bit balance and grid variance), to detect
+/// degenerate hashes of blank or uniform images.
+///
+/// # Examples
+///
+/// ```no_run
+/// use img_hash_linker::algorithm::hash_information::DegenerateHashCheck;
+/// use img_hash_linker::{Preprocessing, compute_hash_with_information};
+///
+/// let image = image::open("path/to/image.jpg").unwrap();
+/// let (hash, information) = compute_hash_with_information(image, &Preprocessing::default(), None)?;
+///
+/// if let Some(warning) = DegenerateHashCheck::default().check(&hash, &information)? {
+///     eprintln!("{}", warning);
+/// }
+/// # Ok::<(), String>(())
+/// ```
+pub fn compute_hash_with_information(
+    image: DynamicImage,
+    preprocessing: &Preprocessing,
+    hash_size: Option<u32>,
+) -> Result<(String, HashInformation), String> {
+    let processed_img: DynamicImage = preprocess_image(image, preprocessing);
+    Ok(ahash::compute_image_hash_with_information(
+        &processed_img,
+        hash_size,
+    ))
+}
+
+/// Computes the perceptual hashes of the given image at several hash sizes.
+///
+/// The image is preprocessed once, then hashed at each size, e.g. to look it up in a
+/// dictionary holding hashes of several sizes. Hashes are returned in the order of `hash_sizes`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use img_hash_linker::{Preprocessing, compute_hashes_with_preprocessing};
+///
+/// let image = image::open("path/to/image.jpg").unwrap();
+/// let hashes = compute_hashes_with_preprocessing(image, &Preprocessing::default(), &[4, 8])?;
+/// assert_eq!(hashes[0].len(), 4);
+/// # Ok::<(), String>(())
+/// ```
+pub fn compute_hashes_with_preprocessing(
+    image: DynamicImage,
+    preprocessing: &Preprocessing,
+    hash_sizes: &[u32],
+) -> Result<Vec<String>, String> {
+    Ok(
+        compute_hashes_with_information(image, preprocessing, hash_sizes)?
+            .into_iter()
+            .map(|(hash, _)| hash)
+            .collect(),
+    )
+}
+
+/// Computes the perceptual hashes of the given image at several hash sizes, along with
+/// how much information each of them carries.
+///
+/// See [`compute_hashes_with_preprocessing`] and [`compute_hash_with_information`].
+pub fn compute_hashes_with_information(
+    image: DynamicImage,
+    preprocessing: &Preprocessing,
+    hash_sizes: &[u32],
+) -> Result<Vec<(String, HashInformation)>, String> {
+    let processed_img: DynamicImage = preprocess_image(image, preprocessing);
+
+    Ok(hash_sizes
+        .iter()
+        .map(|&hash_size| ahash::compute_image_hash_with_information(&processed_img, hash_size))
+        .collect())
+}
+
+/// Applies the processing done before hashing to an image, e.g. to compare the
+/// border-trimmed image with reference thumbnails.
+pub fn preprocess_image(image: DynamicImage, preprocessing: &Preprocessing) -> DynamicImage {
+    match preprocessing {
+        Preprocessing::None => image,
+        Preprocessing::RemoveWhiteBorders => remove_borders::remove_white_borders(&image),
+        Preprocessing::RemoveWhiteBordersWith(options) => {
+            remove_borders::remove_white_borders_with_options(&image, options).image
+        }
+        Preprocessing::ExtractContentRegion(options) => {
+            content_region::extract_content_region(&image, options)
+        }
+    }
+}
+
 /// Opens a link associated with the given hash.
 ///
 /// This function searches through a collection of hash-link pairs to find a matching hash,
@@ -61,18 +211,21 @@ pub fn compute_hash(
 ///
 /// # Arguments
 ///
-/// * `links` - A vector of tuples where each tuple contains `(hash, link)` as `(String, String)`
+/// * `links` - A vector of tuples where each tuple contains `(hash, link)` as `(String, String)`,
+///   or of any other [`HashEntry`]
 /// * `hash` - The hash string to search for in the links collection
 ///
 /// # Returns
 ///
 /// Returns a `Result<String, String>` where:
 /// * `Ok(String)` contains a success message with the opened link
-/// * `Err(String)` contains an error message if the hash is not found
+/// * `Err(String)` contains an error message if the hash is not found or the link cannot be opened
 ///
 /// # Examples
 ///
-/// ```
+/// ```no_run
+/// use img_hash_linker::open_link_from_hash;
+///
 /// let links = vec![
 ///     ("abc123".to_string(), "https://example.com".to_string()),
 ///     ("def456".to_string(), "https://another.com".to_string()),
@@ -85,14 +238,18 @@ pub fn compute_hash(
 /// ```
 ///
 /// Getting the links vector from a csv file:
+/// ```no_run
+/// use img_hash_linker::data_handle::dictionary_entry::DictionaryEntry;
+/// use img_hash_linker::data_handle::load_csv::load_entries_from_csv;
+///
+/// let links: Vec<DictionaryEntry> = load_entries_from_csv("path/to/example.csv".to_string()).unwrap();
 /// ```
-/// let links: Vec<(String, String)> = load_data_from_csv("path/to/example.csv").unwrap();
-/// ```
-pub fn open_link_from_hash(links: Vec<(String, String)>, hash: String) -> Result<String, String> {
-    for (h, link) in &links {
-        if *h == hash {
-            open::that(&link).unwrap();
-            return Ok(format!("Link opened: {}", link));
+pub fn open_link_from_hash<E: HashEntry>(links: Vec<E>, hash: String) -> Result<String, String> {
+    for entry in &links {
+        if entry.hash() == hash {
+            open::that(entry.link())
+                .map_err(|e| format!("Failed to open link {}: {}", entry.link(), e))?;
+            return Ok(format!("Link opened: {}", entry.link()));
         }
     }
 
@@ -1,44 +1,748 @@
 use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process;
 
-use img_hash_linker::algorithm::hash_proximity::try_finding_similar_hash;
-use img_hash_linker::compute_hash;
-use img_hash_linker::data_handle::load_csv::load_data_from_csv;
+use image::DynamicImage;
+
+use img_hash_linker::algorithm::ahash::hex_len_for_hash_size;
+use img_hash_linker::algorithm::batch::{BatchOptions, BatchResult, batch_find_matches};
+use img_hash_linker::algorithm::bit_weights::compute_bit_weights;
+use img_hash_linker::algorithm::calibration::{
+    CalibrationOptions, CalibrationReport, calibrate, write_calibration_reports,
+};
+use img_hash_linker::algorithm::clustering::{HashCluster, cluster_near_duplicates};
+use img_hash_linker::algorithm::content_region::ContentRegionOptions;
+use img_hash_linker::algorithm::distance_metric::WeightedBits;
+use img_hash_linker::algorithm::hash_dictionary::HashDictionary;
+use img_hash_linker::algorithm::hash_information::{
+    DegenerateHashCheck, DegenerateHashPolicy, HashInformation,
+};
+use img_hash_linker::algorithm::hash_proximity::{HashMatch, MatchOutcome};
+use img_hash_linker::algorithm::match_confidence::ImpostorDistribution;
+use img_hash_linker::algorithm::thumbnail_verification::{
+    VerificationOptions, VerifiedMatches, classify_verified_matches, verify_matches,
+};
+use img_hash_linker::data_handle::bit_weights_file::{
+    bit_weights_path, load_bit_weights, write_bit_weights,
+};
+use img_hash_linker::data_handle::dedupe::{
+    DedupeReport, DuplicatePolicy, dedupe_csv_file, dedupe_entries,
+};
+use img_hash_linker::data_handle::dictionary_entry::DictionaryEntry;
+use img_hash_linker::data_handle::dictionary_format::{
+    DictionaryFormat, load_dictionary, save_dictionary, write_dictionary_with_format,
+};
+use img_hash_linker::data_handle::dictionary_schema::DictionarySchema;
+use img_hash_linker::data_handle::impostors_file::{
+    impostors_path, load_impostor_distribution, write_impostor_distribution,
+};
+use img_hash_linker::data_handle::labeled_pairs::{
+    LabeledPair, labeled_pairs_from_directory, load_labeled_pairs,
+};
 use img_hash_linker::open_link_from_hash;
+use img_hash_linker::{
+    Preprocessing, compute_hash_with_information, compute_hashes_with_information, preprocess_image,
+};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    // Extract the dominant picture (e.g. from a screenshot) instead of removing white borders
+    let mut preprocessing: Preprocessing = Preprocessing::RemoveWhiteBorders;
+    if let Some(position) = args.iter().position(|arg| arg == "--content-region") {
+        args.remove(position);
+        preprocessing = Preprocessing::ExtractContentRegion(ContentRegionOptions::default());
+    }
+
+    // What to do with degenerate hashes, e.g. of blank images
+    let mut degenerate: DegenerateHashCheck = DegenerateHashCheck::default();
+    if let Some(policy) = take_option(&mut args, "--degenerate") {
+        degenerate.policy = match policy.as_str() {
+            "allow" => DegenerateHashPolicy::Allow,
+            "warn" => DegenerateHashPolicy::Warn,
+            "reject" => DegenerateHashPolicy::Reject,
+            _ => {
+                eprintln!(
+                    "Invalid degenerate hash policy (use allow, warn or reject): {}",
+                    policy
+                );
+                process::exit(1);
+            }
+        };
+    }
+
+    // Which link to keep when the same hash points to different links
+    let duplicates: DuplicatePolicy = take_option(&mut args, "--duplicates")
+        .map_or(DuplicatePolicy::default(), |policy| {
+            parse_duplicate_policy(&policy)
+        });
 
     if args.len() < 2 {
-        eprintln!("Usage: {} <image_path> [csv_dict_path]", args[0]);
-        eprintln!("  - With only image_path: computes and displays the hash");
-        eprintln!("  - With both arguments: opens the link associated with the image");
+        print_usage(&args[0]);
         process::exit(1);
     }
 
+    if args[1] == "calibrate" {
+        run_calibrate(&args[0], &args[2..], preprocessing);
+        return;
+    }
+
+    if args[1] == "batch" {
+        run_batch(&args[0], &args[2..], preprocessing, degenerate, duplicates);
+        return;
+    }
+
+    if args[1] == "weights" {
+        run_weights(&args[0], &args[2..]);
+        return;
+    }
+
+    if args[1] == "confidence" {
+        run_confidence(&args[0], &args[2..]);
+        return;
+    }
+
+    if args[1] == "clusters" {
+        run_clusters(&args[0], &args[2..]);
+        return;
+    }
+
+    if args[1] == "dedupe" {
+        run_dedupe(&args[0], &args[2..]);
+        return;
+    }
+
     let image_path: String = args.get(1).unwrap().clone();
-    let hash: String = compute_hash(image::open(image_path).unwrap(), true, None).unwrap();
+    let image = image::open(image_path).unwrap();
 
     if args.len() >= 3 {
         let dict_path: String = args.get(2).unwrap().clone();
-        let links: Vec<(String, String)> = load_data_from_csv(dict_path).unwrap();
-
-        match open_link_from_hash(links.clone(), hash.clone()) {
-            Ok(message) => println!("{}", message),
-            Err(e) => match try_finding_similar_hash(hash.clone(), links.clone(), None) {
-                Ok((similar_hash, _link, proximity)) => {
-                    println!(
-                        "{} (Proximity: {:.2}%)",
-                        open_link_from_hash(links.clone(), similar_hash).unwrap(),
-                        proximity * 100.0
-                    );
-                }
-                Err(_) => {
-                    println!("{}", e);
+        let (links, dictionary): (Vec<DictionaryEntry>, HashDictionary) =
+            load_lookup_entries(&dict_path, duplicates)
+                .and_then(|links| {
+                    build_dictionary(&links, &dict_path).map(|dictionary| (links, dictionary))
+                })
+                .unwrap_or_else(|e| exit_with_error(e));
+
+        // Hash the image at every size present in the dictionary, to compare like-for-like
+        let hash_sizes: Vec<u32> = if dictionary.is_empty() {
+            vec![8]
+        } else {
+            dictionary.hash_sizes()
+        };
+        // Process the image once, to hash it and to compare it with the reference thumbnails
+        let image: DynamicImage = preprocess_image(image, &preprocessing);
+        let mut hashes: Vec<String> = Vec::new();
+        for (hash, information) in
+            compute_hashes_with_information(image.clone(), &Preprocessing::None, &hash_sizes)
+                .unwrap_or_else(|e| exit_with_error(e))
+        {
+            check_degenerate_hash(&degenerate, &hash, &information);
+            hashes.push(hash);
+        }
+
+        let outcome: Result<MatchOutcome, String> =
+            if links.iter().any(|entry| entry.thumbnail.is_some()) {
+                find_verified_match(&dictionary, &links, &hashes, &image, &dict_path)
+            } else {
+                dictionary.find_match_for_hashes(&hashes, None, None)
+            };
+
+        match outcome.unwrap_or_else(|e| exit_with_error(e)) {
+            MatchOutcome::Exact(found) => {
+                let link: String =
+                    open_link_from_hash(links, found.hash).unwrap_or_else(|e| exit_with_error(e));
+                println!("{}", link);
+            }
+            MatchOutcome::Confident(found) => {
+                let scores: String = describe_scores(&found);
+                let link: String =
+                    open_link_from_hash(links, found.hash).unwrap_or_else(|e| exit_with_error(e));
+                println!("{} ({})", link, scores);
+            }
+            MatchOutcome::Ambiguous { best, runner_up } => {
+                eprintln!("Ambiguous match, no link was opened. Candidates:");
+                for candidate in [best, runner_up] {
+                    eprintln!("  {} ({})", candidate.link, describe_scores(&candidate));
                 }
-            },
+                process::exit(2);
+            }
+            MatchOutcome::NoMatch => {
+                println!("Hash not found: {}", hashes.join(", "));
+            }
         }
     } else {
+        let (hash, information) =
+            compute_hash_with_information(image, &preprocessing, None).unwrap();
+        check_degenerate_hash(&degenerate, &hash, &information);
         println!("Image hash: {}", hash);
     }
 }
+
+fn print_usage(program: &str) {
+    eprintln!(
+        "Usage: {} [--content-region] [--degenerate allow|warn|reject] [--duplicates first|last|error] <image_path> [dict_path]",
+        program
+    );
+    eprintln!(
+        "       {} [--content-region] calibrate <pairs_csv_or_dir> [--hash-sizes 4,8,...] [--output report.json|report.csv]",
+        program
+    );
+    eprintln!(
+        "       {} [--content-region] [--degenerate allow|warn|reject] [--duplicates first|last|error] batch <dict_path> <image_path>...",
+        program
+    );
+    eprintln!("       {} weights <dict_path> [--hash-size 8]", program);
+    eprintln!(
+        "       {} confidence <dict_path> [--max-pairs 100000]",
+        program
+    );
+    eprintln!("       {} clusters <dict_path> [--max-distance 3]", program);
+    eprintln!(
+        "       {} dedupe <dict_path> [--policy first|last|error] [--output deduped.csv]",
+        program
+    );
+    eprintln!("  - Dictionaries are CSV, TSV (.tsv), JSON arrays (.json) or JSON Lines (.jsonl)");
+    eprintln!("  - With only image_path: computes and displays the hash");
+    eprintln!("  - With both arguments: opens the link associated with the image");
+    eprintln!("  - With --content-region: hashes only the dominant picture of the image");
+    eprintln!(
+        "  - With --degenerate: what to do with hashes of blank or uniform images (default: warn)"
+    );
+    eprintln!(
+        "  - With --duplicates: which link to keep for hashes pointing to different links (default: first)"
+    );
+    eprintln!("  - calibrate: recommends a proximity threshold from labeled image pairs,");
+    eprintln!(
+        "    given as a CSV file (image_a,image_b,same) or a directory of sub-directories per picture"
+    );
+    eprintln!("  - batch: looks many images up in parallel, printing one CSV line per image");
+    eprintln!("  - weights: computes how informative each hash bit is across the dictionary,");
+    eprintln!("    and saves the bit weights alongside it (<dict>.weights.8x8.csv), so that");
+    eprintln!("    lookups compare the hashes of that size with them");
+    eprintln!("  - confidence: measures how close unrelated entries of the dictionary are, and");
+    eprintln!("    saves the distribution alongside it (<dict>.impostors.csv) so that lookups");
+    eprintln!("    report false-match probabilities");
+    eprintln!("  - clusters: groups near-duplicate entries of the dictionary, printing one CSV");
+    eprintln!("    line per entry and flagging the clusters pointing to different links");
+    eprintln!("  - dedupe: merges identical rows and resolves hashes pointing to different links,");
+    eprintln!("    writing the cleaned-up dictionary to the output file or the standard output");
+}
+
+/// Parses a duplicate policy, exiting if it is invalid
+fn parse_duplicate_policy(policy: &str) -> DuplicatePolicy {
+    match policy {
+        "first" => DuplicatePolicy::FirstWins,
+        "last" => DuplicatePolicy::LastWins,
+        "error" => DuplicatePolicy::Error,
+        _ => exit_with_error(format!(
+            "Invalid duplicate policy (use first, last or error): {}",
+            policy
+        )),
+    }
+}
+
+/// Prints an error and exits with a failure status
+fn exit_with_error(error: String) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
+
+/// Takes the value following `flag` out of the arguments, if present
+fn take_option(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let position: usize = args.iter().position(|arg| arg == flag)?;
+    args.remove(position);
+    if position < args.len() {
+        Some(args.remove(position))
+    } else {
+        None
+    }
+}
+
+fn run_calibrate(program: &str, args: &[String], preprocessing: Preprocessing) {
+    let mut args: Vec<String> = args.to_vec();
+    let output: Option<String> = take_option(&mut args, "--output");
+    let hash_sizes: Vec<u32> = match take_option(&mut args, "--hash-sizes") {
+        Some(sizes) => sizes
+            .split(',')
+            .map(|size| size.trim().parse::<u32>())
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| {
+                eprintln!("Invalid hash sizes: {}", e);
+                process::exit(1);
+            }),
+        None => vec![8],
+    };
+
+    let Some(source) = args.first().cloned() else {
+        print_usage(program);
+        process::exit(1);
+    };
+
+    let pairs: Result<Vec<LabeledPair>, String> = if std::path::Path::new(&source).is_dir() {
+        labeled_pairs_from_directory(source)
+    } else {
+        load_labeled_pairs(source)
+    };
+
+    let options: CalibrationOptions = CalibrationOptions {
+        hash_sizes,
+        preprocessing,
+    };
+    let reports: Vec<CalibrationReport> = match pairs.and_then(|pairs| calibrate(&pairs, &options))
+    {
+        Ok(reports) => reports,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    for report in &reports {
+        println!(
+            "{} {}x{}: EER {:.2}%, recommended threshold {:.4} ({} same pairs, {} different pairs)",
+            report.algorithm,
+            report.hash_size,
+            report.hash_size,
+            report.equal_error_rate * 100.0,
+            report.recommended_threshold,
+            report.genuine_count,
+            report.impostor_count
+        );
+    }
+
+    if let Some(output) = output {
+        match write_calibration_reports(&reports, output.clone()) {
+            Ok(()) => println!("Report written to {}", output),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// Loads the dictionary to look images up in, keeping one link per hash with the policy
+/// and warning about the hashes pointing to different links
+fn load_lookup_entries(
+    dict_path: &str,
+    duplicates: DuplicatePolicy,
+) -> Result<Vec<DictionaryEntry>, String> {
+    let report: DedupeReport =
+        dedupe_entries(&load_dictionary(dict_path.to_string())?, duplicates)?;
+    for conflict in &report.conflicts {
+        eprintln!(
+            "Warning: {} points to {}, kept {}",
+            conflict.hash,
+            conflict.links.join(", "),
+            conflict.kept
+        );
+    }
+
+    Ok(report.entries)
+}
+
+/// Parses the dictionary, with the bit weights saved alongside it by the `weights`
+/// subcommand, if any, to compare the hashes of each size, and with the impostor
+/// distribution saved by the `confidence` subcommand, if any, to estimate the
+/// false-match probability of each match
+fn build_dictionary(links: &[DictionaryEntry], dict_path: &str) -> Result<HashDictionary, String> {
+    let mut dictionary: HashDictionary = HashDictionary::from_entries(links)?;
+
+    for hash_size in dictionary.hash_sizes() {
+        let path: PathBuf = bit_weights_path(dict_path, hash_size);
+        if path.exists() {
+            warn_if_older(&path, dict_path, "weights");
+            let weights: WeightedBits = load_bit_weights(path.display().to_string())?;
+            dictionary = dictionary
+                .with_bit_weights(weights)
+                .map_err(|e| format!("Invalid bit weights in {}: {}", path.display(), e))?;
+        }
+    }
+
+    let path: PathBuf = impostors_path(dict_path);
+    if !path.exists() {
+        return Ok(dictionary);
+    }
+    warn_if_older(&path, dict_path, "confidence");
+    let impostors: ImpostorDistribution = load_impostor_distribution(path.display().to_string())?;
+    Ok(dictionary.with_impostor_distribution(impostors))
+}
+
+/// Warns if a file saved alongside the dictionary by `subcommand` was modified before it
+fn warn_if_older(path: &Path, dict_path: &str, subcommand: &str) {
+    let modified = |path: &Path| {
+        path.metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    if let (Some(time), Some(dict_time)) = (modified(path), modified(Path::new(dict_path)))
+        && time < dict_time
+    {
+        eprintln!(
+            "Warning: {} is older than the dictionary, run the {} subcommand again",
+            path.display(),
+            subcommand
+        );
+    }
+}
+
+/// Looks the hashes of an image up in the dictionary, verifying the matches against
+/// the reference thumbnails of their entries
+fn find_verified_match(
+    dictionary: &HashDictionary,
+    links: &[DictionaryEntry],
+    hashes: &[String],
+    image: &DynamicImage,
+    dict_path: &str,
+) -> Result<MatchOutcome, String> {
+    let candidates: Vec<HashMatch> = dictionary.find_top_k_for_hashes(hashes, usize::MAX, None)?;
+
+    // Thumbnail paths are relative to the dictionary
+    let options: VerificationOptions = VerificationOptions::for_dictionary(dict_path);
+    let verified: VerifiedMatches = verify_matches(image, candidates, links, &options);
+    for warning in &verified.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    let verified: Vec<HashMatch> = verified.matches;
+
+    // The best match is exact if it equals the query hash of its own size
+    let query: &str = verified
+        .first()
+        .and_then(|best| hashes.iter().find(|hash| hash.len() == best.hash.len()))
+        .map_or("", String::as_str);
+
+    Ok(classify_verified_matches(query, verified, 0.01))
+}
+
+/// The proximity of a match, along with its false-match probability and thumbnail
+/// similarity if known
+fn describe_scores(found: &HashMatch) -> String {
+    let mut scores: String = format!("Proximity: {:.2}%", found.proximity * 100.0);
+    if let Some(probability) = found.false_match_probability {
+        scores.push_str(&format!(", false match probability: {:.2e}", probability));
+    }
+    if let Some(similarity) = found.thumbnail_similarity {
+        scores.push_str(&format!(", thumbnail similarity: {:.2}", similarity));
+    }
+    scores
+}
+
+/// Applies the degenerate hash policy to a query hash, exiting if it is rejected
+fn check_degenerate_hash(check: &DegenerateHashCheck, hash: &str, information: &HashInformation) {
+    match check.check(hash, information) {
+        Ok(Some(warning)) => eprintln!("Warning: {}", warning),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_batch(
+    program: &str,
+    args: &[String],
+    preprocessing: Preprocessing,
+    degenerate: DegenerateHashCheck,
+    duplicates: DuplicatePolicy,
+) {
+    let Some((dict_path, image_paths)) = args.split_first() else {
+        print_usage(program);
+        process::exit(1);
+    };
+
+    let dictionary: HashDictionary = match load_lookup_entries(dict_path, duplicates)
+        .and_then(|links| build_dictionary(&links, dict_path))
+    {
+        Ok(dictionary) => dictionary,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let options: BatchOptions = BatchOptions {
+        preprocessing,
+        degenerate,
+        ..Default::default()
+    };
+    let progress = |done: usize, total: usize| {
+        if done.is_multiple_of(100) || done == total {
+            eprintln!("{}/{} images looked up", done, total);
+        }
+    };
+    let results: Vec<Result<BatchResult, String>> =
+        batch_find_matches(image_paths, &dictionary, &options, Some(&progress));
+
+    let mut writer: csv::Writer<io::Stdout> = csv::Writer::from_writer(io::stdout());
+    write_csv_row(
+        &mut writer,
+        [
+            "image",
+            "hash",
+            "outcome",
+            "link",
+            "proximity",
+            "false_match_probability",
+            "error",
+        ],
+    );
+    for (image_path, result) in image_paths.iter().zip(results) {
+        match result {
+            Ok(BatchResult {
+                hashes,
+                outcome,
+                warnings,
+                ..
+            }) => {
+                for warning in warnings {
+                    eprintln!("Warning: {}: {}", image_path, warning);
+                }
+                let hash: String = hashes.join(" ");
+                let (name, found) = match outcome {
+                    MatchOutcome::Exact(found) => ("exact", Some(found)),
+                    MatchOutcome::Confident(found) => ("confident", Some(found)),
+                    MatchOutcome::Ambiguous { best, .. } => ("ambiguous", Some(best)),
+                    MatchOutcome::NoMatch => ("no_match", None),
+                };
+                let (link, proximity, probability) = match found {
+                    Some(found) => (
+                        found.link,
+                        format!("{:.4}", found.proximity),
+                        found
+                            .false_match_probability
+                            .map_or(String::new(), |p| format!("{:.2e}", p)),
+                    ),
+                    None => (String::new(), String::new(), String::new()),
+                };
+                write_csv_row(
+                    &mut writer,
+                    [
+                        image_path.as_str(),
+                        &hash,
+                        name,
+                        &link,
+                        &proximity,
+                        &probability,
+                        "",
+                    ],
+                );
+            }
+            Err(e) => {
+                eprintln!("{}: {}", image_path, e);
+                write_csv_row(
+                    &mut writer,
+                    [image_path.as_str(), "", "error", "", "", "", &e],
+                );
+            }
+        }
+    }
+    if let Err(e) = writer.flush() {
+        eprintln!("Failed to write results: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Writes a row of CSV output, exiting if the output is closed
+fn write_csv_row<W: Write, const N: usize>(writer: &mut csv::Writer<W>, row: [&str; N]) {
+    if let Err(e) = writer.write_record(row) {
+        eprintln!("Failed to write results: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run_weights(program: &str, args: &[String]) {
+    let mut args: Vec<String> = args.to_vec();
+    let hash_size: u32 = match take_option(&mut args, "--hash-size") {
+        Some(size) => size.trim().parse().unwrap_or_else(|e| {
+            eprintln!("Invalid hash size: {}", e);
+            process::exit(1);
+        }),
+        None => 8,
+    };
+
+    let Some(dict_path) = args.first().cloned() else {
+        print_usage(program);
+        process::exit(1);
+    };
+
+    let weights_path: String = bit_weights_path(&dict_path, hash_size)
+        .display()
+        .to_string();
+    let weights: WeightedBits = match load_dictionary(dict_path)
+        .and_then(|links| compute_bit_weights(&links, hex_len_for_hash_size(hash_size)))
+        .and_then(|weights| write_bit_weights(&weights, weights_path.clone()).map(|_| weights))
+    {
+        Ok(weights) => weights,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let total: f64 = weights.weights.iter().sum();
+    println!(
+        "Bit weights written to {} ({:.1} informative bits out of {})",
+        weights_path,
+        total,
+        weights.weights.len()
+    );
+}
+
+fn run_confidence(program: &str, args: &[String]) {
+    let mut args: Vec<String> = args.to_vec();
+    let max_pairs: Option<usize> = take_option(&mut args, "--max-pairs").map(|pairs| {
+        pairs.trim().parse().unwrap_or_else(|e| {
+            eprintln!("Invalid maximum number of pairs: {}", e);
+            process::exit(1);
+        })
+    });
+
+    let Some(dict_path) = args.first().cloned() else {
+        print_usage(program);
+        process::exit(1);
+    };
+
+    let impostors_path: String = impostors_path(&dict_path).display().to_string();
+    let impostors: ImpostorDistribution = load_dictionary(dict_path)
+        .and_then(|links| ImpostorDistribution::from_entries(&links, max_pairs))
+        .and_then(|impostors| {
+            write_impostor_distribution(&impostors, impostors_path.clone()).map(|_| impostors)
+        })
+        .unwrap_or_else(|e| exit_with_error(e));
+
+    let pair_count: u64 = impostors
+        .hex_lens()
+        .iter()
+        .map(|&hex_len| impostors.pair_count(hex_len))
+        .sum();
+    println!(
+        "Impostor distribution written to {} ({} pairs of unrelated entries)",
+        impostors_path, pair_count
+    );
+}
+
+fn run_clusters(program: &str, args: &[String]) {
+    let mut args: Vec<String> = args.to_vec();
+    let max_distance: u32 = match take_option(&mut args, "--max-distance") {
+        Some(distance) => distance.trim().parse().unwrap_or_else(|e| {
+            eprintln!("Invalid maximum distance: {}", e);
+            process::exit(1);
+        }),
+        None => 3,
+    };
+
+    let Some(dict_path) = args.first().cloned() else {
+        print_usage(program);
+        process::exit(1);
+    };
+
+    let clusters: Vec<HashCluster> = match load_dictionary(dict_path)
+        .and_then(|links| cluster_near_duplicates(&links, max_distance))
+    {
+        Ok(clusters) => clusters,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut writer: csv::Writer<io::Stdout> = csv::Writer::from_writer(io::stdout());
+    write_csv_row(&mut writer, ["cluster", "hash", "link", "conflicting"]);
+    for (index, cluster) in clusters.iter().enumerate() {
+        let (number, conflicting) = (
+            (index + 1).to_string(),
+            cluster.has_conflicting_links().to_string(),
+        );
+        for entry in &cluster.entries {
+            write_csv_row(
+                &mut writer,
+                [number.as_str(), &entry.hash, &entry.link, &conflicting],
+            );
+        }
+    }
+    if let Err(e) = writer.flush() {
+        eprintln!("Failed to write clusters: {}", e);
+        process::exit(1);
+    }
+
+    let conflicting: usize = clusters
+        .iter()
+        .filter(|cluster| cluster.has_conflicting_links())
+        .count();
+    eprintln!(
+        "{} clusters of near-duplicates, {} with conflicting links",
+        clusters.len(),
+        conflicting
+    );
+}
+
+fn run_dedupe(program: &str, args: &[String]) {
+    let mut args: Vec<String> = args.to_vec();
+    let policy: DuplicatePolicy = take_option(&mut args, "--policy")
+        .map_or(DuplicatePolicy::default(), |policy| {
+            parse_duplicate_policy(&policy)
+        });
+    let output_path: Option<String> = take_option(&mut args, "--output");
+
+    let Some(dict_path) = args.first().cloned() else {
+        print_usage(program);
+        process::exit(1);
+    };
+
+    // CSV rows are written back as they are, with all their columns, unless the output
+    // has another format or layout
+    let output_format: DictionaryFormat =
+        DictionaryFormat::from_path(output_path.as_deref().unwrap_or(&dict_path));
+    let keep_records: bool = DictionaryFormat::from_path(&dict_path) == DictionaryFormat::Csv
+        && output_format == DictionaryFormat::Csv
+        && output_path.as_ref().is_none_or(|path| {
+            DictionarySchema::for_path(path) == DictionarySchema::for_path(&dict_path)
+        });
+
+    let mut deduped_csv: Vec<u8> = Vec::new();
+    let report: DedupeReport = if keep_records {
+        dedupe_csv_file(dict_path.clone(), &mut deduped_csv, policy)
+    } else {
+        load_dictionary(dict_path.clone()).and_then(|links| dedupe_entries(&links, policy))
+    }
+    .unwrap_or_else(|e| exit_with_error(e));
+
+    for conflict in &report.conflicts {
+        eprintln!(
+            "Conflict: {} points to {}, kept {}",
+            conflict.hash,
+            conflict.links.join(", "),
+            conflict.kept
+        );
+    }
+
+    // Write in the format of the output file, or of the dictionary on stdout
+    let written: Result<(), String> = match (&output_path, keep_records) {
+        (Some(path), true) => std::fs::write(path, &deduped_csv)
+            .map_err(|e| format!("Failed to write file {}: {}", path, e)),
+        (None, true) => io::stdout()
+            .write_all(&deduped_csv)
+            .map_err(|e| format!("Failed to write results: {}", e)),
+        (Some(path), false) => save_dictionary(&report.entries, path.clone()),
+        (None, false) => write_dictionary_with_format(
+            &report.entries,
+            io::stdout(),
+            DictionaryFormat::from_path(&dict_path),
+            &DictionarySchema::for_path(&dict_path),
+        ),
+    };
+    if let Err(e) = written {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+
+    eprintln!(
+        "{} entries kept, {} identical rows merged, {} conflicts",
+        report.entries.len(),
+        report.merged,
+        report.conflicts.len()
+    );
+}
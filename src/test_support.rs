@@ -0,0 +1,24 @@
+//! Helpers shared by the unit tests.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A path of the temporary directory that no other test uses, even in concurrent test
+/// runs: the file name is prefixed with the process id and a counter
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "img_hash_linker_{}_{}_{}",
+        std::process::id(),
+        NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        name
+    ))
+}
+
+/// Writes the contents to a new CSV file of the temporary directory, see [`temp_path`]
+pub(crate) fn temp_csv(name: &str, contents: &str) -> String {
+    let path: PathBuf = temp_path(&format!("{}.csv", name));
+    std::fs::write(&path, contents).unwrap();
+    path.display().to_string()
+}